@@ -1,11 +1,13 @@
 const loginLink = document.getElementById("login-link");
 const logoutLink = document.getElementById("logout-link");
+const logoutAllLink = document.getElementById("logout-all-link");
 const protectImg = document.getElementById("protected-img");
 
-logoutLink.addEventListener("click", (e) => {
+// Both log out links work the same way, /logout-all just also ends the sessions on other devices
+[logoutLink, logoutAllLink].forEach(link => link.addEventListener("click", (e) => {
     e.preventDefault();
 
-    let url = logoutLink.href;
+    let url = link.href;
 
     fetch(url, {
         method: 'POST',
@@ -14,18 +16,30 @@ logoutLink.addEventListener("click", (e) => {
         if (response.ok) {
             loginLink.style.display = "block";
             logoutLink.style.display = "none";
+            logoutAllLink.style.display = "none";
             protectImg.src = "/assets/default.jpg";
         } else {
             alert("Failed to logout");
         }
     });
-});
+}));
 
 (() => {
     fetch('/protected').then(response => {
+        if (response.status !== 401) {
+            return response;
+        }
+
+        // The JWT is short-lived, so try to exchange the refresh token for a new one before giving up
+        return fetch(new URL('/refresh', loginLink.href), {
+            method: 'POST',
+            credentials: 'include', // This will include cookies in the request
+        }).then(refreshResponse => refreshResponse.ok ? fetch('/protected') : response);
+    }).then(response => {
         if (response.ok) {
             loginLink.style.display = "none";
             logoutLink.style.display = "block";
+            logoutAllLink.style.display = "block";
 
             response.json().then(data => {
                 let img_url = data.img_url;
@@ -38,6 +52,7 @@ logoutLink.addEventListener("click", (e) => {
         } else {
             loginLink.style.display = "block";
             logoutLink.style.display = "none";
+            logoutAllLink.style.display = "none";
             protectImg.src = "/assets/default.jpg";
         }
     });
@@ -2,13 +2,13 @@ use std::env;
 
 use askama::Template;
 use axum::{
-    http::StatusCode,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
     response::{Html, IntoResponse},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tower_http::services::ServeDir;
 
 #[tokio::main]
@@ -29,6 +29,7 @@ async fn main() {
 struct IndexTemplate {
     login_link: String,
     logout_link: String,
+    logout_all_link: String,
 }
 
 async fn root() -> impl IntoResponse {
@@ -38,18 +39,27 @@ async fn root() -> impl IntoResponse {
     }
     let login_link = format!("http://{}:3000", address);
     let logout_link = format!("http://{}:3000/logout", address);
+    let logout_all_link = format!("http://{}:3000/logout-all", address);
 
     let template = IndexTemplate {
         login_link,
         logout_link,
+        logout_all_link,
     };
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
-        Some(cookie) => cookie,
-        None => {
+// Browsers send the jwt cookie, scripts send a personal API key as a bearer token
+async fn protected(jar: CookieJar, headers: HeaderMap) -> impl IntoResponse {
+    let bearer_token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let token = match (bearer_token, jar.get("jwt")) {
+        (Some(token), _) => token.to_owned(),
+        (None, Some(cookie)) => cookie.value().to_owned(),
+        (None, None) => {
             return StatusCode::UNAUTHORIZED.into_response();
         }
     };
@@ -57,11 +67,12 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
     let api_client = reqwest::Client::builder().build().unwrap();
 
     let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
+        "token": &token,
     });
 
     let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+    // Ask for the introspection body, so we know who is calling
+    let url = format!("http://{}:3000/verify-token?introspect=true", auth_hostname);
 
     let response = match api_client.post(&url).json(&verify_token_body).send().await {
         Ok(response) => response,
@@ -74,10 +85,22 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
         reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
             StatusCode::UNAUTHORIZED.into_response()
         }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
-        })
-        .into_response(),
+        reqwest::StatusCode::OK => {
+            let caller = match response.json::<VerifiedCaller>().await {
+                Ok(caller) => caller,
+                Err(_) => {
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+
+            Json(ProtectedRouteResponse {
+                img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png"
+                    .to_owned(),
+                sub: caller.sub,
+                mfa: caller.mfa,
+            })
+            .into_response()
+        }
         _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -85,4 +108,14 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mfa: Option<bool>,
+}
+
+// The part of the auth service's introspection body we use
+#[derive(Deserialize)]
+struct VerifiedCaller {
+    sub: String,
+    mfa: Option<bool>,
 }
@@ -1,10 +1,42 @@
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
+const passwordResetRequestSection = document.getElementById("password-reset-request-section");
+const passwordResetConfirmSection = document.getElementById("password-reset-confirm-section");
+const consentSection = document.getElementById("consent-section");
 
 const signupLink = document.getElementById("signup-link");
 const twoFALoginLink = document.getElementById("2fa-login-link");
 const signupLoginLink = document.getElementById("signup-login-link");
+const forgotPasswordLink = document.getElementById("forgot-password-link");
+const passwordResetRequestLoginLink = document.getElementById("password-reset-request-login-link");
+
+function showSection(section) {
+    for (const s of [loginSection, twoFASection, signupSection, passwordResetRequestSection, passwordResetConfirmSection, consentSection]) {
+        s.style.display = s === section ? "block" : "none";
+    }
+}
+
+// /authorize sends users who aren't logged in here, with its query string to come back with
+const oauthAuthorize = new URLSearchParams(window.location.search).get("oauth_authorize");
+
+function showLoggedIn(message) {
+    if (oauthAuthorize) {
+        window.location = "/authorize?" + oauthAuthorize;
+    } else {
+        alert(message);
+    }
+}
+
+forgotPasswordLink.addEventListener("click", (e) => {
+    e.preventDefault();
+    showSection(passwordResetRequestSection);
+});
+
+passwordResetRequestLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+    showSection(loginSection);
+});
 
 signupLink.addEventListener("click", (e) => {
     e.preventDefault();
@@ -53,6 +85,12 @@ loginButton.addEventListener("click", (e) => {
             TwoFAForm.email.value = email;
             response.json().then(data => {
                 TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+                document.getElementById("2fa-hint").innerText = {
+                    totp: "Enter the code shown in your authenticator app.",
+                    webauthn: "Use your passkey, or enter a recovery code.",
+                }[data["2FAMethod"]] || "Enter the code we emailed you.";
+                TwoFAPasskeyButton.style.display = data["2FAMethod"] === "webauthn" ? "block" : "none";
+                TwoFAResend.style.display = data["2FAMethod"] === "email" ? "block" : "none";
             });
 
             loginForm.email.value = "";
@@ -66,7 +104,13 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            response.json().then(data => {
+                let message = "You have successfully logged in.";
+                if (data.warning) {
+                    message += "\n\n" + data.warning;
+                }
+                showLoggedIn(message);
+            });
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -125,6 +169,7 @@ signupButton.addEventListener("click", (e) => {
 const TwoFAForm = document.getElementById("2fa-form");
 const TwoFAButton = document.getElementById("2fa-form-submit");
 const TwoFAErrAlter = document.getElementById("2fa-err-alert");
+const TwoFAResend = document.getElementById("2fa-resend");
 
 TwoFAButton.addEventListener("click", (e) => {
     e.preventDefault();
@@ -145,10 +190,21 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            showLoggedIn("You have successfully logged in.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
+        } else if (response.status === 410) {
+            // Too many wrong codes ended the login attempt, a new login sends a new code
+            response.json().then(data => {
+                TwoFAForm.email_code.value = "";
+                TwoFAForm.login_attempt_id.value = "";
+                TwoFAErrAlter.style.display = "none";
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+                loginSection.style.display = "block";
+                twoFASection.style.display = "none";
+            });
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -161,4 +217,353 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+const TwoFAResendLink = document.getElementById("2fa-resend-link");
+
+TwoFAResendLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = TwoFAForm.email.value;
+    const loginAttemptId = TwoFAForm.login_attempt_id.value;
+
+    fetch('/resend-2fa', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, loginAttemptId }),
+    }).then(response => {
+        if (response.ok) {
+            TwoFAErrAlter.style.display = "none";
+            document.getElementById("2fa-hint").innerText = "We emailed you a new code.";
+        } else {
+            response.json().then(data => {
+                TwoFAErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                TwoFAErrAlter.style.display = "block";
+            });
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+// The WebAuthn API works with ArrayBuffers, the auth service with base64url strings
+function base64urlToBuffer(value) {
+    const base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+    return Uint8Array.from(atob(base64), c => c.charCodeAt(0)).buffer;
+}
+
+function bufferToBase64url(buffer) {
+    const base64 = btoa(String.fromCharCode(...new Uint8Array(buffer)));
+    return base64.replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+}
+
+// Runs navigator.credentials.get() with options from /webauthn/login/start,
+// and resolves to the response of /webauthn/login/finish
+async function passkeyLogin(startBody) {
+    const startResponse = await fetch('/webauthn/login/start', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(startBody),
+    });
+    if (!startResponse.ok) {
+        return startResponse;
+    }
+
+    const { publicKey } = await startResponse.json();
+    publicKey.challenge = base64urlToBuffer(publicKey.challenge);
+    publicKey.allowCredentials = publicKey.allowCredentials.map(c => ({ ...c, id: base64urlToBuffer(c.id) }));
+
+    const credential = await navigator.credentials.get({ publicKey });
+
+    return fetch('/webauthn/login/finish', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({
+            credential: {
+                id: credential.id,
+                rawId: bufferToBase64url(credential.rawId),
+                type: credential.type,
+                response: {
+                    clientDataJSON: bufferToBase64url(credential.response.clientDataJSON),
+                    authenticatorData: bufferToBase64url(credential.response.authenticatorData),
+                    signature: bufferToBase64url(credential.response.signature),
+                },
+            },
+        }),
+    });
+}
+
+function showPasskeyLoginResult(response, errAlert) {
+    response.json().then(data => {
+        if (response.ok) {
+            errAlert.style.display = "none";
+            let message = "You have successfully logged in.";
+            if (data.warning) {
+                message += "\n\n" + data.warning;
+            }
+            showLoggedIn(message);
+            showSection(loginSection);
+        } else {
+            errAlert.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+            errAlert.style.display = "block";
+        }
+    });
+}
+
+const passkeyLoginButton = document.getElementById("passkey-login-button");
+
+passkeyLoginButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+
+    passkeyLogin(email ? { email } : {})
+        .then(response => showPasskeyLoginResult(response, loginErrAlter))
+        .catch(() => {
+            loginErrAlter.innerHTML = "<span><strong>Error: </strong>Passkey login was cancelled</span>";
+            loginErrAlter.style.display = "block";
+        });
+});
+
+const TwoFAPasskeyButton = document.getElementById("2fa-passkey-button");
+
+TwoFAPasskeyButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = TwoFAForm.email.value;
+    const loginAttemptId = TwoFAForm.login_attempt_id.value;
+
+    passkeyLogin({ email, loginAttemptId })
+        .then(response => showPasskeyLoginResult(response, TwoFAErrAlter))
+        .catch(() => {
+            TwoFAErrAlter.innerHTML = "<span><strong>Error: </strong>Passkey verification was cancelled</span>";
+            TwoFAErrAlter.style.display = "block";
+        });
+});
+
+const magicLinkButton = document.getElementById("magic-link-button");
+
+magicLinkButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+
+    fetch('/login/magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            loginErrAlter.style.display = "none";
+            alert("If magic link login is enabled for this account, we have sent a login link to your email.");
+        } else {
+            response.json().then(data => {
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+            });
+        }
+    });
+});
+
+// The magic link email links back here with the token in the query string. It is only
+// exchanged by this POST, so link scanners that merely open the link can't use it up.
+const magicLinkToken = new URLSearchParams(window.location.search).get("magic_link_token");
+if (magicLinkToken) {
+    window.history.replaceState(null, "", window.location.pathname);
+
+    fetch('/login/magic-link/callback', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: magicLinkToken }),
+    }).then(response => {
+        if (response.status === 206) {
+            // The 2FA form needs the email, which is the subject of the token
+            const claims = JSON.parse(atob(magicLinkToken.split(".")[1].replace(/-/g, "+").replace(/_/g, "/")));
+            TwoFAForm.email.value = claims.sub;
+            response.json().then(data => {
+                TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+                document.getElementById("2fa-hint").innerText = {
+                    totp: "Enter the code shown in your authenticator app.",
+                    webauthn: "Use your passkey, or enter a recovery code.",
+                }[data["2FAMethod"]] || "Enter the code we emailed you.";
+                TwoFAPasskeyButton.style.display = data["2FAMethod"] === "webauthn" ? "block" : "none";
+                TwoFAResend.style.display = data["2FAMethod"] === "email" ? "block" : "none";
+            });
+            showSection(twoFASection);
+        } else if (response.ok) {
+            showLoggedIn("You have successfully logged in.");
+        } else {
+            alert("This login link is invalid, has expired or was already used.");
+        }
+    });
+}
+
+// -----------------------------------------------------
+
+const passwordResetRequestForm = document.getElementById("password-reset-request-form");
+const passwordResetRequestButton = document.getElementById("password-reset-request-form-submit");
+const passwordResetRequestErrAlert = document.getElementById("password-reset-request-err-alert");
+
+passwordResetRequestButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = passwordResetRequestForm.email.value;
+
+    fetch('/password-reset/request', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            passwordResetRequestForm.email.value = "";
+            passwordResetRequestErrAlert.style.display = "none";
+            alert("If an account exists for this email, we have sent a password reset link to it.");
+            showSection(loginSection);
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    passwordResetRequestErrAlert.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    passwordResetRequestErrAlert.style.display = "block";
+                } else {
+                    passwordResetRequestErrAlert.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+const passwordResetConfirmForm = document.getElementById("password-reset-confirm-form");
+const passwordResetConfirmButton = document.getElementById("password-reset-confirm-form-submit");
+const passwordResetConfirmErrAlert = document.getElementById("password-reset-confirm-err-alert");
+
+// The password reset email links back here with the token in the query string
+const passwordResetToken = new URLSearchParams(window.location.search).get("password_reset_token");
+if (passwordResetToken) {
+    passwordResetConfirmForm.token.value = passwordResetToken;
+    window.history.replaceState(null, "", window.location.pathname);
+    showSection(passwordResetConfirmSection);
+}
+
+passwordResetConfirmButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const token = passwordResetConfirmForm.token.value;
+    const newPassword = passwordResetConfirmForm.password.value;
+
+    fetch('/password-reset/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            passwordResetConfirmForm.token.value = "";
+            passwordResetConfirmForm.password.value = "";
+            passwordResetConfirmErrAlert.style.display = "none";
+            alert("Your password has been changed. Please log in again.");
+            showSection(loginSection);
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    passwordResetConfirmErrAlert.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    passwordResetConfirmErrAlert.style.display = "block";
+                } else {
+                    passwordResetConfirmErrAlert.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+// The verification email links back here with the token in the query string
+const emailVerificationToken = new URLSearchParams(window.location.search).get("email_verification_token");
+if (emailVerificationToken) {
+    window.history.replaceState(null, "", window.location.pathname);
+
+    fetch('/verify-email', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: emailVerificationToken }),
+    }).then(response => {
+        if (response.ok) {
+            alert("Your email address has been verified.");
+        } else {
+            alert("This verification link is invalid or has expired.");
+        }
+    });
+}
+
+// The email sent when an account gets locked links back here with the token in the query string
+const unlockToken = new URLSearchParams(window.location.search).get("unlock_token");
+if (unlockToken) {
+    window.history.replaceState(null, "", window.location.pathname);
+
+    fetch('/unlock-account', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: unlockToken }),
+    }).then(response => {
+        if (response.ok) {
+            alert("Your account has been unlocked, you can log in again.");
+        } else {
+            alert("This unlock link is invalid, has expired or was already used.");
+        }
+    });
+}
+
+// -----------------------------------------------------
+
+// /authorize sends users here the first time a client asks for access to their account
+const oauthConsent = new URLSearchParams(window.location.search).get("oauth_consent");
+if (oauthConsent) {
+    const authorizeParams = Object.fromEntries(new URLSearchParams(oauthConsent));
+    const clientName = new URLSearchParams(window.location.search).get("client_name");
+
+    document.getElementById("consent-client-name").innerText = clientName;
+    document.getElementById("consent-scope").innerText = authorizeParams.scope || "your account";
+    showSection(consentSection);
+
+    const consentErrAlert = document.getElementById("consent-err-alert");
+
+    for (const [buttonId, approved] of [["consent-allow-button", true], ["consent-deny-button", false]]) {
+        document.getElementById(buttonId).addEventListener("click", (e) => {
+            e.preventDefault();
+
+            fetch('/authorize/consent', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({ ...authorizeParams, approved }),
+            }).then(response => {
+                response.json().then(data => {
+                    if (response.ok) {
+                        window.location = data.redirectTo;
+                    } else {
+                        consentErrAlert.innerHTML = `<span><strong>Error: </strong>${data.error_description || data.error}</span>`;
+                        consentErrAlert.style.display = "block";
+                    }
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   family_id TEXT NOT NULL,
+   expires_at BIGINT NOT NULL,
+   used BOOLEAN NOT NULL DEFAULT FALSE,
+   revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens(family_id);
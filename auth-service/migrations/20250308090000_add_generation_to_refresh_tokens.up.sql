@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS generation BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Accounts created before email verification existed are trusted as they are
+UPDATE users SET verified = TRUE;
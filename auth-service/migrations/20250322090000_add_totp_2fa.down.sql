@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS totp_secrets;
+
+ALTER TABLE users DROP COLUMN IF EXISTS two_fa_method;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'email';
+
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   encrypted_secret BYTEA NOT NULL,
+   confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+   last_used_step BIGINT
+);
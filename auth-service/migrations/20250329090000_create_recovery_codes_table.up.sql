@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS recovery_codes(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   code_hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_email_idx ON recovery_codes(email);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS webauthn_credentials(
+   credential_id BYTEA NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   public_key BYTEA NOT NULL,
+   sign_count BIGINT NOT NULL DEFAULT 0,
+   attestation_format TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_credentials_email_idx ON webauthn_credentials(email);
@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   client_id TEXT NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   redirect_uris TEXT[] NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS oauth_consents(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   client_id TEXT NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+   scopes TEXT[] NOT NULL,
+   granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (email, client_id)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS amr;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS auth_time;
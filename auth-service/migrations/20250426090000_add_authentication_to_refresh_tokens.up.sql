@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS auth_time BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS amr TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS service_accounts(
+   id TEXT NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   secret_hash TEXT NOT NULL,
+   scopes TEXT[] NOT NULL,
+   disabled BOOLEAN NOT NULL DEFAULT FALSE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
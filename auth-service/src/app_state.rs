@@ -1,22 +1,150 @@
+use axum::extract::FromRef;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::services::data_stores::hashmap_two_fa_code_store;
-
-use crate::{domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore}};
+use crate::domain::{
+    AccountLockoutStore, ApiKeyStore, AuthorizationCodeStore, BannedTokenStore, CooldownStore, EmailClient, MagicLinkStore,
+    OAuthClientStore, OAuthConsentStore, PasswordResetTokenStore, RateLimit, RateLimitStore,
+    RecoveryCodeStore, RefreshTokenStore, ServiceAccountStore, SessionStore, SigningKeyStore, TotpStore,
+    TrustedProxies, TwoFACodeStore, UserStore, WebauthnChallengeStore, WebauthnCredentialStore,
+};
+use crate::utils::DEFAULT_MAX_TWO_FA_ATTEMPTS;
+use crate::services::data_stores::{
+    HashmapAccountLockoutStore, HashmapApiKeyStore, HashmapAuthorizationCodeStore, HashmapCooldownStore, HashmapMagicLinkStore,
+    HashmapOAuthClientStore, HashmapOAuthConsentStore, HashmapPasswordResetTokenStore,
+    HashmapRateLimitStore,
+    HashmapRefreshTokenStore, HashmapRecoveryCodeStore, HashmapServiceAccountStore,
+    HashmapSessionStore, HashmapSigningKeyStore,
+    HashmapTotpStore, HashmapWebauthnChallengeStore, HashmapWebauthnCredentialStore,
+};
 
 // Using a type alias to improve readability!
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
 pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type CooldownStoreType = Arc<RwLock<dyn CooldownStore + Send + Sync>>;
+pub type TotpStoreType = Arc<RwLock<dyn TotpStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type WebauthnCredentialStoreType = Arc<RwLock<dyn WebauthnCredentialStore + Send + Sync>>;
+pub type WebauthnChallengeStoreType = Arc<RwLock<dyn WebauthnChallengeStore + Send + Sync>>;
+pub type MagicLinkStoreType = Arc<RwLock<dyn MagicLinkStore + Send + Sync>>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type OAuthConsentStoreType = Arc<RwLock<dyn OAuthConsentStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
+pub type ServiceAccountStoreType = Arc<RwLock<dyn ServiceAccountStore + Send + Sync>>;
+pub type ApiKeyStoreType = Arc<RwLock<dyn ApiKeyStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type AccountLockoutStoreType = Arc<RwLock<dyn AccountLockoutStore + Send + Sync>>;
+pub type RateLimitStoreType = Arc<RwLock<dyn RateLimitStore + Send + Sync>>;
+pub type SigningKeyStoreType = Arc<RwLock<dyn SigningKeyStore + Send + Sync>>;
+
+// What `login` does with users that have not verified their email address yet
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnverifiedLoginPolicy {
+    // Reject the login until the email address is verified
+    Block,
+    // Log the user in, but include a warning in the response
+    #[default]
+    Warn,
+}
+
+impl std::str::FromStr for UnverifiedLoginPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "warn" => Ok(Self::Warn),
+            _ => Err(format!("unknown unverified login policy: {}", s)),
+        }
+    }
+}
+
+// Caps how many sessions a user can have at once, see `routes::start_session`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SessionLimit {
+    pub max_sessions: usize,
+    pub policy: SessionLimitPolicy,
+}
+
+// What happens to a login that would exceed the user's session limit
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SessionLimitPolicy {
+    // Reject the login until the user ends one of their sessions
+    #[default]
+    Reject,
+    // Log the user in and end their oldest session
+    EvictOldest,
+}
+
+impl std::str::FromStr for SessionLimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "evict_oldest" => Ok(Self::EvictOldest),
+            _ => Err(format!("unknown session limit policy: {}", s)),
+        }
+    }
+}
+
+// Request limits of the throttled routes, see `utils::rate_limit`. Each route has its own
+// buckets per client IP and per target email address.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimits {
+    pub per_ip: RateLimit,
+    pub per_email: RateLimit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            per_ip: RateLimit {
+                burst: 20,
+                refill_interval_seconds: 3,
+            },
+            per_email: RateLimit {
+                burst: 10,
+                refill_interval_seconds: 30,
+            },
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    // Wrong 2FA codes after which the login attempt ends
+    pub max_two_fa_attempts: u32,
     pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub cooldown_store: CooldownStoreType,
+    pub unverified_login_policy: UnverifiedLoginPolicy,
+    pub totp_store: TotpStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub webauthn_credential_store: WebauthnCredentialStoreType,
+    pub webauthn_challenge_store: WebauthnChallengeStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub oauth_consent_store: OAuthConsentStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    pub service_account_store: ServiceAccountStoreType,
+    pub api_key_store: ApiKeyStoreType,
+    pub session_store: SessionStoreType,
+    // Users can have any number of sessions unless set
+    pub session_limit: Option<SessionLimit>,
+    pub account_lockout_store: AccountLockoutStoreType,
+    pub rate_limit_store: RateLimitStoreType,
+    pub rate_limits: RateLimits,
+    pub trusted_proxies: TrustedProxies,
+    pub signing_key_store: SigningKeyStoreType,
 }
 
 impl AppState {
@@ -25,6 +153,183 @@ impl AppState {
         banned_token_store: BannedTokenStoreType, 
         two_fa_code_store: TwoFACodeStoreType, 
         email_client: EmailClientType) -> Self {
-        Self { user_store, banned_token_store, two_fa_code_store, email_client }
+        Self {
+            user_store,
+            banned_token_store,
+            two_fa_code_store,
+            max_two_fa_attempts: DEFAULT_MAX_TWO_FA_ATTEMPTS,
+            email_client,
+            // Optional stores default to in-memory implementations.
+            // Use the `with_*` methods below to plug in persistent ones.
+            refresh_token_store: Arc::new(RwLock::new(HashmapRefreshTokenStore::default())),
+            password_reset_token_store: Arc::new(RwLock::new(
+                HashmapPasswordResetTokenStore::default(),
+            )),
+            cooldown_store: Arc::new(RwLock::new(HashmapCooldownStore::default())),
+            unverified_login_policy: UnverifiedLoginPolicy::default(),
+            totp_store: Arc::new(RwLock::new(HashmapTotpStore::default())),
+            recovery_code_store: Arc::new(RwLock::new(HashmapRecoveryCodeStore::default())),
+            webauthn_credential_store: Arc::new(RwLock::new(
+                HashmapWebauthnCredentialStore::default(),
+            )),
+            webauthn_challenge_store: Arc::new(RwLock::new(
+                HashmapWebauthnChallengeStore::default(),
+            )),
+            magic_link_store: Arc::new(RwLock::new(HashmapMagicLinkStore::default())),
+            oauth_client_store: Arc::new(RwLock::new(HashmapOAuthClientStore::default())),
+            oauth_consent_store: Arc::new(RwLock::new(HashmapOAuthConsentStore::default())),
+            authorization_code_store: Arc::new(RwLock::new(
+                HashmapAuthorizationCodeStore::default(),
+            )),
+            service_account_store: Arc::new(RwLock::new(HashmapServiceAccountStore::default())),
+            api_key_store: Arc::new(RwLock::new(HashmapApiKeyStore::default())),
+            session_store: Arc::new(RwLock::new(HashmapSessionStore::default())),
+            session_limit: None,
+            account_lockout_store: Arc::new(RwLock::new(HashmapAccountLockoutStore::default())),
+            rate_limit_store: Arc::new(RwLock::new(HashmapRateLimitStore::default())),
+            rate_limits: RateLimits::default(),
+            trusted_proxies: TrustedProxies::default(),
+            signing_key_store: Arc::new(RwLock::new(HashmapSigningKeyStore::default())),
+        }
+    }
+
+    pub fn with_max_two_fa_attempts(mut self, max_two_fa_attempts: u32) -> Self {
+        self.max_two_fa_attempts = max_two_fa_attempts;
+        self
+    }
+
+    pub fn with_email_client(mut self, email_client: EmailClientType) -> Self {
+        self.email_client = email_client;
+        self
+    }
+
+    pub fn with_refresh_token_store(mut self, refresh_token_store: RefreshTokenStoreType) -> Self {
+        self.refresh_token_store = refresh_token_store;
+        self
+    }
+
+    pub fn with_password_reset_token_store(
+        mut self,
+        password_reset_token_store: PasswordResetTokenStoreType,
+    ) -> Self {
+        self.password_reset_token_store = password_reset_token_store;
+        self
+    }
+
+    pub fn with_cooldown_store(mut self, cooldown_store: CooldownStoreType) -> Self {
+        self.cooldown_store = cooldown_store;
+        self
+    }
+
+    pub fn with_unverified_login_policy(mut self, policy: UnverifiedLoginPolicy) -> Self {
+        self.unverified_login_policy = policy;
+        self
+    }
+
+    pub fn with_totp_store(mut self, totp_store: TotpStoreType) -> Self {
+        self.totp_store = totp_store;
+        self
+    }
+
+    pub fn with_recovery_code_store(mut self, recovery_code_store: RecoveryCodeStoreType) -> Self {
+        self.recovery_code_store = recovery_code_store;
+        self
+    }
+
+    pub fn with_webauthn_credential_store(
+        mut self,
+        webauthn_credential_store: WebauthnCredentialStoreType,
+    ) -> Self {
+        self.webauthn_credential_store = webauthn_credential_store;
+        self
+    }
+
+    pub fn with_webauthn_challenge_store(
+        mut self,
+        webauthn_challenge_store: WebauthnChallengeStoreType,
+    ) -> Self {
+        self.webauthn_challenge_store = webauthn_challenge_store;
+        self
+    }
+
+    pub fn with_magic_link_store(mut self, magic_link_store: MagicLinkStoreType) -> Self {
+        self.magic_link_store = magic_link_store;
+        self
+    }
+
+    pub fn with_oauth_client_store(mut self, oauth_client_store: OAuthClientStoreType) -> Self {
+        self.oauth_client_store = oauth_client_store;
+        self
     }
-}
\ No newline at end of file
+
+    pub fn with_oauth_consent_store(mut self, oauth_consent_store: OAuthConsentStoreType) -> Self {
+        self.oauth_consent_store = oauth_consent_store;
+        self
+    }
+
+    pub fn with_authorization_code_store(
+        mut self,
+        authorization_code_store: AuthorizationCodeStoreType,
+    ) -> Self {
+        self.authorization_code_store = authorization_code_store;
+        self
+    }
+
+    pub fn with_service_account_store(
+        mut self,
+        service_account_store: ServiceAccountStoreType,
+    ) -> Self {
+        self.service_account_store = service_account_store;
+        self
+    }
+
+    pub fn with_api_key_store(mut self, api_key_store: ApiKeyStoreType) -> Self {
+        self.api_key_store = api_key_store;
+        self
+    }
+
+    pub fn with_session_store(mut self, session_store: SessionStoreType) -> Self {
+        self.session_store = session_store;
+        self
+    }
+
+    pub fn with_session_limit(mut self, session_limit: SessionLimit) -> Self {
+        self.session_limit = Some(session_limit);
+        self
+    }
+
+    pub fn with_account_lockout_store(
+        mut self,
+        account_lockout_store: AccountLockoutStoreType,
+    ) -> Self {
+        self.account_lockout_store = account_lockout_store;
+        self
+    }
+
+    pub fn with_rate_limit_store(mut self, rate_limit_store: RateLimitStoreType) -> Self {
+        self.rate_limit_store = rate_limit_store;
+        self
+    }
+
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    pub fn with_trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    pub fn with_signing_key_store(mut self, signing_key_store: SigningKeyStoreType) -> Self {
+        self.signing_key_store = signing_key_store;
+        self
+    }
+}
+
+// Lets extractors such as `ClientInfo` resolve client addresses
+impl FromRef<AppState> for TrustedProxies {
+    fn from_ref(state: &AppState) -> Self {
+        state.trusted_proxies.clone()
+    }
+}
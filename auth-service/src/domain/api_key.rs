@@ -0,0 +1,160 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, Result};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{Email, Scope};
+
+// Makes API keys recognizable, both for `verify_token` and for secret scanners
+pub const API_KEY_PREFIX: &str = "ak_";
+
+// How much of a key is kept in the clear, so users can tell their keys apart when listing them
+const API_KEY_DISPLAY_LENGTH: usize = 8;
+
+const API_KEY_LENGTH: usize = 32;
+
+// Keys that expire can be valid for at most a year
+pub const MAX_API_KEY_LIFETIME_DAYS: u32 = 365;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKeyId(String);
+
+impl ApiKeyId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(&id).wrap_err("Invalid API key id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for ApiKeyId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for ApiKeyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A personal API key, only shown to the user when it is created
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn parse(key: String) -> Result<Self> {
+        let encoded = key
+            .strip_prefix(API_KEY_PREFIX)
+            .ok_or(eyre!("Invalid API key"))?;
+
+        match URL_SAFE_NO_PAD.decode(encoded) {
+            Ok(bytes) if bytes.len() == API_KEY_LENGTH => Ok(Self(key)),
+            _ => Err(eyre!("Invalid API key")),
+        }
+    }
+
+    // Like refresh tokens, API keys are only stored hashed
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+
+    pub fn display_prefix(&self) -> String {
+        self.0
+            .chars()
+            .take(API_KEY_PREFIX.len() + API_KEY_DISPLAY_LENGTH)
+            .collect()
+    }
+}
+
+impl Default for ApiKey {
+    fn default() -> Self {
+        let mut bytes = [0u8; API_KEY_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(format!("{}{}", API_KEY_PREFIX, URL_SAFE_NO_PAD.encode(bytes)))
+    }
+}
+
+impl AsRef<str> for ApiKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What is stored about an API key. It acts for the user that created it, limited to `scope`
+// unless the scope is empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKeyRecord {
+    pub id: ApiKeyId,
+    pub email: Email,
+    pub name: String,
+    pub key_hash: String,
+    pub prefix: String,
+    pub scope: Scope,
+    // Unix timestamps (seconds)
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+}
+
+impl ApiKeyRecord {
+    pub fn new(
+        key: &ApiKey,
+        email: Email,
+        name: String,
+        scope: Scope,
+        expires_at: Option<i64>,
+    ) -> Self {
+        Self {
+            id: ApiKeyId::default(),
+            email,
+            name,
+            key_hash: key.hash(),
+            prefix: key.display_prefix(),
+            scope,
+            created_at: Utc::now().timestamp(),
+            expires_at,
+            last_used_at: None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_parse() {
+        let key = ApiKey::default();
+
+        assert!(key.as_ref().starts_with(API_KEY_PREFIX));
+        assert_eq!(ApiKey::parse(key.as_ref().to_owned()).unwrap(), key);
+        assert_eq!(key.display_prefix().len(), API_KEY_PREFIX.len() + 8);
+        assert!(key.as_ref().starts_with(&key.display_prefix()));
+
+        let without_prefix = key.as_ref().trim_start_matches(API_KEY_PREFIX).to_owned();
+        assert!(ApiKey::parse(without_prefix).is_err());
+        assert!(ApiKey::parse("ak_short".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_api_key_record_expiry() {
+        let key = ApiKey::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let now = Utc::now().timestamp();
+
+        let record = ApiKeyRecord::new(&key, email.clone(), "CI".to_owned(), Scope::default(), None);
+        assert!(!record.is_expired());
+        assert_eq!(record.key_hash, key.hash());
+
+        let record = ApiKeyRecord::new(&key, email, "CI".to_owned(), Scope::default(), Some(now));
+        assert!(record.is_expired());
+    }
+}
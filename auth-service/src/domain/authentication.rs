@@ -0,0 +1,99 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+
+// How the user proved who they are, published as the `amr` claim of ID tokens, see RFC 8176
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthMethod {
+    Password,
+    // Emailed 2FA codes, TOTP codes, recovery codes and magic links
+    OneTimePassword,
+    // Passkeys and security keys
+    HardwareKey,
+    // Added when a second factor was verified on top of the first one
+    MultiFactor,
+}
+
+impl AuthMethod {
+    pub fn parse(method: &str) -> Result<Self> {
+        match method {
+            "pwd" => Ok(Self::Password),
+            "otp" => Ok(Self::OneTimePassword),
+            "hwk" => Ok(Self::HardwareKey),
+            "mfa" => Ok(Self::MultiFactor),
+            _ => Err(eyre!("Unknown authentication method: {}", method)),
+        }
+    }
+}
+
+impl AsRef<str> for AuthMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Password => "pwd",
+            Self::OneTimePassword => "otp",
+            Self::HardwareKey => "hwk",
+            Self::MultiFactor => "mfa",
+        }
+    }
+}
+
+// When and how the user logged in. It is carried over when tokens are refreshed, so it always
+// describes the login that started the session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Authentication {
+    // Unix timestamp (seconds). 0 for sessions started before this was recorded.
+    pub time: i64,
+    pub methods: Vec<AuthMethod>,
+}
+
+impl Authentication {
+    pub fn now(methods: &[AuthMethod]) -> Self {
+        Self {
+            time: Utc::now().timestamp(),
+            methods: methods.to_vec(),
+        }
+    }
+
+    // Unknown methods are skipped, so a method can be dropped without breaking old sessions
+    pub fn from_parts(time: i64, methods: &[String]) -> Self {
+        Self {
+            time,
+            methods: methods
+                .iter()
+                .filter_map(|method| AuthMethod::parse(method).ok())
+                .collect(),
+        }
+    }
+
+    pub fn method_names(&self) -> Vec<String> {
+        self.methods
+            .iter()
+            .map(|method| method.as_ref().to_owned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authentication_round_trips_method_names() {
+        let authentication = Authentication::now(&[
+            AuthMethod::Password,
+            AuthMethod::OneTimePassword,
+            AuthMethod::MultiFactor,
+        ]);
+
+        assert_eq!(authentication.method_names(), vec!["pwd", "otp", "mfa"]);
+        assert_eq!(
+            Authentication::from_parts(authentication.time, &authentication.method_names()),
+            authentication
+        );
+
+        let names = vec!["hwk".to_owned(), "retina".to_owned()];
+        assert_eq!(
+            Authentication::from_parts(0, &names).methods,
+            vec![AuthMethod::HardwareKey]
+        );
+    }
+}
@@ -1,11 +1,14 @@
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 use thiserror::Error;
 use color_eyre::eyre::{eyre, Context, Report, Result};
 
-use crate::routes::TwoFactorAuthResponse;
-
-use super::{Email, Password, User};
+use super::totp::constant_time_eq;
+use super::{
+    ApiKeyId, ApiKeyRecord, AttestationFormat, Authentication, AuthorizationCode, AuthorizationGrant, Email, OAuthClient, OAuthClientId,
+    Password, RateLimit, Scope, ServiceAccount, ServiceAccountId, Session, TwoFAMethod, User, WebauthnChallenge,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
@@ -14,13 +17,38 @@ pub trait UserStore: Send + Sync {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
 
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError>;
+
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+
+    // Turns on 2FA for the user, checked with the given method from the next login on
+    async fn enable_2fa(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError>;
+
+    // Opts the user in or out of logging in through /login/magic-link
+    async fn set_magic_link_enabled(&mut self, email: &Email, enabled: bool) -> Result<(), UserStoreError>;
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
+    // Tokens are banned by their `jti` until `expires_at` (Unix timestamp, seconds), when the
+    // token is rejected anyway: its `exp` plus the leeway, see `utils::auth::ban_expiry`. After
+    // that the ban can be forgotten.
+    async fn store_token(&mut self, jti: String, expires_at: i64) -> Result<(), BannedTokenStoreError>;
+
+    async fn token_exists(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
+
+    // Every token carries the user's token generation at the time it was issued.
+    // Bumping the generation bans all of the user's outstanding tokens at once.
+    async fn ban_user_tokens(&mut self, email: &Email) -> Result<u64, BannedTokenStoreError>;
+
+    async fn get_token_generation(&self, email: &Email) -> Result<u64, BannedTokenStoreError>;
 
-    async fn token_exists(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Bans every auth token of a session, see `Session`. Like `store_token`, the ban only has to
+    // last until the session's last auth token expires.
+    async fn ban_session(&mut self, session_id: String, expires_at: i64) -> Result<(), BannedTokenStoreError>;
+
+    async fn session_banned(&self, session_id: &str) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -53,7 +81,12 @@ impl PartialEq for UserStoreError {
     }
 }
 
-// This trait represents the interface all concrete 2FA code stores should implement
+// Pending 2FA login attempts a user can have at once, e.g. in different browsers. Starting
+// another one drops the oldest.
+pub const MAX_PENDING_LOGIN_ATTEMPTS: usize = 5;
+
+// This trait represents the interface all concrete 2FA code stores should implement.
+// Codes are stored per login attempt, users can have up to MAX_PENDING_LOGIN_ATTEMPTS.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
     async fn add_code(
@@ -63,18 +96,44 @@ pub trait TwoFACodeStore {
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
     
+    // Returns the user the login attempt belongs to along with its code
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError>;
+
+    // Counts a wrong code entered for the login attempt. The `max_attempts`th wrong code
+    // removes the code and fails with TooManyAttempts, the user has to log in again.
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+
+    // Replaces the login attempt's code with a new one and restarts its expiry. Wrong codes
+    // entered so far still count. Fails with TooManyResends once the code was replaced
+    // `max_resends` times.
+    async fn replace_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
 }
 
 #[derive(Debug, Error)]
 pub enum TwoFACodeStoreError {
     #[error("Login Attempt ID not found")]
     LoginAttemptIdNotFound,
+    #[error("Too many 2FA attempts")]
+    TooManyAttempts,
+    #[error("Too many 2FA code resends")]
+    TooManyResends,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -84,12 +143,14 @@ impl PartialEq for TwoFACodeStoreError {
         matches!(
             (self, other),
             (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                | (Self::TooManyAttempts, Self::TooManyAttempts)
+                | (Self::TooManyResends, Self::TooManyResends)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LoginAttemptId(String);
 
 impl LoginAttemptId {
@@ -126,6 +187,11 @@ impl TwoFACode {
             Err(eyre!("Invalid 2FA code")) // Updated!
         }
     }
+
+    // Compares in constant time, so response times don't reveal how many digits were right
+    pub fn verify(&self, code: &TwoFACode) -> bool {
+        constant_time_eq(&self.0, &code.0)
+    }
 }
 
 impl Default for TwoFACode {
@@ -142,4 +208,879 @@ impl AsRef<str> for TwoFACode {
     fn as_ref(&self) -> &str {
         &self.0
     }
-}
\ No newline at end of file
+}
+
+// This trait represents the interface all concrete refresh token stores should implement
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError>;
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+
+    // Marks a token as rotated. A used token must never be accepted again.
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+
+    // Revokes every token issued in the same family, including ones not yet used.
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self> {
+        if is_random_token(&token, REFRESH_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+
+    // Stores only ever see the SHA-256 of a refresh token, so a leaked store
+    // cannot be used to mint new access tokens.
+    pub fn hash(&self) -> String {
+        hash_token(&self.0)
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        Self(generate_random_token(REFRESH_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+// Every refresh token issued from a single login shares a family id,
+// which lets us revoke the whole chain when a rotated token is replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshTokenFamilyId(String);
+
+impl RefreshTokenFamilyId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid refresh token family id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for RefreshTokenFamilyId {
+    fn default() -> Self {
+        RefreshTokenFamilyId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for RefreshTokenFamilyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub email: Email,
+    pub family_id: RefreshTokenFamilyId,
+    // Unix timestamp (seconds)
+    pub expires_at: i64,
+    // The user's token generation when the token was issued, see `BannedTokenStore::ban_user_tokens`
+    pub generation: u64,
+    // The login that started the family, new auth tokens report it as their auth_time and amr
+    pub authentication: Authentication,
+    pub used: bool,
+    pub revoked: bool,
+}
+
+impl RefreshTokenRecord {
+    pub fn new(
+        email: Email,
+        family_id: RefreshTokenFamilyId,
+        expires_at: i64,
+        generation: u64,
+        authentication: Authentication,
+    ) -> Self {
+        Self {
+            email,
+            family_id,
+            expires_at,
+            generation,
+            authentication,
+            used: false,
+            revoked: false,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        chrono::Utc::now().timestamp() >= self.expires_at
+    }
+}
+
+// This trait represents the interface all concrete password reset token stores should implement
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+
+    // Removes the token and returns the email it was issued for, so it can only be used once.
+    // Expired tokens are reported as not found.
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self> {
+        if is_random_token(&token, PASSWORD_RESET_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid password reset token"))
+        }
+    }
+
+    // Like refresh tokens, reset tokens are only stored hashed
+    pub fn hash(&self) -> String {
+        hash_token(&self.0)
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(generate_random_token(PASSWORD_RESET_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 64;
+
+fn generate_random_token(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+fn is_random_token(token: &str, length: usize) -> bool {
+    token.len() == length && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// Tracks per-key cooldowns, e.g. the minimum time between two verification emails
+#[async_trait::async_trait]
+pub trait CooldownStore {
+    // Starts a cooldown of `seconds` for `key`. If one is already running it is left untouched
+    // and the seconds remaining are returned instead.
+    async fn start_cooldown(&mut self, key: &str, seconds: u64)
+        -> Result<Option<u64>, CooldownStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum CooldownStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[async_trait::async_trait]
+pub trait TotpStore {
+    // Stores a new secret awaiting confirmation, replacing any earlier unconfirmed one.
+    // Fails with AlreadyConfirmed once the user confirmed a secret.
+    async fn add_pending_secret(
+        &mut self,
+        email: &Email,
+        encrypted_secret: Vec<u8>,
+    ) -> Result<(), TotpStoreError>;
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecretRecord, TotpStoreError>;
+
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpStoreError>;
+
+    // Records that the code of time step `step` was used. Fails with CodeAlreadyUsed unless
+    // `step` is later than every step used before, so a code can't be replayed.
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError>;
+}
+
+// The secret is stored encrypted, see `encrypt_secret`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpSecretRecord {
+    pub encrypted_secret: Vec<u8>,
+    pub confirmed: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum TotpStoreError {
+    #[error("TOTP secret not found")]
+    SecretNotFound,
+    #[error("TOTP secret already confirmed")]
+    AlreadyConfirmed,
+    #[error("TOTP code already used")]
+    CodeAlreadyUsed,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TotpStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SecretNotFound, Self::SecretNotFound)
+                | (Self::AlreadyConfirmed, Self::AlreadyConfirmed)
+                | (Self::CodeAlreadyUsed, Self::CodeAlreadyUsed)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    // Replaces the user's recovery codes, the old set can no longer be used
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+
+    // Removes the matching code, so each code can only be used once
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+
+    // Puts back a code taken by `use_code`, e.g. when the login it was used for failed anyway
+    async fn restore_code(
+        &mut self,
+        email: &Email,
+        code: RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Two groups of five lowercase letters and digits, e.g. "k3x9q-7tmw2"
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    pub fn parse(code: String) -> Result<Self> {
+        // Users copy these from wherever they wrote them down, be lenient about case and spacing
+        let code = code.trim().to_ascii_lowercase();
+
+        let valid = code.len() == 2 * RECOVERY_CODE_GROUP_LENGTH + 1
+            && code.char_indices().all(|(i, c)| match i {
+                RECOVERY_CODE_GROUP_LENGTH => c == '-',
+                _ => c.is_ascii_lowercase() || c.is_ascii_digit(),
+            });
+
+        if valid {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect()
+        };
+
+        Self(format!("{}-{}", group(), group()))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Number of codes in a set, see the /2fa/recovery-codes route
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+#[async_trait::async_trait]
+pub trait WebauthnCredentialStore {
+    // Fails with CredentialAlreadyExists if the credential ID is already registered
+    async fn add_credential(
+        &mut self,
+        credential: WebauthnCredential,
+    ) -> Result<(), WebauthnCredentialStoreError>;
+
+    async fn get_credential(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<WebauthnCredential, WebauthnCredentialStoreError>;
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError>;
+
+    // Records the signature counter of the latest assertion. Fails with SignCountNotIncreased
+    // unless the counter went up, which hints at a cloned authenticator. Authenticators
+    // without a counter always report 0, which is fine as long as they never reported more.
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), WebauthnCredentialStoreError>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebauthnCredential {
+    pub credential_id: Vec<u8>,
+    pub email: Email,
+    // The ES256 public key as an uncompressed SEC1 point, see `CredentialPublicKey`
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+    pub attestation_format: AttestationFormat,
+}
+
+#[derive(Debug, Error)]
+pub enum WebauthnCredentialStoreError {
+    #[error("Credential already exists")]
+    CredentialAlreadyExists,
+    #[error("Credential not found")]
+    CredentialNotFound,
+    #[error("Signature counter did not increase")]
+    SignCountNotIncreased,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for WebauthnCredentialStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CredentialAlreadyExists, Self::CredentialAlreadyExists)
+                | (Self::CredentialNotFound, Self::CredentialNotFound)
+                | (Self::SignCountNotIncreased, Self::SignCountNotIncreased)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Challenges handed out for WebAuthn ceremonies that have not been completed yet
+#[async_trait::async_trait]
+pub trait WebauthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: WebauthnChallenge,
+        ceremony: WebauthnCeremony,
+    ) -> Result<(), WebauthnChallengeStoreError>;
+
+    // Removes the challenge, so each one can only be answered once
+    async fn take_challenge(
+        &mut self,
+        challenge: &WebauthnChallenge,
+    ) -> Result<WebauthnCeremony, WebauthnChallengeStoreError>;
+}
+
+// What a challenge was issued for
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebauthnCeremony {
+    // Adding a passkey to the account of a logged in user
+    Registration { email: Email },
+    // Logging in with a passkey. `login_attempt_id` is set when the passkey is the second
+    // factor of a password login, `email` whenever the user told us who they are.
+    Authentication {
+        email: Option<Email>,
+        login_attempt_id: Option<LoginAttemptId>,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum WebauthnChallengeStoreError {
+    #[error("Challenge not found")]
+    ChallengeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for WebauthnChallengeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ChallengeNotFound, Self::ChallengeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Tracks the magic login links that were sent and not used yet, see /login/magic-link
+#[async_trait::async_trait]
+pub trait MagicLinkStore {
+    // Replaces any link sent to the user earlier, only the latest one can be used
+    async fn add_link(&mut self, email: Email, link_id: MagicLinkId) -> Result<(), MagicLinkStoreError>;
+
+    // Removes the user's link. Fails with LinkNotFound unless `link_id` is the latest link
+    // sent to the user, and it hasn't expired or been used yet.
+    async fn use_link(&mut self, email: &Email, link_id: &MagicLinkId) -> Result<(), MagicLinkStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkStoreError {
+    #[error("Magic link not found")]
+    LinkNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LinkNotFound, Self::LinkNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Identifies a single magic link, it is embedded in the link's signed token
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagicLinkId(String);
+
+impl MagicLinkId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid magic link id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for MagicLinkId {
+    fn default() -> Self {
+        MagicLinkId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for MagicLinkId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Counts failed logins per user and locks accounts with too many of them, see `routes::login`
+#[async_trait::async_trait]
+pub trait AccountLockoutStore {
+    // Records a failed login and returns the number of failures so far. The count starts over
+    // `window_seconds` after the first failure it includes.
+    async fn record_failed_login(
+        &mut self,
+        email: &Email,
+        window_seconds: u64,
+    ) -> Result<u32, AccountLockoutStoreError>;
+
+    // Forgets the user's failed logins, e.g. after a successful one
+    async fn clear_failed_logins(&mut self, email: &Email) -> Result<(), AccountLockoutStoreError>;
+
+    // Locks the account for `seconds`, replacing any earlier lock. The lock id is embedded in
+    // the unlock link emailed to the user.
+    async fn lock_account(
+        &mut self,
+        email: &Email,
+        lock_id: AccountLockId,
+        seconds: u64,
+    ) -> Result<(), AccountLockoutStoreError>;
+
+    // Seconds left until the account unlocks on its own, None unless it is locked
+    async fn get_lock(&self, email: &Email) -> Result<Option<u64>, AccountLockoutStoreError>;
+
+    // Lifts the lock and forgets the failed logins that caused it. Fails with LockNotFound
+    // unless `lock_id` is the account's current lock.
+    async fn unlock_account(
+        &mut self,
+        email: &Email,
+        lock_id: &AccountLockId,
+    ) -> Result<(), AccountLockoutStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AccountLockoutStoreError {
+    #[error("Account lock not found")]
+    LockNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AccountLockoutStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LockNotFound, Self::LockNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Identifies a single account lock, it is embedded in the unlock link's signed token
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountLockId(String);
+
+impl AccountLockId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid account lock id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for AccountLockId {
+    fn default() -> Self {
+        AccountLockId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for AccountLockId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, client_id: &OAuthClientId) -> Result<OAuthClient, OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("OAuth client already exists")]
+    ClientAlreadyExists,
+    #[error("OAuth client not found")]
+    ClientNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientAlreadyExists, Self::ClientAlreadyExists)
+                | (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Remembers which scopes each user granted to each OAuth client, so /authorize only asks once
+#[async_trait::async_trait]
+pub trait OAuthConsentStore {
+    // Adds `scope` to the scopes the user already granted to the client
+    async fn grant_consent(
+        &mut self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<(), OAuthConsentStoreError>;
+
+    // Whether the user granted the client every scope in `scope`
+    async fn has_consent(
+        &self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<bool, OAuthConsentStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthConsentStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthConsentStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+
+    // Removes the code and returns what it was issued for, so it can only be exchanged once.
+    // Expired codes are reported as not found.
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuthorizationCodeStoreError {
+    #[error("Authorization code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AuthorizationCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait ServiceAccountStore {
+    async fn add_account(&mut self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError>;
+
+    async fn get_account(
+        &self,
+        id: &ServiceAccountId,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError>;
+
+    // Replaces the secret, the old one stops working right away
+    async fn update_secret(
+        &mut self,
+        id: &ServiceAccountId,
+        secret_hash: String,
+    ) -> Result<(), ServiceAccountStoreError>;
+
+    async fn set_disabled(
+        &mut self,
+        id: &ServiceAccountId,
+        disabled: bool,
+    ) -> Result<(), ServiceAccountStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum ServiceAccountStoreError {
+    #[error("Service account already exists")]
+    AccountAlreadyExists,
+    #[error("Service account not found")]
+    AccountNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ServiceAccountStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::AccountAlreadyExists, Self::AccountAlreadyExists)
+                | (Self::AccountNotFound, Self::AccountNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait ApiKeyStore {
+    async fn add_key(&mut self, record: ApiKeyRecord) -> Result<(), ApiKeyStoreError>;
+
+    async fn get_key_by_hash(&self, key_hash: &str) -> Result<ApiKeyRecord, ApiKeyStoreError>;
+
+    // Oldest first
+    async fn list_keys(&self, email: &Email) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError>;
+
+    // Only deletes keys of the given user, keys of other users are reported as not found
+    async fn revoke_key(&mut self, email: &Email, id: &ApiKeyId) -> Result<(), ApiKeyStoreError>;
+
+    async fn record_use(&mut self, id: &ApiKeyId, used_at: i64) -> Result<(), ApiKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum ApiKeyStoreError {
+    #[error("API key not found")]
+    KeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ApiKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::KeyNotFound, Self::KeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError>;
+
+    // Oldest first
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+
+    async fn touch_session(
+        &mut self,
+        id: &RefreshTokenFamilyId,
+        last_seen_at: i64,
+    ) -> Result<(), SessionStoreError>;
+
+    // Only removes sessions of the given user, sessions of other users are reported as not found
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &RefreshTokenFamilyId,
+    ) -> Result<(), SessionStoreError>;
+
+    // For when all of the user's tokens are banned, see `BannedTokenStore::ban_user_tokens`
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RateLimitStore {
+    // Takes a token from the bucket under `key`, which starts out full. Returns the seconds
+    // until a token is available again if the bucket is empty, the request is refused then.
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+    ) -> Result<Option<u64>, RateLimitStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RateLimitStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+// A signing key generated at runtime, shared by every instance through the signing key store.
+// See `utils::auth::rotate_signing_key`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigningKeyRecord {
+    pub kid: String,
+    pub algorithm: jsonwebtoken::Algorithm,
+    // See `SigningKey::export`, encrypted with `utils::encrypt_secret`
+    pub encrypted_key: Vec<u8>,
+    // Unix timestamp from which the key signs new tokens
+    pub activates_at: i64,
+}
+
+#[async_trait::async_trait]
+pub trait SigningKeyStore {
+    async fn add_key(&mut self, record: SigningKeyRecord) -> Result<(), SigningKeyStoreError>;
+
+    async fn get_keys(&self) -> Result<Vec<SigningKeyRecord>, SigningKeyStoreError>;
+
+    async fn remove_key(&mut self, kid: &str) -> Result<(), SigningKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SigningKeyStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
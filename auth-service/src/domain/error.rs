@@ -1,6 +1,6 @@
 use std::error;
 
-use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use axum::{http::{header::{CACHE_CONTROL, RETRY_AFTER, WWW_AUTHENTICATE}, StatusCode}, response::{IntoResponse, Response}, Json};
 use serde::{Deserialize, Serialize};
 // use thiserror::Error;
 use color_eyre::eyre::Report;
@@ -17,6 +17,30 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("TOTP already enabled")]
+    TotpAlreadyEnabled,
+    #[error("Passkey already registered")]
+    PasskeyAlreadyRegistered,
+    #[error("Service account not found")]
+    ServiceAccountNotFound,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Too many sessions")]
+    TooManySessions,
+    #[error("Account locked")]
+    AccountLocked { retry_after_seconds: u64 },
+    #[error("Too many requests")]
+    TooManyRequests { retry_after_seconds: u64 },
+    #[error("Too many 2FA attempts")]
+    TooManyTwoFAAttempts,
+    #[error("Too many 2FA code resends")]
+    TooManyTwoFAResends,
+    #[error("Payload too large")]
+    PayloadTooLarge,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -30,6 +54,12 @@ impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
 
+        let retry_after = match self {
+            AuthAPIError::TooManyRequests { retry_after_seconds }
+            | AuthAPIError::AccountLocked { retry_after_seconds } => Some(retry_after_seconds),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
@@ -38,12 +68,152 @@ impl IntoResponse for AuthAPIError {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             },
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
-            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token")
+            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email address not verified"),
+            AuthAPIError::TotpAlreadyEnabled => (StatusCode::CONFLICT, "TOTP already enabled"),
+            AuthAPIError::PasskeyAlreadyRegistered => {
+                (StatusCode::CONFLICT, "Passkey already registered")
+            }
+            AuthAPIError::ServiceAccountNotFound => {
+                (StatusCode::NOT_FOUND, "Service account not found")
+            }
+            AuthAPIError::ApiKeyNotFound => (StatusCode::NOT_FOUND, "API key not found"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::TooManySessions => {
+                (StatusCode::CONFLICT, "Too many active sessions, log out on another device first")
+            }
+            AuthAPIError::AccountLocked { .. } => (
+                StatusCode::LOCKED,
+                "Account locked after too many failed logins, try again later or use the link we emailed you",
+            ),
+            AuthAPIError::TooManyRequests { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many requests")
+            }
+            AuthAPIError::TooManyTwoFAAttempts => (
+                StatusCode::GONE,
+                "Too many incorrect codes, log in again to get a new one",
+            ),
+            AuthAPIError::TooManyTwoFAResends => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many codes sent, log in again to get a new one",
+            ),
+            AuthAPIError::PayloadTooLarge => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+            }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+
+        match retry_after {
+            Some(seconds) => (status, [(RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+// Errors of the OAuth endpoints, reported in the format of RFC 6749 section 5.2 rather than as
+// `ErrorResponse`, so off-the-shelf OAuth clients understand them
+#[derive(Debug, thiserror::Error)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("Invalid client: {0}")]
+    InvalidClient(&'static str),
+    #[error("Invalid grant: {0}")]
+    InvalidGrant(&'static str),
+    #[error("Invalid scope: {0}")]
+    InvalidScope(&'static str),
+    #[error("Access denied: {0}")]
+    AccessDenied(&'static str),
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    // OpenID Connect errors for authorization requests with prompt=none
+    #[error("Login required")]
+    LoginRequired,
+    #[error("Consent required")]
+    ConsentRequired,
+    // Bearer token errors of RFC 6750 section 3.1, used by /userinfo
+    #[error("Invalid token: {0}")]
+    InvalidToken(&'static str),
+    #[error("Insufficient scope: {0}")]
+    InsufficientScope(&'static str),
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl OAuthError {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient(_) => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::AccessDenied(_) => "access_denied",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::LoginRequired => "login_required",
+            OAuthError::ConsentRequired => "consent_required",
+            OAuthError::InvalidToken(_) => "invalid_token",
+            OAuthError::InsufficientScope(_) => "insufficient_scope",
+            OAuthError::UnexpectedError(_) => "server_error",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(description)
+            | OAuthError::InvalidClient(description)
+            | OAuthError::InvalidGrant(description)
+            | OAuthError::InvalidScope(description)
+            | OAuthError::AccessDenied(description)
+            | OAuthError::InvalidToken(description)
+            | OAuthError::InsufficientScope(description) => description,
+            OAuthError::UnsupportedResponseType => "Only the \"code\" response type is supported",
+            OAuthError::UnsupportedGrantType => "Unsupported grant type",
+            OAuthError::LoginRequired => "The user is not logged in",
+            OAuthError::ConsentRequired => "The user has not consented to the client yet",
+            OAuthError::UnexpectedError(_) => "Unexpected error",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: String,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+
+        let status = match self {
+            OAuthError::InvalidClient(_) | OAuthError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            OAuthError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            OAuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.error_code().to_owned(),
+            error_description: self.description().to_owned(),
+        });
+
+        match self {
+            // Resource servers report bearer token errors in the WWW-Authenticate header
+            OAuthError::InvalidToken(_) | OAuthError::InsufficientScope(_) => {
+                let challenge = format!(
+                    "Bearer error=\"{}\", error_description=\"{}\"",
+                    self.error_code(),
+                    self.description()
+                );
+                (status, [(CACHE_CONTROL, "no-store".to_owned()), (WWW_AUTHENTICATE, challenge)], body)
+                    .into_response()
+            }
+            _ => (status, [(CACHE_CONTROL, "no-store")], body).into_response(),
+        }
     }
 }
 
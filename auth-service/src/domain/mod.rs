@@ -4,10 +4,26 @@ pub mod data_stores;
 pub mod email;
 pub mod password;
 pub mod email_client;
+pub mod totp;
+pub mod webauthn;
+pub mod oauth;
+pub mod authentication;
+pub mod service_account;
+pub mod api_key;
+pub mod session;
+pub mod rate_limit;
 
 pub use user::*;
 pub use error::*;
 pub use data_stores::*;
 pub use email::*;
 pub use password::*;
-pub use email_client::*;
\ No newline at end of file
+pub use email_client::*;
+pub use totp::*;
+pub use webauthn::*;
+pub use oauth::*;
+pub use authentication::*;
+pub use service_account::*;
+pub use api_key::*;
+pub use session::*;
+pub use rate_limit::*;
@@ -0,0 +1,305 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+use super::{Authentication, Email};
+
+// Authorization codes are exchanged right after the redirect, they don't need to live long
+pub const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+
+// The only PKCE method we accept, "plain" would let anyone who sees the code redeem it
+pub const CODE_CHALLENGE_METHOD_S256: &str = "S256";
+
+// Requesting this scope makes the authorization an OpenID Connect one, which adds an ID token
+pub const OPENID_SCOPE: &str = "openid";
+
+// Lets clients read the user's email address from /userinfo
+pub const EMAIL_SCOPE: &str = "email";
+
+const AUTHORIZATION_CODE_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OAuthClientId(String);
+
+impl OAuthClientId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(&id).wrap_err("Invalid OAuth client id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for OAuthClientId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for OAuthClientId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A redirect URI registered for a client. Authorization requests must name one of them
+// exactly, see `OAuthClient::find_redirect_uri`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedirectUri(Url);
+
+impl RedirectUri {
+    pub fn parse(uri: String) -> Result<Self> {
+        let url = Url::parse(&uri).wrap_err("Invalid redirect URI")?;
+
+        if url.fragment().is_some() {
+            return Err(eyre!("Redirect URI must not contain a fragment"));
+        }
+
+        // Plain http is only safe when the redirect never leaves the machine
+        let is_loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+        match url.scheme() {
+            "https" => {}
+            "http" if is_loopback => {}
+            _ => return Err(eyre!("Redirect URI must use https")),
+        }
+
+        // Compare the URI as registered, not Url's normalized form, so matching stays exact
+        if url.as_str() != uri {
+            return Err(eyre!("Redirect URI must be in normalized form"));
+        }
+
+        Ok(Self(url))
+    }
+
+    // The URI with the given query parameters appended, used to send the response of an
+    // authorization request back to the client
+    pub fn with_params(&self, params: &[(&str, &str)]) -> String {
+        let mut url = self.0.clone();
+        url.query_pairs_mut().extend_pairs(params);
+        url.into()
+    }
+}
+
+impl AsRef<str> for RedirectUri {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OAuthClient {
+    pub client_id: OAuthClientId,
+    pub name: String,
+    pub redirect_uris: Vec<RedirectUri>,
+}
+
+impl OAuthClient {
+    pub fn find_redirect_uri(&self, uri: &str) -> Option<&RedirectUri> {
+        self.redirect_uris.iter().find(|registered| registered.as_ref() == uri)
+    }
+}
+
+// Space separated scope tokens, kept sorted so equal scopes compare and store the same way
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scope(Vec<String>);
+
+impl Scope {
+    pub fn parse(scope: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+
+        for token in scope.split(' ').filter(|token| !token.is_empty()) {
+            // scope-token = 1*( %x21 / %x23-5B / %x5D-7E ), see RFC 6749 section 3.3
+            if !token.chars().all(|c| matches!(c, '\x21' | '\x23'..='\x5B' | '\x5D'..='\x7E')) {
+                return Err(eyre!("Invalid scope token"));
+            }
+            tokens.push(token.to_owned());
+        }
+
+        tokens.sort();
+        tokens.dedup();
+        Ok(Self(tokens))
+    }
+
+    pub fn from_tokens(tokens: Vec<String>) -> Result<Self> {
+        Self::parse(&tokens.join(" "))
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn has(&self, token: &str) -> bool {
+        self.0.iter().any(|scope_token| scope_token == token)
+    }
+
+    pub fn contains(&self, other: &Scope) -> bool {
+        other.0.iter().all(|token| self.0.contains(token))
+    }
+
+    pub fn union(&self, other: &Scope) -> Scope {
+        let mut tokens = self.0.clone();
+        tokens.extend(other.0.iter().cloned());
+        tokens.sort();
+        tokens.dedup();
+        Self(tokens)
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join(" "))
+    }
+}
+
+// PKCE code challenge, the base64url encoded SHA-256 of the client's code verifier
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    pub fn parse(challenge: String) -> Result<Self> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(digest) if digest.len() == 32 => Ok(Self(challenge)),
+            _ => Err(eyre!("Invalid code challenge")),
+        }
+    }
+
+    pub fn from_verifier(verifier: &str) -> Self {
+        Self(URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())))
+    }
+
+    // code-verifier = 43*128unreserved, see RFC 7636 section 4.1
+    pub fn verify(&self, verifier: &str) -> bool {
+        let valid_verifier = (43..=128).contains(&verifier.len())
+            && verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'));
+
+        valid_verifier && *self == Self::from_verifier(verifier)
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthorizationCode(String);
+
+impl AuthorizationCode {
+    pub fn parse(code: String) -> Result<Self> {
+        match URL_SAFE_NO_PAD.decode(&code) {
+            Ok(bytes) if bytes.len() == AUTHORIZATION_CODE_LENGTH => Ok(Self(code)),
+            _ => Err(eyre!("Invalid authorization code")),
+        }
+    }
+
+    // Like refresh and password reset tokens, codes are only stored hashed
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        let mut bytes = [0u8; AUTHORIZATION_CODE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for AuthorizationCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What an authorization code was issued for, checked again when it is exchanged at /token
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: OAuthClientId,
+    pub redirect_uri: RedirectUri,
+    pub email: Email,
+    pub scope: Scope,
+    pub code_challenge: CodeChallenge,
+    // Echoed in the ID token so the client can tie it to its authorization request
+    pub nonce: Option<String>,
+    // How the user logged in before authorizing, reported in the ID token
+    pub authentication: Authentication,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_uri_must_be_https_unless_loopback() {
+        assert!(RedirectUri::parse("https://app.example.com/callback".to_owned()).is_ok());
+        assert!(RedirectUri::parse("http://localhost:8000/callback".to_owned()).is_ok());
+        assert!(RedirectUri::parse("http://127.0.0.1/callback".to_owned()).is_ok());
+        assert!(RedirectUri::parse("http://app.example.com/callback".to_owned()).is_err());
+        assert!(RedirectUri::parse("javascript:alert(1)".to_owned()).is_err());
+        assert!(RedirectUri::parse("https://app.example.com/callback#x".to_owned()).is_err());
+        assert!(RedirectUri::parse("/callback".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_redirect_uri_matching_is_exact() {
+        let client = OAuthClient {
+            client_id: OAuthClientId::default(),
+            name: "App".to_owned(),
+            redirect_uris: vec![
+                RedirectUri::parse("https://app.example.com/callback".to_owned()).unwrap()
+            ],
+        };
+
+        assert!(client.find_redirect_uri("https://app.example.com/callback").is_some());
+        assert!(client.find_redirect_uri("https://app.example.com/callback/").is_none());
+        assert!(client.find_redirect_uri("https://app.example.com/callback?x=1").is_none());
+        assert!(client.find_redirect_uri("https://APP.example.com/callback").is_none());
+    }
+
+    #[test]
+    fn test_redirect_uri_with_params() {
+        let uri = RedirectUri::parse("https://app.example.com/callback?tenant=1".to_owned()).unwrap();
+
+        assert_eq!(
+            uri.with_params(&[("code", "abc"), ("state", "x y")]),
+            "https://app.example.com/callback?tenant=1&code=abc&state=x+y"
+        );
+    }
+
+    #[test]
+    fn test_scope() {
+        let scope = Scope::parse("profile  email profile").unwrap();
+        assert_eq!(scope.to_string(), "email profile");
+        assert!(scope.contains(&Scope::parse("email").unwrap()));
+        assert!(!scope.contains(&Scope::parse("email admin").unwrap()));
+        assert_eq!(scope.union(&Scope::parse("admin").unwrap()).to_string(), "admin email profile");
+        assert!(Scope::parse("bad\"token").is_err());
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // Example from RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge =
+            CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned()).unwrap();
+
+        assert_eq!(CodeChallenge::from_verifier(verifier), challenge);
+        assert!(challenge.verify(verifier));
+        assert!(!challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj"));
+        assert!(!challenge.verify("too-short"));
+        assert!(CodeChallenge::parse("plain-challenge".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_authorization_code() {
+        let code = AuthorizationCode::default();
+        assert_eq!(AuthorizationCode::parse(code.as_ref().to_owned()).unwrap(), code);
+        assert_ne!(code, AuthorizationCode::default());
+        assert!(AuthorizationCode::parse("invalid".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,13 @@
+// A token bucket. It holds up to `burst` tokens, each request takes one, and one token is
+// regained every `refill_interval_seconds`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub refill_interval_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn refill_interval_ms(&self) -> u64 {
+        self.refill_interval_seconds * 1000
+    }
+}
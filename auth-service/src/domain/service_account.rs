@@ -0,0 +1,128 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::Scope;
+
+const SERVICE_ACCOUNT_SECRET_LENGTH: usize = 32;
+
+// Who a token was issued to. Tokens without the claim were issued to users.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalType {
+    #[default]
+    User,
+    ServiceAccount,
+}
+
+impl PrincipalType {
+    pub fn is_user(&self) -> bool {
+        *self == Self::User
+    }
+}
+
+// Also the client id the service account authenticates with at /token
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceAccountId(String);
+
+impl ServiceAccountId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(&id).wrap_err("Invalid service account id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for ServiceAccountId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for ServiceAccountId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The client secret of a service account. It is only shown once, when the account is created or
+// the secret is rotated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceAccountSecret(String);
+
+impl ServiceAccountSecret {
+    pub fn parse(secret: String) -> Result<Self> {
+        match URL_SAFE_NO_PAD.decode(&secret) {
+            Ok(bytes) if bytes.len() == SERVICE_ACCOUNT_SECRET_LENGTH => Ok(Self(secret)),
+            _ => Err(eyre!("Invalid service account secret")),
+        }
+    }
+
+    // The secret is random enough that a fast hash is as good as a password hash
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for ServiceAccountSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; SERVICE_ACCOUNT_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for ServiceAccountSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A machine identity for backend jobs. It can only get tokens through the client credentials
+// grant, limited to the scopes it was created with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceAccount {
+    pub id: ServiceAccountId,
+    pub name: String,
+    pub secret_hash: String,
+    pub scope: Scope,
+    pub disabled: bool,
+}
+
+impl ServiceAccount {
+    pub fn new(name: String, scope: Scope, secret: &ServiceAccountSecret) -> Self {
+        Self {
+            id: ServiceAccountId::default(),
+            name,
+            secret_hash: secret.hash(),
+            scope,
+            disabled: false,
+        }
+    }
+
+    // Compares hashes, so the comparison time doesn't depend on how much of the secret matched
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        match ServiceAccountSecret::parse(secret.to_owned()) {
+            Ok(secret) => secret.hash() == self.secret_hash,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_secret() {
+        let secret = ServiceAccountSecret::default();
+        let account = ServiceAccount::new("Job".to_owned(), Scope::default(), &secret);
+
+        assert!(ServiceAccountSecret::parse(secret.as_ref().to_owned()).is_ok());
+        assert!(account.verify_secret(secret.as_ref()));
+        assert!(!account.verify_secret(ServiceAccountSecret::default().as_ref()));
+        assert!(!account.verify_secret("secret"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, Extensions, HeaderMap},
+};
+use chrono::Utc;
+
+use super::{Email, RefreshTokenFamilyId};
+
+// Browsers can send user agents of any length, we only keep enough to recognize the device
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+// Reverse proxies in front of the service. Requests relayed by them are attributed to the
+// client named in their `X-Forwarded-For` header instead of to the proxy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.contains(ip)
+    }
+}
+
+// The IP address of the client that sent the request. `X-Forwarded-For` is read from the right,
+// each trusted proxy vouches for the address before it. The first address not vouched for is
+// the client, anything to its left could have been made up by the client itself.
+pub fn client_ip(
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    trusted_proxies: &TrustedProxies,
+) -> Option<IpAddr> {
+    // Only set when the server is run with connect info, see `Application::build`
+    let mut ip = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())?;
+
+    let forwarded_for = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+
+    for hop in forwarded_for.into_iter().rev() {
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+
+        match hop.trim().parse() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+
+    Some(ip)
+}
+
+// Where a request came from, recorded on the sessions it starts
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+    TrustedProxies: FromRef<S>,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        let trusted_proxies = TrustedProxies::from_ref(state);
+        let ip_address = client_ip(&parts.headers, &parts.extensions, &trusted_proxies)
+            .map(|ip| ip.to_string());
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+// A login of the user on one device. A session is identified by the refresh token family its
+// login started, which lives exactly as long as the session. Its auth tokens carry the id as
+// their `sid` claim.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: RefreshTokenFamilyId,
+    pub email: Email,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    // Unix timestamps in milliseconds, so logins within the same second still sort by age.
+    // Sessions are seen again whenever their tokens are refreshed.
+    pub created_at: i64,
+    pub last_seen_at: i64,
+}
+
+impl Session {
+    pub fn new(id: RefreshTokenFamilyId, email: Email, client: ClientInfo) -> Self {
+        let now = Utc::now().timestamp_millis();
+
+        Self {
+            id,
+            email,
+            user_agent: client.user_agent,
+            ip_address: client.ip_address,
+            created_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_from(peer: &str, forwarded_for: &[&str]) -> (HeaderMap, Extensions) {
+        let mut headers = HeaderMap::new();
+        for value in forwarded_for {
+            headers.append(X_FORWARDED_FOR, value.parse().unwrap());
+        }
+
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 4000)));
+
+        (headers, extensions)
+    }
+
+    fn proxies(ips: &[&str]) -> TrustedProxies {
+        TrustedProxies(ips.iter().map(|ip| ip.parse().unwrap()).collect())
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_of_untrusted_peer() {
+        let (headers, extensions) = request_from("203.0.113.7", &["198.51.100.1"]);
+
+        assert_eq!(client_ip(&headers, &extensions, &proxies(&[])), ip("203.0.113.7"));
+        assert_eq!(
+            client_ip(&headers, &extensions, &proxies(&["10.0.0.1"])),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_proxies() {
+        let trusted = proxies(&["10.0.0.1", "10.0.0.2"]);
+
+        let (headers, extensions) = request_from("10.0.0.1", &["198.51.100.1"]);
+        assert_eq!(client_ip(&headers, &extensions, &trusted), ip("198.51.100.1"));
+
+        // Addresses left of the first untrusted hop may be spoofed by the client
+        let (headers, extensions) =
+            request_from("10.0.0.1", &["192.0.2.9, 198.51.100.1", "10.0.0.2"]);
+        assert_eq!(client_ip(&headers, &extensions, &trusted), ip("198.51.100.1"));
+
+        // A request made by the proxy itself
+        let (headers, extensions) = request_from("10.0.0.1", &[]);
+        assert_eq!(client_ip(&headers, &extensions, &trusted), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_client_ip_stops_at_invalid_hop() {
+        let (headers, extensions) = request_from("10.0.0.1", &["198.51.100.1, unknown"]);
+
+        assert_eq!(
+            client_ip(&headers, &extensions, &proxies(&["10.0.0.1"])),
+            ip("10.0.0.1")
+        );
+    }
+}
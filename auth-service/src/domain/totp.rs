@@ -0,0 +1,210 @@
+use base32::Alphabet;
+use color_eyre::eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+use super::Email;
+
+// RFC 6238 defaults, which is what every authenticator app supports
+const TOTP_DIGITS: usize = 6;
+pub const TOTP_STEP_SECONDS: i64 = 30;
+
+// Codes of the neighbouring time steps are accepted as well, to allow for clock drift
+const TOTP_ALLOWED_DRIFT_STEPS: i64 = 1;
+
+// 160 bits, the length recommended by RFC 4226 for HMAC-SHA1
+const TOTP_SECRET_LENGTH: usize = 20;
+
+const BASE32: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+#[derive(Clone, PartialEq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.is_empty() {
+            return Err(eyre!("TOTP secret must not be empty"));
+        }
+
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // The form users type into their authenticator app
+    pub fn to_base32(&self) -> String {
+        base32::encode(BASE32, &self.0)
+    }
+
+    // Key URI understood by authenticator apps, usually shown as a QR code
+    pub fn otpauth_uri(&self, issuer: &str, email: &Email) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(email.as_ref()),
+            self.to_base32(),
+            percent_encode(issuer),
+            TOTP_DIGITS,
+            TOTP_STEP_SECONDS
+        )
+    }
+
+    // Check `code` against the time steps around `now` (a unix timestamp), and return
+    // the step it belongs to, so the caller can refuse to accept it a second time
+    pub fn verify(&self, code: &TotpCode, now: i64) -> Option<u64> {
+        let current_step = now.div_euclid(TOTP_STEP_SECONDS);
+
+        (current_step - TOTP_ALLOWED_DRIFT_STEPS..=current_step + TOTP_ALLOWED_DRIFT_STEPS)
+            .filter_map(|step| u64::try_from(step).ok())
+            .find(|&step| constant_time_eq(self.code_at(step).as_ref(), code.as_ref()))
+    }
+
+    // RFC 4226 HOTP value for the counter `step`, i.e. the code shown during that time step
+    pub fn code_at(&self, step: u64) -> TotpCode {
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(&self.0).expect("HMAC can take a key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+
+        TotpCode(format!(
+            "{:0width$}",
+            binary % 10u32.pow(TOTP_DIGITS as u32),
+            width = TOTP_DIGITS
+        ))
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = vec![0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+}
+
+// Keep the secret out of logs
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret(..)")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpCode(String);
+
+impl TotpCode {
+    pub fn parse(code: String) -> Result<Self> {
+        if code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("TOTP code must be {} digits", TOTP_DIGITS))
+        }
+    }
+}
+
+impl AsRef<str> for TotpCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+// Percent-encode everything but RFC 3986 unreserved characters
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test secret and SHA1 vectors from RFC 6238 appendix B, truncated to 6 digits
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::from_bytes(b"12345678901234567890".to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_code_matches_rfc_6238_test_vectors() {
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        for (time, expected) in vectors {
+            let step = (time / TOTP_STEP_SECONDS) as u64;
+            assert_eq!(rfc_secret().code_at(step).as_ref(), expected, "time: {}", time);
+        }
+    }
+
+    #[test]
+    fn test_verify_accepts_one_step_of_drift() {
+        let secret = rfc_secret();
+        let code = TotpCode::parse("081804".to_owned()).unwrap();
+        let step = 1111111109 / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&code, 1111111109), Some(step as u64));
+        assert_eq!(secret.verify(&code, 1111111109 + TOTP_STEP_SECONDS), Some(step as u64));
+        assert_eq!(secret.verify(&code, 1111111109 - TOTP_STEP_SECONDS), Some(step as u64));
+
+        assert_eq!(secret.verify(&code, 1111111109 + 2 * TOTP_STEP_SECONDS), None);
+        assert_eq!(secret.verify(&code, 1111111109 - 2 * TOTP_STEP_SECONDS), None);
+    }
+
+    #[test]
+    fn test_verify_rejects_code_of_other_secret() {
+        let code = TotpCode::parse("287082".to_owned()).unwrap();
+
+        assert_eq!(TotpSecret::default().verify(&code, 59), None);
+    }
+
+    #[test]
+    fn test_parse_totp_code() {
+        assert!(TotpCode::parse("123456".to_owned()).is_ok());
+        assert!(TotpCode::parse("12345".to_owned()).is_err());
+        assert!(TotpCode::parse("1234567".to_owned()).is_err());
+        assert!(TotpCode::parse("12345a".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        assert_eq!(
+            rfc_secret().otpauth_uri("Auth Service", &email),
+            "otpauth://totp/Auth%20Service:test%40example.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Auth%20Service\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
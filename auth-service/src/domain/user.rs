@@ -1,12 +1,20 @@
+use color_eyre::eyre::{eyre, Result};
+
 use super::{Email, Password};
 
 // The User struct should contain 3 fields. email, which is a String; 
 // password, which is also a String; and requires_2fa, which is a boolean. 
+// verified is set once the user followed the link in the verification email.
+// two_fa_method decides how the second factor is checked when requires_2fa is set.
+// magic_link_enabled lets the user log in with an emailed link instead of the password.
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool
+    pub requires_2fa: bool,
+    pub verified: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub magic_link_enabled: bool,
 }
 
 impl User {
@@ -14,7 +22,42 @@ impl User {
         User {
             email,
             password,
-            requires_2fa
+            requires_2fa,
+            verified: false,
+            two_fa_method: TwoFAMethod::default(),
+            magic_link_enabled: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TwoFAMethod {
+    // A code sent by email on every login
+    #[default]
+    Email,
+    // A code from an authenticator app, see `TotpSecret`
+    Totp,
+    // A passkey or security key, the login is finished at /webauthn/login/finish
+    Webauthn,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: &str) -> Result<Self> {
+        match method {
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            "webauthn" => Ok(Self::Webauthn),
+            _ => Err(eyre!("Unknown 2FA method: {}", method)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Email => "email",
+            Self::Totp => "totp",
+            Self::Webauthn => "webauthn",
         }
     }
 }
\ No newline at end of file
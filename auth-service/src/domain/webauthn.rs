@@ -0,0 +1,783 @@
+use std::io::Cursor;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use color_eyre::eyre::{eyre, Context, Result};
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use x509_cert::{
+    der::{
+        asn1::OctetString,
+        oid::{db::rfc5280::ID_CE_BASIC_CONSTRAINTS, ObjectIdentifier},
+        Decode, Encode,
+    },
+    ext::pkix::BasicConstraints,
+    Certificate, Version,
+};
+
+use super::Email;
+
+// Only ES256 (ECDSA with P-256 and SHA-256) is supported, every platform authenticator and
+// security key implements it
+pub const COSE_ALGORITHM_ES256: i64 = -7;
+
+// How long the browser has to complete a ceremony
+pub const WEBAUTHN_CHALLENGE_TTL_SECONDS: i64 = 300;
+
+const WEBAUTHN_CHALLENGE_LENGTH: usize = 32;
+
+// Authenticator data flags, see https://www.w3.org/TR/webauthn-2/#sctn-authenticator-data
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// id-fido-gen-ce-aaguid, lets an attestation certificate name the authenticator model
+const AAGUID_EXTENSION_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.45724.1.1.4");
+
+// The relying party is this service. `id` is the domain credentials are scoped to and
+// `origin` the page the ceremonies run on.
+#[derive(Clone, Debug)]
+pub struct RelyingParty {
+    pub id: String,
+    pub origin: String,
+}
+
+// Random bytes the authenticator signs, base64url encoded like in clientDataJSON
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebauthnChallenge(String);
+
+impl WebauthnChallenge {
+    pub fn parse(challenge: String) -> Result<Self> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(bytes) if bytes.len() == WEBAUTHN_CHALLENGE_LENGTH => Ok(Self(challenge)),
+            _ => Err(eyre!("Invalid WebAuthn challenge")),
+        }
+    }
+}
+
+impl Default for WebauthnChallenge {
+    fn default() -> Self {
+        let mut bytes = [0u8; WEBAUTHN_CHALLENGE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for WebauthnChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// WebAuthn user handles must not contain personal information, so the email is hashed
+pub fn webauthn_user_handle(email: &Email) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(email.as_ref().as_bytes()))
+}
+
+// The collected client data the browser hands to the authenticator. It is signed as part
+// of every ceremony, via its hash.
+#[derive(Debug)]
+pub struct ClientData {
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+    cross_origin: bool,
+    hash: [u8; 32],
+}
+
+#[derive(Deserialize)]
+struct ClientDataJson {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    cross_origin: bool,
+}
+
+impl ClientData {
+    pub fn parse(client_data_json: &[u8]) -> Result<Self> {
+        let client_data: ClientDataJson =
+            serde_json::from_slice(client_data_json).wrap_err("Invalid client data")?;
+
+        Ok(Self {
+            ceremony_type: client_data.ceremony_type,
+            challenge: client_data.challenge,
+            origin: client_data.origin,
+            cross_origin: client_data.cross_origin,
+            hash: Sha256::digest(client_data_json).into(),
+        })
+    }
+
+    // The challenge the browser was given, look it up to find out which ceremony this answers
+    pub fn challenge(&self) -> Result<WebauthnChallenge> {
+        WebauthnChallenge::parse(self.challenge.clone())
+    }
+
+    fn check(&self, rp: &RelyingParty, ceremony_type: &str) -> Result<()> {
+        if self.ceremony_type != ceremony_type {
+            return Err(eyre!("Expected a {} ceremony", ceremony_type));
+        }
+
+        if self.origin != rp.origin || self.cross_origin {
+            return Err(eyre!("Ceremony ran on an unexpected origin: {}", self.origin));
+        }
+
+        Ok(())
+    }
+}
+
+// An ES256 credential public key
+#[derive(Clone, Debug, PartialEq)]
+pub struct CredentialPublicKey(VerifyingKey);
+
+impl CredentialPublicKey {
+    // The uncompressed SEC1 point, which is how credentials are stored
+    pub fn from_sec1_bytes(bytes: &[u8]) -> Result<Self> {
+        let key = VerifyingKey::from_sec1_bytes(bytes).wrap_err("Invalid credential public key")?;
+        Ok(Self(key))
+    }
+
+    pub fn to_sec1_bytes(&self) -> Vec<u8> {
+        self.0.to_encoded_point(false).as_bytes().to_vec()
+    }
+
+    // Parse a COSE_Key, see https://www.rfc-editor.org/rfc/rfc9053#section-7.1.1
+    fn from_cose(key: &Value) -> Result<Self> {
+        let key = key.as_map().ok_or_else(|| eyre!("COSE key is not a map"))?;
+
+        let kty = int_entry(key, 1).and_then(as_i128);
+        let alg = int_entry(key, 3).and_then(as_i128);
+        let crv = int_entry(key, -1).and_then(as_i128);
+
+        // EC2 key type on the P-256 curve
+        if kty != Some(2) || alg != Some(COSE_ALGORITHM_ES256.into()) || crv != Some(1) {
+            return Err(eyre!("Unsupported credential algorithm, only ES256 is supported"));
+        }
+
+        let (Some(x), Some(y)) = (
+            int_entry(key, -2).and_then(Value::as_bytes),
+            int_entry(key, -3).and_then(Value::as_bytes),
+        ) else {
+            return Err(eyre!("COSE key is missing its coordinates"));
+        };
+
+        let point = [&[0x04], x.as_slice(), y.as_slice()].concat();
+        Self::from_sec1_bytes(&point)
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+        verify_signature(&self.0, message, signature)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttestationFormat {
+    // The authenticator makes no statement about itself
+    None,
+    // Signed with the credential key (self attestation) or an attestation certificate
+    Packed,
+}
+
+impl AttestationFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "none" => Ok(Self::None),
+            "packed" => Ok(Self::Packed),
+            _ => Err(eyre!("Unsupported attestation format: {}", format)),
+        }
+    }
+}
+
+impl AsRef<str> for AttestationFormat {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Packed => "packed",
+        }
+    }
+}
+
+// A credential created by a successful registration ceremony
+#[derive(Debug)]
+pub struct RegisteredCredential {
+    pub credential_id: Vec<u8>,
+    pub public_key: CredentialPublicKey,
+    pub sign_count: u32,
+    pub attestation_format: AttestationFormat,
+}
+
+// Verify the response to navigator.credentials.create(). The caller is responsible for
+// checking that the challenge in `client_data` was issued for this registration.
+//
+// Attestation certificates are not checked against a trust anchor, we don't restrict which
+// authenticators can be used. A packed statement only has to be signed correctly.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    client_data: &ClientData,
+    attestation_object: &[u8],
+) -> Result<RegisteredCredential> {
+    client_data.check(rp, "webauthn.create")?;
+
+    let attestation_object: Value = ciborium::de::from_reader(attestation_object)
+        .wrap_err("Invalid attestation object")?;
+    let attestation_object = attestation_object
+        .as_map()
+        .ok_or_else(|| eyre!("Attestation object is not a map"))?;
+
+    let (Some(format), Some(statement), Some(auth_data_bytes)) = (
+        text_entry(attestation_object, "fmt").and_then(Value::as_text),
+        text_entry(attestation_object, "attStmt").and_then(Value::as_map),
+        text_entry(attestation_object, "authData").and_then(Value::as_bytes),
+    ) else {
+        return Err(eyre!("Attestation object is missing fields"));
+    };
+
+    let auth_data = AuthenticatorData::parse(auth_data_bytes)?;
+    auth_data.check(rp, false)?;
+
+    let Some(credential) = auth_data.attested_credential else {
+        return Err(eyre!("Authenticator data has no attested credential"));
+    };
+
+    let attestation_format = AttestationFormat::parse(format)?;
+
+    match attestation_format {
+        AttestationFormat::None => {
+            if !statement.is_empty() {
+                return Err(eyre!("Attestation statement of format none must be empty"));
+            }
+        }
+        AttestationFormat::Packed => {
+            let signed_data = [auth_data_bytes.as_slice(), &client_data.hash].concat();
+            verify_packed_statement(statement, &signed_data, &credential)?;
+        }
+    }
+
+    Ok(RegisteredCredential {
+        credential_id: credential.credential_id,
+        public_key: credential.public_key,
+        sign_count: auth_data.sign_count,
+        attestation_format,
+    })
+}
+
+// Verify the response to navigator.credentials.get() against the stored credential key, and
+// return the authenticator's signature counter. The caller is responsible for checking the
+// challenge, and that the counter went up.
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    client_data: &ClientData,
+    authenticator_data: &[u8],
+    signature: &[u8],
+    public_key: &CredentialPublicKey,
+    user_verification_required: bool,
+) -> Result<u32> {
+    client_data.check(rp, "webauthn.get")?;
+
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+    auth_data.check(rp, user_verification_required)?;
+
+    let signed_data = [authenticator_data, &client_data.hash].concat();
+    public_key.verify(&signed_data, signature)?;
+
+    Ok(auth_data.sign_count)
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<AttestedCredential>,
+}
+
+struct AttestedCredential {
+    aaguid: [u8; 16],
+    credential_id: Vec<u8>,
+    public_key: CredentialPublicKey,
+}
+
+impl AuthenticatorData {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 37 {
+            return Err(eyre!("Authenticator data is too short"));
+        }
+
+        let rp_id_hash: [u8; 32] = bytes[..32].try_into()?;
+        let flags = bytes[32];
+        let sign_count = u32::from_be_bytes(bytes[33..37].try_into()?);
+
+        let attested_credential = match flags & FLAG_ATTESTED_CREDENTIAL_DATA {
+            0 => None,
+            _ => Some(AttestedCredential::parse(&bytes[37..])?),
+        };
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    fn check(&self, rp: &RelyingParty, user_verification_required: bool) -> Result<()> {
+        if self.rp_id_hash[..] != Sha256::digest(rp.id.as_bytes())[..] {
+            return Err(eyre!("Credential belongs to another relying party"));
+        }
+
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(eyre!("User was not present"));
+        }
+
+        if user_verification_required && self.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(eyre!("User was not verified"));
+        }
+
+        Ok(())
+    }
+}
+
+impl AttestedCredential {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 18 {
+            return Err(eyre!("Attested credential data is too short"));
+        }
+
+        let aaguid: [u8; 16] = bytes[..16].try_into()?;
+        let id_length = u16::from_be_bytes(bytes[16..18].try_into()?) as usize;
+
+        let Some(credential_id) = bytes.get(18..18 + id_length) else {
+            return Err(eyre!("Attested credential data is too short"));
+        };
+
+        // The COSE key is followed by extensions, if any, which we ignore
+        let mut reader = Cursor::new(&bytes[18 + id_length..]);
+        let public_key: Value =
+            ciborium::de::from_reader(&mut reader).wrap_err("Invalid credential public key")?;
+
+        Ok(Self {
+            aaguid,
+            credential_id: credential_id.to_vec(),
+            public_key: CredentialPublicKey::from_cose(&public_key)?,
+        })
+    }
+}
+
+// See https://www.w3.org/TR/webauthn-2/#sctn-packed-attestation
+fn verify_packed_statement(
+    statement: &[(Value, Value)],
+    signed_data: &[u8],
+    credential: &AttestedCredential,
+) -> Result<()> {
+    let alg = text_entry(statement, "alg").and_then(as_i128);
+    let Some(signature) = text_entry(statement, "sig").and_then(Value::as_bytes) else {
+        return Err(eyre!("Packed attestation statement has no signature"));
+    };
+
+    if alg != Some(COSE_ALGORITHM_ES256.into()) {
+        return Err(eyre!("Unsupported attestation algorithm, only ES256 is supported"));
+    }
+
+    let certificate = text_entry(statement, "x5c")
+        .and_then(Value::as_array)
+        .and_then(|chain| chain.first())
+        .and_then(Value::as_bytes);
+
+    match certificate {
+        Some(certificate) => {
+            let certificate =
+                Certificate::from_der(certificate).wrap_err("Invalid attestation certificate")?;
+            check_attestation_certificate(&certificate, &credential.aaguid)?;
+
+            let key = VerifyingKey::from_public_key_der(
+                &certificate.tbs_certificate.subject_public_key_info.to_der()?,
+            )
+            .wrap_err("Attestation certificate does not hold a P-256 key")?;
+
+            verify_signature(&key, signed_data, signature)
+        }
+        // Self attestation, signed with the new credential's own key
+        None => credential.public_key.verify(signed_data, signature),
+    }
+}
+
+// See https://www.w3.org/TR/webauthn-2/#sctn-packed-attestation-cert-requirements
+fn check_attestation_certificate(certificate: &Certificate, aaguid: &[u8; 16]) -> Result<()> {
+    let tbs_certificate = &certificate.tbs_certificate;
+
+    if tbs_certificate.version != Version::V3 {
+        return Err(eyre!("Attestation certificate must be X.509 version 3"));
+    }
+
+    let subject = tbs_certificate.subject.to_string();
+    if !subject.split(',').any(|part| part == "OU=Authenticator Attestation") {
+        return Err(eyre!("Attestation certificate has an unexpected subject: {}", subject));
+    }
+
+    let extensions = tbs_certificate.extensions.as_deref().unwrap_or_default();
+
+    let is_ca = extensions
+        .iter()
+        .find(|extension| extension.extn_id == ID_CE_BASIC_CONSTRAINTS)
+        .map(|extension| BasicConstraints::from_der(extension.extn_value.as_bytes()))
+        .transpose()
+        .wrap_err("Invalid basic constraints in attestation certificate")?
+        .map(|constraints| constraints.ca);
+
+    if is_ca != Some(false) {
+        return Err(eyre!("Attestation certificate must not be a CA certificate"));
+    }
+
+    if let Some(extension) = extensions
+        .iter()
+        .find(|extension| extension.extn_id == AAGUID_EXTENSION_OID)
+    {
+        let certificate_aaguid = OctetString::from_der(extension.extn_value.as_bytes())
+            .wrap_err("Invalid AAGUID in attestation certificate")?;
+
+        if extension.critical || certificate_aaguid.as_bytes() != aaguid {
+            return Err(eyre!("Attestation certificate is for another authenticator model"));
+        }
+    }
+
+    Ok(())
+}
+
+// WebAuthn signatures are DER encoded
+fn verify_signature(key: &VerifyingKey, message: &[u8], signature: &[u8]) -> Result<()> {
+    let signature = Signature::from_der(signature).wrap_err("Invalid signature encoding")?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    key.verify(message, &signature)
+        .map_err(|_| eyre!("Signature verification failed"))
+}
+
+fn text_entry<'a>(map: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn int_entry(map: &[(Value, Value)], key: i128) -> Option<&Value> {
+    map.iter()
+        .find(|(k, _)| as_i128(k) == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn as_i128(value: &Value) -> Option<i128> {
+    value.as_integer().map(i128::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use p256::{ecdsa::SigningKey, pkcs8::EncodePublicKey};
+    use std::{str::FromStr, time::Duration};
+    use x509_cert::{
+        builder::{Builder, CertificateBuilder, Profile},
+        name::Name,
+        serial_number::SerialNumber,
+        spki::SubjectPublicKeyInfoOwned,
+        time::Validity,
+    };
+
+    fn rp() -> RelyingParty {
+        RelyingParty {
+            id: "localhost".to_owned(),
+            origin: "http://localhost:3000".to_owned(),
+        }
+    }
+
+    // Just enough of an authenticator to produce registration and assertion responses
+    struct Authenticator {
+        key: SigningKey,
+        credential_id: Vec<u8>,
+    }
+
+    impl Authenticator {
+        fn new() -> Self {
+            Self {
+                key: SigningKey::random(&mut rand::thread_rng()),
+                credential_id: vec![7; 16],
+            }
+        }
+
+        fn client_data(ceremony_type: &str, origin: &str) -> Vec<u8> {
+            serde_json::json!({
+                "type": ceremony_type,
+                "challenge": WebauthnChallenge::default().as_ref(),
+                "origin": origin,
+            })
+            .to_string()
+            .into_bytes()
+        }
+
+        fn authenticator_data(&self, rp_id: &str, flags: u8, attested: bool) -> Vec<u8> {
+            let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            data.push(flags | if attested { FLAG_ATTESTED_CREDENTIAL_DATA } else { 0 });
+            data.extend(1u32.to_be_bytes());
+
+            if attested {
+                let point = self.key.verifying_key().to_encoded_point(false);
+                let cose_key = Value::Map(vec![
+                    (1.into(), 2.into()),
+                    (3.into(), COSE_ALGORITHM_ES256.into()),
+                    ((-1).into(), 1.into()),
+                    ((-2).into(), Value::Bytes(point.x().unwrap().to_vec())),
+                    ((-3).into(), Value::Bytes(point.y().unwrap().to_vec())),
+                ]);
+
+                data.extend([0u8; 16]);
+                data.extend((self.credential_id.len() as u16).to_be_bytes());
+                data.extend(&self.credential_id);
+                ciborium::ser::into_writer(&cose_key, &mut data).unwrap();
+            }
+
+            data
+        }
+
+        fn sign(key: &SigningKey, auth_data: &[u8], client_data: &[u8]) -> Vec<u8> {
+            let message = [auth_data, &Sha256::digest(client_data)].concat();
+            let signature: Signature = p256::ecdsa::signature::Signer::sign(key, &message);
+            signature.to_der().as_bytes().to_vec()
+        }
+
+        fn attestation_object(&self, format: &str, statement: Vec<(Value, Value)>, auth_data: Vec<u8>) -> Vec<u8> {
+            let object = Value::Map(vec![
+                ("fmt".into(), format.into()),
+                ("attStmt".into(), Value::Map(statement)),
+                ("authData".into(), Value::Bytes(auth_data)),
+            ]);
+
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(&object, &mut bytes).unwrap();
+            bytes
+        }
+    }
+
+    fn attestation_certificate(key: &SigningKey, subject: &str) -> Vec<u8> {
+        let subject = Name::from_str(subject).unwrap();
+        let spki = SubjectPublicKeyInfoOwned::from_der(
+            key.verifying_key().to_public_key_der().unwrap().as_bytes(),
+        )
+        .unwrap();
+
+        let builder = CertificateBuilder::new(
+            Profile::Leaf {
+                issuer: subject.clone(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            SerialNumber::from(1u32),
+            Validity::from_now(Duration::from_secs(3600)).unwrap(),
+            subject,
+            spki,
+            key,
+        )
+        .unwrap();
+
+        builder
+            .build::<p256::ecdsa::DerSignature>()
+            .unwrap()
+            .to_der()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_registration_with_none_attestation() {
+        let authenticator = Authenticator::new();
+        let client_data = Authenticator::client_data("webauthn.create", &rp().origin);
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, true);
+        let attestation_object = authenticator.attestation_object("none", vec![], auth_data);
+
+        let credential = verify_registration(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &attestation_object,
+        )
+        .unwrap();
+
+        assert_eq!(credential.credential_id, authenticator.credential_id);
+        assert_eq!(credential.attestation_format, AttestationFormat::None);
+        assert_eq!(
+            credential.public_key,
+            CredentialPublicKey(*authenticator.key.verifying_key())
+        );
+    }
+
+    #[test]
+    fn test_verify_registration_with_packed_self_attestation() {
+        let authenticator = Authenticator::new();
+        let client_data = Authenticator::client_data("webauthn.create", &rp().origin);
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, true);
+        let signature = Authenticator::sign(&authenticator.key, &auth_data, &client_data);
+
+        let statement = vec![
+            ("alg".into(), COSE_ALGORITHM_ES256.into()),
+            ("sig".into(), Value::Bytes(signature)),
+        ];
+        let attestation_object = authenticator.attestation_object("packed", statement, auth_data);
+
+        let credential = verify_registration(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &attestation_object,
+        )
+        .unwrap();
+        assert_eq!(credential.attestation_format, AttestationFormat::Packed);
+
+        // A statement signed by some other key must be rejected
+        let other_key = SigningKey::random(&mut rand::thread_rng());
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, true);
+        let signature = Authenticator::sign(&other_key, &auth_data, &client_data);
+
+        let statement = vec![
+            ("alg".into(), COSE_ALGORITHM_ES256.into()),
+            ("sig".into(), Value::Bytes(signature)),
+        ];
+        let attestation_object = authenticator.attestation_object("packed", statement, auth_data);
+
+        assert!(verify_registration(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &attestation_object
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify_registration_with_packed_certificate_attestation() {
+        let authenticator = Authenticator::new();
+        let attestation_key = SigningKey::random(&mut rand::thread_rng());
+        let client_data = Authenticator::client_data("webauthn.create", &rp().origin);
+
+        for (subject, valid) in [
+            ("CN=Test Authenticator,OU=Authenticator Attestation,O=Test,C=US", true),
+            ("CN=Test Authenticator,O=Test,C=US", false),
+        ] {
+            let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, true);
+            let signature = Authenticator::sign(&attestation_key, &auth_data, &client_data);
+            let certificate = attestation_certificate(&attestation_key, subject);
+
+            let statement = vec![
+                ("alg".into(), COSE_ALGORITHM_ES256.into()),
+                ("sig".into(), Value::Bytes(signature)),
+                ("x5c".into(), Value::Array(vec![Value::Bytes(certificate)])),
+            ];
+            let attestation_object =
+                authenticator.attestation_object("packed", statement, auth_data);
+
+            let result = verify_registration(
+                &rp(),
+                &ClientData::parse(&client_data).unwrap(),
+                &attestation_object,
+            );
+            assert_eq!(result.is_ok(), valid, "subject: {}", subject);
+        }
+    }
+
+    #[test]
+    fn test_verify_registration_rejects_other_relying_party() {
+        let authenticator = Authenticator::new();
+
+        // Wrong origin in the client data
+        let client_data = Authenticator::client_data("webauthn.create", "https://evil.example");
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, true);
+        let attestation_object = authenticator.attestation_object("none", vec![], auth_data);
+
+        assert!(verify_registration(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &attestation_object
+        )
+        .is_err());
+
+        // Credential scoped to another RP ID
+        let client_data = Authenticator::client_data("webauthn.create", &rp().origin);
+        let auth_data = authenticator.authenticator_data("evil.example", FLAG_USER_PRESENT, true);
+        let attestation_object = authenticator.attestation_object("none", vec![], auth_data);
+
+        assert!(verify_registration(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &attestation_object
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify_assertion() {
+        let authenticator = Authenticator::new();
+        let public_key = CredentialPublicKey(*authenticator.key.verifying_key());
+        let client_data = Authenticator::client_data("webauthn.get", &rp().origin);
+        let client_data_parsed = ClientData::parse(&client_data).unwrap();
+
+        // User present and verified
+        let flags = FLAG_USER_PRESENT | FLAG_USER_VERIFIED;
+        let auth_data = authenticator.authenticator_data(&rp().id, flags, false);
+        let signature = Authenticator::sign(&authenticator.key, &auth_data, &client_data);
+
+        let result = verify_assertion(&rp(), &client_data_parsed, &auth_data, &signature, &public_key, true);
+        assert_eq!(result.unwrap(), 1);
+
+        // User present only, fine unless user verification is required
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, false);
+        let signature = Authenticator::sign(&authenticator.key, &auth_data, &client_data);
+
+        assert!(verify_assertion(&rp(), &client_data_parsed, &auth_data, &signature, &public_key, false).is_ok());
+        assert!(verify_assertion(&rp(), &client_data_parsed, &auth_data, &signature, &public_key, true).is_err());
+
+        // Signature over other data
+        let other_client_data = Authenticator::client_data("webauthn.get", &rp().origin);
+        let signature = Authenticator::sign(&authenticator.key, &auth_data, &other_client_data);
+
+        assert!(verify_assertion(&rp(), &client_data_parsed, &auth_data, &signature, &public_key, false).is_err());
+    }
+
+    #[test]
+    fn test_verify_assertion_rejects_registration_response() {
+        let authenticator = Authenticator::new();
+        let public_key = CredentialPublicKey(*authenticator.key.verifying_key());
+        let client_data = Authenticator::client_data("webauthn.create", &rp().origin);
+        let auth_data = authenticator.authenticator_data(&rp().id, FLAG_USER_PRESENT, false);
+        let signature = Authenticator::sign(&authenticator.key, &auth_data, &client_data);
+
+        let result = verify_assertion(
+            &rp(),
+            &ClientData::parse(&client_data).unwrap(),
+            &auth_data,
+            &signature,
+            &public_key,
+            false,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_credential_public_key_sec1_roundtrip() {
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let public_key = CredentialPublicKey(*key.verifying_key());
+
+        let parsed = CredentialPublicKey::from_sec1_bytes(&public_key.to_sec1_bytes()).unwrap();
+        assert_eq!(parsed, public_key);
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let challenge = WebauthnChallenge::default();
+        assert_eq!(
+            WebauthnChallenge::parse(challenge.as_ref().to_owned()).unwrap(),
+            challenge
+        );
+
+        assert!(WebauthnChallenge::parse("too-short".to_owned()).is_err());
+        assert!(WebauthnChallenge::parse("not base64url!".to_owned()).is_err());
+    }
+}
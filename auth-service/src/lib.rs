@@ -1,11 +1,18 @@
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 
 use app_state::AppState;
-use axum::{http::{Method, StatusCode}, response::{IntoResponse, Response}, routing::post, serve::Serve, Json, Router};
+use axum::{
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::Method,
+    middleware::{self, AddExtension},
+    routing::{delete, get, post},
+    serve::Serve,
+    Router,
+};
 use redis::{Client, RedisResult};
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
-use utils::{make_span_with_request_id, on_request, on_response};
+use utils::{make_span_with_request_id, on_request, on_response, rate_limit};
 
 pub mod routes;
 pub mod domain;
@@ -15,7 +22,11 @@ pub mod utils;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    // Run with connect info, so routes can tell where requests come from, see `ClientInfo`
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -36,13 +47,58 @@ impl Application {
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
-        let router = Router::new()
-            .nest_service("/", ServeDir::new("assets"))
+        // Routes that take passwords or codes, throttled to slow down guessing them. Resending
+        // codes is throttled too, so it can't be used to flood inboxes.
+        let throttled_routes = Router::new()
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
-            .route("/logout", post(routes::logout))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/resend-2fa", post(routes::resend_2fa))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit));
+
+        let router = Router::new()
+            .nest_service("/", ServeDir::new("assets"))
+            .merge(throttled_routes)
+            .route("/login/magic-link", post(routes::request_magic_link))
+            .route("/login/magic-link/callback", post(routes::magic_link_callback))
+            .route("/login/magic-link/opt-in", post(routes::set_magic_link_opt_in))
+            .route("/unlock-account", post(routes::unlock_account))
+            .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/2fa/totp/enroll", post(routes::enroll_totp))
+            .route("/2fa/totp/confirm", post(routes::confirm_totp))
+            .route("/2fa/recovery-codes", post(routes::generate_recovery_codes))
+            .route("/api-keys", get(routes::list_api_keys).post(routes::create_api_key))
+            .route("/api-keys/:id", delete(routes::revoke_api_key))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:id", delete(routes::revoke_session))
+            .route("/webauthn/register/start", post(routes::start_webauthn_registration))
+            .route("/webauthn/register/finish", post(routes::finish_webauthn_registration))
+            .route("/webauthn/login/start", post(routes::start_webauthn_login))
+            .route("/webauthn/login/finish", post(routes::finish_webauthn_login))
+            .route("/authorize", get(routes::authorize))
+            .route("/authorize/consent", post(routes::authorize_consent))
+            .route("/token", post(routes::token))
+            .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke))
             .route("/verify-token", post(routes::verify_token))
+            .route("/verify-email", post(routes::verify_email))
+            .route("/verify-email/resend", post(routes::resend_verification_email))
+            .route("/refresh", post(routes::refresh))
+            .route("/change-password", post(routes::change_password))
+            .route("/password-reset/request", post(routes::request_password_reset))
+            .route("/password-reset/confirm", post(routes::confirm_password_reset))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route("/.well-known/openid-configuration", get(routes::openid_configuration))
+            .route("/admin/signing-keys/rotate", post(routes::rotate_signing_key))
+            .route("/admin/oauth/clients", post(routes::register_oauth_client))
+            .route("/admin/service-accounts", post(routes::create_service_account))
+            .route(
+                "/admin/service-accounts/:id/rotate-secret",
+                post(routes::rotate_service_account_secret),
+            )
+            .route("/admin/service-accounts/:id/disable", post(routes::disable_service_account))
             .with_state(app_state)
             .layer(cors)
             .layer(
@@ -57,7 +113,10 @@ impl Application {
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Application {
             address,
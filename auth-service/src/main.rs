@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use auth_service::{app_state::AppState, domain::{EmailClient, TwoFACode, TwoFACodeStore}, get_postgres_pool, get_redis_client, services::{data_stores::{HashmapTwoFACodeStore, HashmapUserStore, HashsetBannedTokenStore, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore}, MockEmailClient}, utils::{init_tracing, prod, DATABASE_URL, REDIS_HOST_NAME}, Application};
+use auth_service::{domain::TrustedProxies, app_state::{AppState, EmailClientType, SigningKeyStoreType, SessionLimit, SessionLimitPolicy, UnverifiedLoginPolicy}, get_postgres_pool, get_redis_client, services::{data_stores::{PostgresRecoveryCodeStore, PostgresTotpStore, PostgresUserStore, PostgresOAuthClientStore, PostgresOAuthConsentStore, PostgresServiceAccountStore, PostgresApiKeyStore, PostgresWebauthnCredentialStore, RedisAuthorizationCodeStore, RedisBannedTokenStore, RedisCooldownStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisTwoFACodeStore, RedisMagicLinkStore, RedisWebauthnChallengeStore, RedisSessionStore, RedisAccountLockoutStore, RedisRateLimitStore, RedisSigningKeyStore}, MockEmailClient, SmtpCredentials, SmtpEmailClient, SmtpSettings, SmtpTls}, utils::{init_tracing, prod, run_key_ring_sync, run_scheduled_key_rotation, sync_key_ring, DATABASE_URL, JWT_SIGNING_KEY_ROTATION_SECONDS, KEY_RING, KEY_RING_SYNC_SECONDS, MAX_SESSIONS_PER_USER, REDIS_HOST_NAME, MAX_TWO_FA_ATTEMPTS, SESSION_LIMIT_POLICY, SMTP_HOST, SMTP_PASSWORD, SMTP_POOL_MAX_SIZE, SMTP_PORT, SMTP_SENDER, SMTP_TIMEOUT_SECONDS, SMTP_TLS, SMTP_USERNAME, TRUSTED_PROXIES, UNVERIFIED_LOGIN_POLICY}, Application};
 use sqlx::PgPool;
 use tokio::sync::RwLock;
 
@@ -8,25 +8,122 @@ use tokio::sync::RwLock;
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
+
+    // Fail at startup rather than on the first login if the signing key is misconfigured
+    lazy_static::initialize(&KEY_RING);
     
     // We will use this PostgreSQL pool in the next task! 
     let pg_pool = configure_postgresql().await;
     let redis_connection = Arc::new(RwLock::new(configure_redis()));
 
+    // Keys generated by any instance are shared through Redis, so tokens verify everywhere
+    let signing_key_store: SigningKeyStoreType = Arc::new(RwLock::new(RedisSigningKeyStore::new(
+        redis_connection.clone())));
+
+    sync_key_ring(&signing_key_store)
+        .await
+        .expect("Failed to load signing keys from Redis");
+
+    tokio::spawn(run_key_ring_sync(
+        signing_key_store.clone(),
+        Duration::from_secs(KEY_RING_SYNC_SECONDS),
+    ));
+
+    if let Some(seconds) = *JWT_SIGNING_KEY_ROTATION_SECONDS {
+        tokio::spawn(run_scheduled_key_rotation(
+            signing_key_store.clone(),
+            Duration::from_secs(seconds),
+        ));
+    }
+
     // let user_store = Arc::new(RwLock::new(HashmapUserStore::default()));
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+
+    let totp_store = Arc::new(RwLock::new(PostgresTotpStore::new(pg_pool.clone())));
+
+    let recovery_code_store = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+
+    let webauthn_credential_store = Arc::new(RwLock::new(PostgresWebauthnCredentialStore::new(pg_pool.clone())));
+
+    let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+
+    let oauth_consent_store = Arc::new(RwLock::new(PostgresOAuthConsentStore::new(pg_pool.clone())));
+
+    let service_account_store = Arc::new(RwLock::new(PostgresServiceAccountStore::new(pg_pool.clone())));
+
+    let api_key_store = Arc::new(RwLock::new(PostgresApiKeyStore::new(pg_pool)));
     
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
         redis_connection.clone(),
     )));
     
     let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
+        redis_connection.clone())));
+
+    let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+        redis_connection.clone())));
+
+    let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+        redis_connection.clone())));
+
+    let cooldown_store = Arc::new(RwLock::new(RedisCooldownStore::new(redis_connection.clone())));
+
+    let webauthn_challenge_store = Arc::new(RwLock::new(RedisWebauthnChallengeStore::new(
+        redis_connection.clone())));
+
+    let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_connection.clone())));
+
+    let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_connection.clone())));
+
+    let account_lockout_store = Arc::new(RwLock::new(RedisAccountLockoutStore::new(
+        redis_connection.clone())));
+
+    let rate_limit_store = Arc::new(RwLock::new(RedisRateLimitStore::new(
+        redis_connection.clone())));
+
+    let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
         redis_connection)));
+
+    let unverified_login_policy: UnverifiedLoginPolicy = UNVERIFIED_LOGIN_POLICY
+        .parse()
+        .expect("UNVERIFIED_LOGIN_POLICY must be either \"block\" or \"warn\".");
+
+    let session_limit_policy: SessionLimitPolicy = SESSION_LIMIT_POLICY
+        .parse()
+        .expect("SESSION_LIMIT_POLICY must be either \"reject\" or \"evict_oldest\".");
     
-    let email_client = Arc::new(RwLock::new(MockEmailClient{}));
+    let email_client = configure_email_client();
 
-    let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client);
+    let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client)
+        .with_max_two_fa_attempts(*MAX_TWO_FA_ATTEMPTS)
+        .with_refresh_token_store(refresh_token_store)
+        .with_password_reset_token_store(password_reset_token_store)
+        .with_cooldown_store(cooldown_store)
+        .with_unverified_login_policy(unverified_login_policy)
+        .with_totp_store(totp_store)
+        .with_recovery_code_store(recovery_code_store)
+        .with_webauthn_credential_store(webauthn_credential_store)
+        .with_webauthn_challenge_store(webauthn_challenge_store)
+        .with_magic_link_store(magic_link_store)
+        .with_oauth_client_store(oauth_client_store)
+        .with_oauth_consent_store(oauth_consent_store)
+        .with_authorization_code_store(authorization_code_store)
+        .with_service_account_store(service_account_store)
+        .with_api_key_store(api_key_store)
+        .with_session_store(session_store)
+        .with_account_lockout_store(account_lockout_store)
+        .with_rate_limit_store(rate_limit_store)
+        .with_signing_key_store(signing_key_store)
+        .with_trusted_proxies(TrustedProxies(TRUSTED_PROXIES.clone()));
     
+    let app_state = match *MAX_SESSIONS_PER_USER {
+        Some(max_sessions) => app_state.with_session_limit(SessionLimit {
+            max_sessions,
+            policy: session_limit_policy,
+        }),
+        None => app_state,
+    };
+
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
@@ -49,6 +146,37 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
+fn configure_email_client() -> EmailClientType {
+    let Some(host) = SMTP_HOST.clone() else {
+        tracing::warn!("SMTP_HOST is not set, emails are only logged and never sent");
+        return Arc::new(RwLock::new(MockEmailClient{}));
+    };
+
+    let tls: SmtpTls = SMTP_TLS
+        .parse()
+        .expect("SMTP_TLS must be either \"starttls\", \"tls\" or \"none\".");
+
+    let credentials = match (SMTP_USERNAME.clone(), SMTP_PASSWORD.clone()) {
+        (Some(username), Some(password)) => Some(SmtpCredentials { username, password }),
+        (None, None) => None,
+        _ => panic!("SMTP_USERNAME and SMTP_PASSWORD must be set together."),
+    };
+
+    let settings = SmtpSettings {
+        host,
+        port: *SMTP_PORT,
+        tls,
+        credentials,
+        sender: SMTP_SENDER.clone().expect("SMTP_SENDER must be set when SMTP_HOST is."),
+        timeout: Duration::from_secs(*SMTP_TIMEOUT_SECONDS),
+        pool_max_size: *SMTP_POOL_MAX_SIZE,
+    };
+
+    let email_client = SmtpEmailClient::new(settings).expect("Failed to create SMTP email client");
+
+    Arc::new(RwLock::new(email_client))
+}
+
 fn configure_redis() -> redis::Connection {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
@@ -0,0 +1,128 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AccountLockId, AccountLockoutStoreError, AuthAPIError, Email, UserStoreError},
+    utils::{
+        auth::ACCOUNT_LOCK_SECONDS, generate_account_unlock_token, validate_account_unlock_token,
+        AUTH_SERVICE_URL,
+    },
+};
+
+// Failed logins allowed within the window before the account is locked
+pub const MAX_FAILED_LOGINS: u32 = 5;
+pub const FAILED_LOGIN_WINDOW_SECONDS: u64 = 60 * 15;
+
+#[tracing::instrument(name = "Unlock account", skip_all)]
+// Lift the lock named in the emailed unlock link, so the user can log in again right away
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, lock_id) =
+        validate_account_unlock_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state
+        .account_lockout_store
+        .write()
+        .await
+        .unlock_account(&email, &lock_id)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(AccountLockoutStoreError::LockNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Fails with AccountLocked while the account is locked, whatever the password
+pub(crate) async fn check_account_lock(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    match state
+        .account_lockout_store
+        .read()
+        .await
+        .get_lock(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+    {
+        Some(retry_after_seconds) => Err(AuthAPIError::AccountLocked { retry_after_seconds }),
+        None => Ok(()),
+    }
+}
+
+// Count a failed login and return the error to respond with. The failure that reaches
+// MAX_FAILED_LOGINS locks the account and emails the user a link to unlock it.
+pub(crate) async fn record_failed_login(state: &AppState, email: &Email) -> AuthAPIError {
+    match lock_account_if_needed(state, email).await {
+        Ok(true) => AuthAPIError::AccountLocked {
+            retry_after_seconds: ACCOUNT_LOCK_SECONDS as u64,
+        },
+        Ok(false) => AuthAPIError::IncorrectCredentials,
+        Err(e) => AuthAPIError::UnexpectedError(e),
+    }
+}
+
+async fn lock_account_if_needed(state: &AppState, email: &Email) -> Result<bool> {
+    let mut account_lockout_store = state.account_lockout_store.write().await;
+
+    let failed_logins = account_lockout_store
+        .record_failed_login(email, FAILED_LOGIN_WINDOW_SECONDS)
+        .await?;
+
+    if failed_logins < MAX_FAILED_LOGINS {
+        return Ok(false);
+    }
+
+    tracing::warn!("Too many failed logins, locking account");
+
+    let lock_id = AccountLockId::default();
+
+    account_lockout_store
+        .lock_account(email, lock_id.clone(), ACCOUNT_LOCK_SECONDS as u64)
+        .await?;
+
+    drop(account_lockout_store);
+
+    // Emails that aren't registered get locked all the same, so the response can't be used to
+    // find out which are. Failing to send the email only means waiting for the lock to end.
+    if let Err(e) = send_unlock_email(state, email, &lock_id).await {
+        tracing::error!("Failed to send account unlock email: {:?}", e);
+    }
+
+    Ok(true)
+}
+
+#[tracing::instrument(name = "Send account unlock email", skip_all)]
+async fn send_unlock_email(state: &AppState, email: &Email, lock_id: &AccountLockId) -> Result<()> {
+    match state.user_store.read().await.get_user(email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    let token = generate_account_unlock_token(email, lock_id)
+        .wrap_err("failed to create account unlock token")?;
+
+    let content = format!(
+        "Your account was locked for {} minutes after too many failed logins.\n\
+         If it was you, use the following link to unlock it right away: {}/?unlock_token={}\n\
+         If it wasn't, someone may be trying to guess your password. Consider changing it.",
+        ACCOUNT_LOCK_SECONDS / 60,
+        *AUTH_SERVICE_URL,
+        token
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Your account has been locked", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: String,
+}
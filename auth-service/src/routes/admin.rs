@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, OAuthClient, OAuthClientId, RedirectUri, Scope, ServiceAccount,
+        ServiceAccountId, ServiceAccountSecret, ServiceAccountStoreError, OPENID_SCOPE,
+    },
+    utils::{auth, ADMIN_API_TOKEN},
+};
+
+#[tracing::instrument(name = "Rotate signing key", skip_all)]
+pub async fn rotate_signing_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers)?;
+
+    let (kid, activates_at) = auth::rotate_signing_key(&state.signing_key_store)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(RotateSigningKeyResponse { kid, activates_at }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateSigningKeyResponse {
+    pub kid: String,
+    // Unix timestamp from which the new key signs tokens
+    #[serde(rename = "activatesAt")]
+    pub activates_at: i64,
+}
+
+#[tracing::instrument(name = "Register OAuth client", skip_all)]
+// Registers a public client of the authorization code flow, see /authorize
+pub async fn register_oauth_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<RegisterOAuthClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers)?;
+
+    let name = request.name.trim().to_owned();
+    if name.is_empty() || request.redirect_uris.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let redirect_uris = request
+        .redirect_uris
+        .into_iter()
+        .map(RedirectUri::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let client = OAuthClient {
+        client_id: OAuthClientId::default(),
+        name,
+        redirect_uris,
+    };
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(OAuthClientResponse {
+        client_id: client.client_id.as_ref().to_owned(),
+        name: client.name,
+        redirect_uris: client
+            .redirect_uris
+            .iter()
+            .map(|uri| uri.as_ref().to_owned())
+            .collect(),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[derive(Deserialize)]
+pub struct RegisterOAuthClientRequest {
+    pub name: String,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthClientResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: String,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+}
+
+#[tracing::instrument(name = "Create service account", skip_all)]
+// Creates a machine identity for a backend job, see the client credentials grant at /token. The
+// secret is only returned here and by `rotate_service_account_secret`.
+pub async fn create_service_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateServiceAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers)?;
+
+    let name = request.name.trim().to_owned();
+    let scope = Scope::parse(&request.scope).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Service accounts are not users, there is nothing for OpenID Connect to say about them
+    if name.is_empty() || scope.has(OPENID_SCOPE) {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let secret = ServiceAccountSecret::default();
+    let account = ServiceAccount::new(name, scope, &secret);
+
+    state
+        .service_account_store
+        .write()
+        .await
+        .add_account(account.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(ServiceAccountResponse::new(&account, Some(secret)));
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "Rotate service account secret", skip_all)]
+// Replaces the secret of a service account. Tokens issued with the old secret stay valid until
+// they expire.
+pub async fn rotate_service_account_secret(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers)?;
+
+    let id = ServiceAccountId::parse(id).map_err(|_| AuthAPIError::ServiceAccountNotFound)?;
+    let secret = ServiceAccountSecret::default();
+
+    let mut service_account_store = state.service_account_store.write().await;
+
+    service_account_store
+        .update_secret(&id, secret.hash())
+        .await
+        .map_err(service_account_store_error)?;
+
+    let account = service_account_store
+        .get_account(&id)
+        .await
+        .map_err(service_account_store_error)?;
+
+    Ok(Json(ServiceAccountResponse::new(&account, Some(secret))))
+}
+
+#[tracing::instrument(name = "Disable service account", skip_all)]
+// Stops a service account from getting new tokens. Tokens it already has are rejected by
+// /verify-token from now on.
+pub async fn disable_service_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers)?;
+
+    let id = ServiceAccountId::parse(id).map_err(|_| AuthAPIError::ServiceAccountNotFound)?;
+
+    let mut service_account_store = state.service_account_store.write().await;
+
+    service_account_store
+        .set_disabled(&id, true)
+        .await
+        .map_err(service_account_store_error)?;
+
+    let account = service_account_store
+        .get_account(&id)
+        .await
+        .map_err(service_account_store_error)?;
+
+    Ok(Json(ServiceAccountResponse::new(&account, None)))
+}
+
+fn service_account_store_error(e: ServiceAccountStoreError) -> AuthAPIError {
+    match e {
+        ServiceAccountStoreError::AccountNotFound => AuthAPIError::ServiceAccountNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateServiceAccountRequest {
+    pub name: String,
+    // Space separated, the most a token of the account can be granted
+    pub scope: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceAccountResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret", default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub name: String,
+    pub scope: String,
+    pub disabled: bool,
+}
+
+impl ServiceAccountResponse {
+    fn new(account: &ServiceAccount, secret: Option<ServiceAccountSecret>) -> Self {
+        Self {
+            client_id: account.id.as_ref().to_owned(),
+            client_secret: secret.map(|secret| secret.as_ref().to_owned()),
+            name: account.name.clone(),
+            scope: account.scope.to_string(),
+            disabled: account.disabled,
+        }
+    }
+}
+
+// Admin routes expect `Authorization: Bearer <ADMIN_API_TOKEN>` and are disabled when it is not set
+fn authorize_admin(headers: &HeaderMap) -> Result<(), AuthAPIError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    let admin_token = ADMIN_API_TOKEN.as_ref().ok_or(AuthAPIError::InvalidToken)?;
+
+    // Compare digests so the comparison time does not depend on how much of the token matched
+    if Sha256::digest(token.as_bytes()) != Sha256::digest(admin_token.as_bytes()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(())
+}
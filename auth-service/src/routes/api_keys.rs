@@ -0,0 +1,153 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        ApiKey, ApiKeyId, ApiKeyRecord, ApiKeyStoreError, AuthAPIError, Scope,
+        MAX_API_KEY_LIFETIME_DAYS,
+    },
+    utils::get_authenticated_email,
+};
+
+#[tracing::instrument(name = "Create API key", skip_all)]
+// Create a personal API key for scripts and CI. The key is only stored hashed, this response is
+// the only time it is shown.
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let name = request.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let scope = match request.scope {
+        Some(scope) => Scope::parse(&scope).map_err(|_| AuthAPIError::InvalidCredentials)?,
+        None => Scope::default(),
+    };
+
+    let expires_at = match request.expires_in_days {
+        Some(days) if (1..=MAX_API_KEY_LIFETIME_DAYS).contains(&days) => {
+            Some(Utc::now().timestamp() + i64::from(days) * 60 * 60 * 24)
+        }
+        Some(_) => return Err(AuthAPIError::InvalidCredentials),
+        None => None,
+    };
+
+    let key = ApiKey::default();
+    let record = ApiKeyRecord::new(&key, email, name, scope, expires_at);
+
+    state
+        .api_key_store
+        .write()
+        .await
+        .add_key(record.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(CreateApiKeyResponse {
+        key: key.as_ref().to_owned(),
+        api_key: ApiKeyResponse::from(record),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "List API keys", skip_all)]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let keys = state
+        .api_key_store
+        .read()
+        .await
+        .list_keys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(ListApiKeysResponse {
+        api_keys: keys.into_iter().map(ApiKeyResponse::from).collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Revoke API key", skip_all)]
+// The key stops working right away
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let id = ApiKeyId::parse(id).map_err(|_| AuthAPIError::ApiKeyNotFound)?;
+
+    match state.api_key_store.write().await.revoke_key(&email, &id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(ApiKeyStoreError::KeyNotFound) => Err(AuthAPIError::ApiKeyNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    // Space separated. Keys without a scope act for the user without restrictions.
+    pub scope: Option<String>,
+    // Keys without an expiry are valid until they are revoked
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    pub key: String,
+    #[serde(rename = "apiKey")]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListApiKeysResponse {
+    #[serde(rename = "apiKeys")]
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+// Everything about a key except the key itself. Timestamps are Unix timestamps (seconds).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scope: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+}
+
+impl From<ApiKeyRecord> for ApiKeyResponse {
+    fn from(record: ApiKeyRecord) -> Self {
+        Self {
+            id: record.id.as_ref().to_owned(),
+            name: record.name,
+            prefix: record.prefix,
+            scope: record.scope.to_string(),
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+            last_used_at: record.last_used_at,
+        }
+    }
+}
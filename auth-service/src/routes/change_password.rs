@@ -0,0 +1,113 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthMethod, Authentication, ClientInfo, Password},
+    utils::get_authenticated_email,
+};
+
+use super::{check_account_lock, record_failed_login, start_session};
+
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match get_authenticated_email(&jar, &state.banned_token_store).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // A current password that doesn't meet the policy can't be the right one
+    let current_password = match Password::parse(request.current_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    let new_password = match Password::parse(request.new_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Wrong current passwords count toward the same lockout as failed logins, so a stolen
+    // session can't be used to guess the password either
+    if let Err(e) = check_account_lock(&state, &email).await {
+        return (jar, Err(e));
+    }
+
+    // Checking the password is slow, logins and signups go on meanwhile
+    let password_valid = state
+        .user_store
+        .read()
+        .await
+        .validate_user(&email, &current_password)
+        .await
+        .is_ok();
+
+    if !password_valid {
+        return (jar, Err(record_failed_login(&state, &email).await));
+    }
+
+    if let Err(e) = state
+        .account_lockout_store
+        .write()
+        .await
+        .clear_failed_logins(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, new_password)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // End every session of the user, including this one, then start a fresh session below
+    if let Err(e) = state
+        .banned_token_store
+        .write()
+        .await
+        .ban_user_tokens(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_user_sessions(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // The new session starts with the password the user just entered
+    let authentication = Authentication::now(&[AuthMethod::Password]);
+
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &email, authentication, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    (jar.add(auth_cookie).add(refresh_cookie), Ok(StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::State,
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthMethod, OAuthError, PrincipalType},
+    utils::AUTH_SERVICE_URL,
+};
+
+use super::{authenticate_service_account, verify_any_token, VerifiedToken};
+
+#[tracing::instrument(name = "Introspect", skip_all)]
+// Tells resource servers who a token belongs to, see RFC 7662. Only service accounts may ask, so
+// tokens can't be probed anonymously. Tokens that are invalid for any reason are reported as
+// inactive rather than as an error.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectionRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate_service_account(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    // Both kinds of token are told apart by their format, so `token_type_hint` is not needed
+    let response = match verify_any_token(&state, &request.token).await {
+        Some(verified) => IntrospectionResponse::from(&verified),
+        None => IntrospectionResponse::inactive(),
+    };
+
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Everything but `active` is left out for inactive tokens. `principal`, `auth_time`, `amr` and
+// `mfa` are extensions, `mfa` tells whether the user completed 2FA when logging in. It is left
+// out for service accounts and API keys, which don't log in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<PrincipalType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mfa: Option<bool>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+}
+
+impl From<&VerifiedToken> for IntrospectionResponse {
+    fn from(verified: &VerifiedToken) -> Self {
+        let active = Self {
+            active: true,
+            token_type: Some("Bearer".to_owned()),
+            iss: Some(AUTH_SERVICE_URL.clone()),
+            ..Self::default()
+        };
+
+        match verified {
+            VerifiedToken::Jwt(claims) => {
+                let mfa = claims.principal.is_user().then(|| {
+                    claims
+                        .amr
+                        .iter()
+                        .any(|method| method == AuthMethod::MultiFactor.as_ref())
+                });
+
+                Self {
+                    scope: claims.scope.clone(),
+                    client_id: claims.client_id.clone(),
+                    exp: Some(claims.exp as i64),
+                    iat: claims.iat,
+                    sub: Some(claims.sub.clone()),
+                    jti: claims.jti.clone(),
+                    principal: Some(claims.principal),
+                    auth_time: claims.auth_time,
+                    amr: claims.amr.clone(),
+                    mfa,
+                    ..active
+                }
+            }
+            VerifiedToken::ApiKey(record) => Self {
+                scope: Some(record.scope.to_string()).filter(|scope| !scope.is_empty()),
+                exp: record.expires_at,
+                iat: Some(record.created_at),
+                sub: Some(record.email.as_ref().to_owned()),
+                principal: Some(PrincipalType::User),
+                ..active
+            },
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use axum::{response::IntoResponse, Json};
+
+use crate::{domain::AuthAPIError, utils::read_key_ring};
+
+// Publish the public signing keys so other services can verify auth tokens without calling us.
+// Retired keys stay listed until the tokens they signed have expired.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks() -> Result<impl IntoResponse, AuthAPIError> {
+    let key_ring = read_key_ring().map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(key_ring.jwks()))
+}
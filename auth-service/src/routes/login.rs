@@ -2,15 +2,18 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode}, utils::generate_auth_cookie};
+use crate::{app_state::{AppState, UnverifiedLoginPolicy}, domain::{AuthAPIError, AuthMethod, Authentication, ClientInfo, Email, LoginAttemptId, Password, TwoFACode, TwoFAMethod}};
+
+use super::{check_account_lock, record_failed_login, start_session};
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(request): Json<LoginRequest>) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     
-    let user_store = &state.user_store.read().await;
+    let user_store = state.user_store.read().await;
     
     let password = match Password::parse(request.password) {
         Ok(password) => password,
@@ -22,9 +25,26 @@ pub async fn login(
         _ => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
     
+    // A locked account can't be logged into even with the right password, or the lock would
+    // not slow down guessing at all
+    if let Err(e) = check_account_lock(&state, &email).await {
+        return (jar, Err(e));
+    }
+
     let user_validated = user_store.validate_user(&email, &password).await.is_ok();
     if !user_validated {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+        drop(user_store);
+        return (jar, Err(record_failed_login(&state, &email).await));
+    }
+
+    if let Err(e) = state
+        .account_lockout_store
+        .write()
+        .await
+        .clear_failed_logins(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
     
     // let auth_cookie= match generate_auth_cookie(&email) {
@@ -37,20 +57,38 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    // Emailing the 2FA code and starting the session can take a while, don't hold up signups
+    // and password changes meanwhile
+    drop(user_store);
+
     // let update_jar = jar.add(auth_cookie);
 
+    let warning = match (user.verified, state.unverified_login_policy) {
+        (true, _) => None,
+        (false, UnverifiedLoginPolicy::Block) => return (jar, Err(AuthAPIError::EmailNotVerified)),
+        (false, UnverifiedLoginPolicy::Warn) => Some(UNVERIFIED_EMAIL_WARNING.to_owned()),
+    };
+
     // Handle request based on user's 2FA configuration
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user.email, user.two_fa_method, &state, jar, warning).await,
+        false => {
+            let authentication = Authentication::now(&[AuthMethod::Password]);
+            handle_no_2fa(&user.email, authentication, client, &state, jar, warning).await
+        }
     }
 }
 
+pub(crate) const UNVERIFIED_EMAIL_WARNING: &str =
+    "Your email address is not verified yet, use the link we sent you to verify it.";
+
 #[tracing::instrument(name = "Handle 2FA", skip_all)]
-async fn handle_2fa(
+pub(crate) async fn handle_2fa(
     email: &Email,
+    method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
+    warning: Option<String>,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
@@ -58,35 +96,47 @@ async fn handle_2fa(
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
-    let result = state.email_client
-            .read()
-            .await;
-            // .send_email(&email, "subject", "content");
-
-        if let Err(e) = state
+    // The login attempt is stored for TOTP users as well, so verify_2fa can tell that the
+    // password was checked. Their code is never sent, it only serves as a placeholder.
+    if let Err(e) = state
         .two_fa_code_store
         .write()
         .await
         .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if method == TwoFAMethod::Email {
+        if let Err(e) = state
+            .email_client
+            .read()
+            .await
+            .send_email(email, "2FA Code", two_fa_code.as_ref())
+            .await
         {
-            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            return (jar, Err(AuthAPIError::UnexpectedError(e)));
         }
-        
-    // match result.send_email(email, "2FA Code", two_fa_code.as_ref()).await {
-    //     Ok(_) => {},
-    //     Err(err) => { return (jar, Err(AuthAPIError::UnexpectedError)) }
-    // }
-    if let Err(e) = result
-    .send_email(email, "2FA Code", two_fa_code.as_ref())
-    .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
     }
 
+    let recovery_codes_remaining = match state
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(email)
+        .await
+    {
+        Ok(count) => count,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().to_owned(),
+        method: method.as_ref().to_owned(),
+        recovery_codes_remaining,
+        warning,
     }));
 
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
@@ -110,20 +160,24 @@ async fn handle_2fa(
 }
 
 #[tracing::instrument(name = "Handle no 2FA", skip_all)]
-async fn handle_no_2fa(
+pub(crate) async fn handle_no_2fa(
     email: &Email,
+    authentication: Authentication,
+    client: ClientInfo,
+    state: &AppState,
     jar: CookieJar,
+    warning: Option<String>,
 ) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
-    let auth_cookie= match generate_auth_cookie(email) {
-        Ok(auth_cookie) => auth_cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    let (auth_cookie, refresh_cookie) = match start_session(state, email, authentication, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
 
-    let update_jar = jar.add(auth_cookie);
+    let update_jar = jar.add(auth_cookie).add(refresh_cookie);
     
     (
         update_jar,
-        Ok((StatusCode::OK, Json(LoginResponse::RegularAuth)))
+        Ok((StatusCode::OK, Json(LoginResponse::RegularAuth(RegularAuthResponse { warning }))))
     )
 }
 
@@ -132,16 +186,31 @@ async fn handle_no_2fa(
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum LoginResponse {
-    RegularAuth,
+    RegularAuth(RegularAuthResponse),
     TwoFactorAuth(TwoFactorAuthResponse),
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegularAuthResponse {
+    // Set when the user is logged in despite an unverified email address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
 // If a user requires 2FA, this JSON body should be returned!
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    // "email", "totp" or "webauthn", tells the client how the user proves the second factor
+    #[serde(rename = "2FAMethod")]
+    pub method: String,
+    // Lets the client nag users who are about to run out of recovery codes
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
 }
 
 // #[derive(Serialize, PartialEq, Debug, Deserialize)]
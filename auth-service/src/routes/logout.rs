@@ -1,9 +1,7 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::{CookieJar};
 
-use color_eyre::eyre::Error;
-
-use crate::{app_state::AppState, domain::AuthAPIError, utils::{validate_token, JWT_COOKIE_NAME}};
+use crate::{app_state::AppState, domain::{AuthAPIError, Email, RefreshToken, RefreshTokenFamilyId, SessionStoreError}, utils::{ban_token, get_authenticated_email, validate_token, JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME}};
 
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(State(state): State<AppState>, jar: CookieJar) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -19,23 +17,80 @@ pub async fn logout(State(state): State<AppState>, jar: CookieJar) -> (CookieJar
     // TODO: Validate JWT token by calling `validate_token` from the auth service.
     // If the token is valid you can ignore the returned claims for now.
     // Return AuthAPIError::InvalidToken is validation fails.
-    match validate_token(&token, state.banned_token_store.clone()).await {
+    let claims = match validate_token(&token, state.banned_token_store.clone()).await {
         Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken))
     };
 
-        // Add token to banned list
-        if let Err(e) = state
+    // Add token to banned list
+    if let Err(e) = ban_token(&claims, &state.banned_token_store).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    }
+
+    // Revoke the refresh token family as well, otherwise the session
+    // could simply be resumed through the refresh route.
+    if let Some(token) = jar
+        .get(REFRESH_TOKEN_COOKIE_NAME)
+        .and_then(|cookie| RefreshToken::parse(cookie.value().to_owned()).ok())
+    {
+        let mut refresh_token_store = state.refresh_token_store.write().await;
+
+        if let Ok(record) = refresh_token_store.get_token(&token).await {
+            if let Err(e) = refresh_token_store.revoke_family(&record.family_id).await {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+        }
+    }
+
+    // The session no longer shows up in the user's list of sessions
+    let session = claims.sid.and_then(|sid| RefreshTokenFamilyId::parse(sid).ok());
+    if let (Some(session_id), Ok(email)) = (session, Email::parse(claims.sub)) {
+        match state
+            .session_store
+            .write()
+            .await
+            .remove_session(&email, &session_id)
+            .await
+        {
+            Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        }
+    }
+
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_TOKEN_COOKIE_NAME);
+
+    (jar, Ok(StatusCode::OK))
+}
+#[tracing::instrument(name = "Logout all", skip_all)]
+// Ends every session of the user, on every device. Bumping the user's token generation makes all
+// of their outstanding auth and refresh tokens invalid at once, see `BannedTokenStore::ban_user_tokens`.
+pub async fn logout_all(State(state): State<AppState>, jar: CookieJar) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match get_authenticated_email(&jar, &state.banned_token_store).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if let Err(e) = state
         .banned_token_store
         .write()
         .await
-        .store_token(token.to_owned())
+        .ban_user_tokens(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_user_sessions(&email)
         .await
     {
-       return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let jar = jar.remove(JWT_COOKIE_NAME);
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_TOKEN_COOKIE_NAME);
 
     (jar, Ok(StatusCode::OK))
-}
\ No newline at end of file
+}
@@ -0,0 +1,176 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthMethod, Authentication, ClientInfo, Email, MagicLinkId, MagicLinkStoreError, UserStoreError},
+    utils::{
+        auth::MAGIC_LINK_TTL_SECONDS, generate_magic_link_token, get_authenticated_email,
+        validate_magic_link_token, AUTH_SERVICE_URL,
+    },
+};
+
+use super::{handle_2fa, handle_no_2fa};
+
+// Minimum time between two magic links to the same address
+pub const MAGIC_LINK_RESEND_COOLDOWN_SECONDS: u64 = 60;
+
+#[tracing::instrument(name = "Request magic link", skip_all)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // The cooldown applies whether or not an account exists, so the response can't be used
+    // to find out which emails are registered
+    if let Some(retry_after_seconds) = state
+        .cooldown_store
+        .write()
+        .await
+        .start_cooldown(
+            &format!("magic_link:{}", email.as_ref()),
+            MAGIC_LINK_RESEND_COOLDOWN_SECONDS,
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+    {
+        return Err(AuthAPIError::TooManyRequests { retry_after_seconds });
+    }
+
+    // For the same reason, failures past this point are only logged
+    if let Err(e) = send_magic_link_email(&state, email).await {
+        tracing::error!("Failed to send magic link email: {:?}", e);
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Send magic link email", skip_all)]
+async fn send_magic_link_email(state: &AppState, email: Email) -> Result<()> {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if user.magic_link_enabled => {}
+        Ok(_) | Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    let link_id = MagicLinkId::default();
+    let token = generate_magic_link_token(&email, &link_id)
+        .wrap_err("failed to create magic link token")?;
+
+    // Replaces the link sent with any earlier request
+    state
+        .magic_link_store
+        .write()
+        .await
+        .add_link(email.clone(), link_id)
+        .await
+        .wrap_err("failed to store magic link")?;
+
+    let content = format!(
+        "Use the following link to log in: {}/?magic_link_token={}\n\
+         The link can be used once and expires in {} minutes. If you did not ask to log in, you can ignore this email.",
+        *AUTH_SERVICE_URL,
+        token,
+        MAGIC_LINK_TTL_SECONDS / 60
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(&email, "Your login link", &content)
+        .await
+}
+
+#[tracing::instrument(name = "Magic link callback", skip_all)]
+// Exchange the token from a magic link for the same response and cookies as `login`.
+// The link stands in for the password only, users with 2FA still have to pass /verify-2fa.
+pub async fn magic_link_callback(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<MagicLinkCallbackRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, link_id) = match validate_magic_link_token(&request.token) {
+        Ok(claims) => claims,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    match state
+        .magic_link_store
+        .write()
+        .await
+        .use_link(&email, &link_id)
+        .await
+    {
+        Ok(()) => {}
+        Err(MagicLinkStoreError::LinkNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // The user may have opted out after the link was sent
+    if !user.magic_link_enabled {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    // Following the link proves the user owns the email address
+    if !user.verified {
+        if let Err(e) = state.user_store.write().await.mark_verified(&email).await {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+    }
+
+    match user.requires_2fa {
+        true => handle_2fa(&user.email, user.two_fa_method, &state, jar, None).await,
+        false => {
+            let authentication = Authentication::now(&[AuthMethod::OneTimePassword]);
+            handle_no_2fa(&user.email, authentication, client, &state, jar, None).await
+        }
+    }
+}
+
+#[tracing::instrument(name = "Set magic link opt-in", skip_all)]
+// Lets the logged in user turn magic link logins on or off for their account
+pub async fn set_magic_link_opt_in(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<MagicLinkOptInRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    match state
+        .user_store
+        .write()
+        .await
+        .set_magic_link_enabled(&email, request.enabled)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkCallbackRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkOptInRequest {
+    pub enabled: bool,
+}
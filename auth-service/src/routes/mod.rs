@@ -1,11 +1,47 @@
+mod account_lockout;
+mod admin;
+mod api_keys;
+mod change_password;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth;
+mod oidc;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod revoke;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
 
+pub use account_lockout::*;
+pub use admin::*;
+pub use api_keys::*;
+pub use change_password::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oauth::*;
+pub use oidc::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use resend_2fa::*;
+pub use revoke::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
+pub use webauthn::*;
@@ -0,0 +1,566 @@
+use axum::{
+    extract::{Query, RawQuery, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, PRAGMA},
+        HeaderMap,
+    },
+    response::{IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Authentication, AuthorizationCode, AuthorizationCodeStoreError,
+        AuthorizationGrant, CodeChallenge, Email, OAuthClient, OAuthClientId,
+        OAuthClientStoreError, OAuthError, RedirectUri, Scope, ServiceAccount, ServiceAccountId,
+        ServiceAccountStoreError, CODE_CHALLENGE_METHOD_S256, OPENID_SCOPE,
+    },
+    utils::{
+        auth::TOKEN_TTL_SECONDS, generate_access_token, generate_id_token,
+        generate_service_account_token, get_authenticated_claims,
+    },
+};
+
+#[tracing::instrument(name = "Authorize", skip_all)]
+// Start of the authorization code flow. Users who are not logged in yet are sent to the login
+// page, and users who haven't consented to the client yet to the consent page. Both continue
+// the flow by coming back here once done. With prompt=none the client is told instead.
+pub async fn authorize(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    RawQuery(query): RawQuery,
+    Query(params): Query<AuthorizeParams>,
+) -> Response {
+    let request = match parse_authorization_request(&state, params).await {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+
+    let query = query.unwrap_or_default();
+
+    let Some((email, authentication)) = get_logged_in_user(&state, &jar, &request).await else {
+        if request.prompt_none {
+            return Redirect::to(&request.redirect.with_error(&OAuthError::LoginRequired))
+                .into_response();
+        }
+        return Redirect::to(&login_page_url(&[("oauth_authorize", &query)])).into_response();
+    };
+
+    match state
+        .oauth_consent_store
+        .read()
+        .await
+        .has_consent(&email, &request.client.client_id, &request.scope)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) if request.prompt_none => {
+            return Redirect::to(&request.redirect.with_error(&OAuthError::ConsentRequired))
+                .into_response()
+        }
+        Ok(false) => {
+            return Redirect::to(&login_page_url(&[
+                ("oauth_consent", &query),
+                ("client_name", &request.client.name),
+            ]))
+            .into_response()
+        }
+        Err(e) => return OAuthError::UnexpectedError(e.into()).into_response(),
+    }
+
+    match issue_authorization_code(&state, request, email, authentication).await {
+        Ok(redirect_uri) => Redirect::to(&redirect_uri).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[tracing::instrument(name = "Authorize consent", skip_all)]
+// Records the logged in user's answer on the consent page. Instead of redirecting, the response
+// tells the page where to send the user, since it is a fetch request.
+pub async fn authorize_consent(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(consent): Json<ConsentRequest>,
+) -> Response {
+    let request = match parse_authorization_request(&state, consent.params).await {
+        Ok(request) => request,
+        Err(AuthorizeError::Redirect(redirect_uri)) => {
+            return Json(ConsentResponse { redirect_to: redirect_uri }).into_response()
+        }
+        Err(e) => return e.into_response(),
+    };
+
+    let Some((email, authentication)) = get_logged_in_user(&state, &jar, &request).await else {
+        return AuthAPIError::InvalidToken.into_response();
+    };
+
+    if !consent.approved {
+        let error = OAuthError::AccessDenied("The user denied the request");
+        return Json(ConsentResponse {
+            redirect_to: request.redirect.with_error(&error),
+        })
+        .into_response();
+    }
+
+    if let Err(e) = state
+        .oauth_consent_store
+        .write()
+        .await
+        .grant_consent(&email, &request.client.client_id, &request.scope)
+        .await
+    {
+        return OAuthError::UnexpectedError(e.into()).into_response();
+    }
+
+    match issue_authorization_code(&state, request, email, authentication).await {
+        Ok(redirect_uri) => Json(ConsentResponse { redirect_to: redirect_uri }).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[tracing::instrument(name = "Token", skip_all)]
+// Issues access tokens, either for an authorization code or for the credentials of a service
+// account
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let response = match request.grant_type.as_deref() {
+        Some("authorization_code") => exchange_authorization_code(&state, request).await?,
+        Some("client_credentials") => {
+            issue_service_account_token(&state, &headers, request).await?
+        }
+        Some(_) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest("Missing grant_type")),
+    };
+
+    Ok(([(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")], Json(response)))
+}
+
+// Only public clients are supported, they prove they started the flow with the PKCE code
+// verifier instead of a client secret
+async fn exchange_authorization_code(
+    state: &AppState,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let (Some(code), Some(client_id), Some(redirect_uri), Some(code_verifier)) = (
+        request.code,
+        request.client_id,
+        request.redirect_uri,
+        request.code_verifier,
+    ) else {
+        return Err(OAuthError::InvalidRequest(
+            "code, client_id, redirect_uri and code_verifier are required",
+        ));
+    };
+
+    let client_id = OAuthClientId::parse(client_id)
+        .map_err(|_| OAuthError::InvalidClient("Unknown client"))?;
+
+    let code = AuthorizationCode::parse(code)
+        .map_err(|_| OAuthError::InvalidGrant("Invalid authorization code"))?;
+
+    // The code is used up even if the checks below fail, so the verifier can't be guessed
+    let grant = match state
+        .authorization_code_store
+        .write()
+        .await
+        .take_code(&code)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => {
+            return Err(OAuthError::InvalidGrant(
+                "The authorization code is invalid, expired or was already used",
+            ))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if grant.client_id != client_id {
+        return Err(OAuthError::InvalidGrant("The authorization code was issued to another client"));
+    }
+
+    if grant.redirect_uri.as_ref() != redirect_uri {
+        return Err(OAuthError::InvalidGrant("redirect_uri does not match the authorization request"));
+    }
+
+    if !grant.code_challenge.verify(&code_verifier) {
+        return Err(OAuthError::InvalidGrant("Invalid code_verifier"));
+    }
+
+    let access_token = generate_access_token(&grant, state.banned_token_store.clone())
+        .await
+        .map_err(OAuthError::UnexpectedError)?;
+
+    let id_token = grant
+        .scope
+        .has(OPENID_SCOPE)
+        .then(|| generate_id_token(&grant))
+        .transpose()
+        .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: Some(grant.scope.to_string()).filter(|scope| !scope.is_empty()),
+        id_token,
+    })
+}
+
+// The client credentials grant of RFC 6749 section 4.4. Service accounts get a token for the
+// requested scope, or for every scope they were created with if none is requested.
+async fn issue_service_account_token(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let account = authenticate_service_account(
+        state,
+        headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    let scope = match request.scope {
+        Some(scope) => {
+            Scope::parse(&scope).map_err(|_| OAuthError::InvalidScope("Invalid scope"))?
+        }
+        None => account.scope.clone(),
+    };
+
+    if !account.scope.contains(&scope) {
+        return Err(OAuthError::InvalidScope(
+            "The requested scope exceeds the scope of the service account",
+        ));
+    }
+
+    let access_token =
+        generate_service_account_token(&account, &scope).map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: Some(scope.to_string()).filter(|scope| !scope.is_empty()),
+        id_token: None,
+    })
+}
+
+// Service accounts are the only confidential clients. Disabled accounts fail to authenticate.
+pub(crate) async fn authenticate_service_account(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<ServiceAccount, OAuthError> {
+    let (client_id, client_secret) = read_client_credentials(headers, client_id, client_secret)?;
+
+    let id = ServiceAccountId::parse(client_id)
+        .map_err(|_| OAuthError::InvalidClient("Client authentication failed"))?;
+
+    let account = match state.service_account_store.read().await.get_account(&id).await {
+        Ok(account) => account,
+        Err(ServiceAccountStoreError::AccountNotFound) => {
+            return Err(OAuthError::InvalidClient("Client authentication failed"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if !account.verify_secret(&client_secret) || account.disabled {
+        return Err(OAuthError::InvalidClient("Client authentication failed"));
+    }
+
+    Ok(account)
+}
+
+// Confidential clients authenticate with HTTP Basic or with client_id and client_secret in the
+// request body, but not both, see RFC 6749 section 2.3.1
+fn read_client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<(String, String), OAuthError> {
+    let basic = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "));
+
+    match (basic, client_id, client_secret) {
+        (Some(credentials), None, None) => STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .and_then(|credentials| {
+                let (id, secret) = credentials.split_once(':')?;
+                Some((form_urldecode(id)?, form_urldecode(secret)?))
+            })
+            .ok_or(OAuthError::InvalidClient("Client authentication failed")),
+        (None, Some(client_id), Some(client_secret)) => {
+            Ok((client_id.to_owned(), client_secret.to_owned()))
+        }
+        (Some(_), _, _) => Err(OAuthError::InvalidRequest(
+            "Use only one client authentication method",
+        )),
+        _ => Err(OAuthError::InvalidClient("Client authentication is required")),
+    }
+}
+
+// The client id and secret are form-urlencoded before they're put in the Basic header
+fn form_urldecode(value: &str) -> Option<String> {
+    if value.contains(['&', '=']) {
+        return None;
+    }
+
+    Some(form_urlencoded::parse(value.as_bytes()).map(|(decoded, _)| decoded).collect())
+}
+
+// An authorization request that passed validation
+struct AuthorizationRequest {
+    client: OAuthClient,
+    redirect: ClientRedirect,
+    scope: Scope,
+    code_challenge: CodeChallenge,
+    nonce: Option<String>,
+    // Only check whether the user is logged in and has consented, never show a page
+    prompt_none: bool,
+    // How many seconds ago the user may have logged in at most
+    max_age: Option<i64>,
+}
+
+// Where the response to an authorization request is sent
+struct ClientRedirect {
+    redirect_uri: RedirectUri,
+    state: Option<String>,
+}
+
+impl ClientRedirect {
+    // The redirect URI with the response parameters, echoing `state` like the spec requires
+    fn with_params(&self, params: &[(&str, &str)]) -> String {
+        let mut params = params.to_vec();
+        if let Some(state) = &self.state {
+            params.push(("state", state));
+        }
+        self.redirect_uri.with_params(&params)
+    }
+
+    fn with_error(&self, error: &OAuthError) -> String {
+        self.with_params(&[
+            ("error", error.error_code()),
+            ("error_description", error.description()),
+        ])
+    }
+}
+
+enum AuthorizeError {
+    // The client or redirect URI is invalid, so the error can only be shown to the user
+    Invalid(OAuthError),
+    // Any other error is reported to the client through the redirect URI
+    Redirect(String),
+}
+
+impl IntoResponse for AuthorizeError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthorizeError::Invalid(e) => e.into_response(),
+            AuthorizeError::Redirect(redirect_uri) => Redirect::to(&redirect_uri).into_response(),
+        }
+    }
+}
+
+async fn parse_authorization_request(
+    state: &AppState,
+    params: AuthorizeParams,
+) -> Result<AuthorizationRequest, AuthorizeError> {
+    let client_id = params
+        .client_id
+        .and_then(|client_id| OAuthClientId::parse(client_id).ok())
+        .ok_or(AuthorizeError::Invalid(OAuthError::InvalidClient("Unknown client")))?;
+
+    let client = match state.oauth_client_store.read().await.get_client(&client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(AuthorizeError::Invalid(OAuthError::InvalidClient("Unknown client")))
+        }
+        Err(e) => return Err(AuthorizeError::Invalid(OAuthError::UnexpectedError(e.into()))),
+    };
+
+    let redirect_uri = params
+        .redirect_uri
+        .and_then(|uri| client.find_redirect_uri(&uri).cloned())
+        .ok_or(AuthorizeError::Invalid(OAuthError::InvalidRequest(
+            "redirect_uri is not registered for the client",
+        )))?;
+
+    let redirect = ClientRedirect {
+        redirect_uri,
+        state: params.state,
+    };
+
+    let error = |e: OAuthError| AuthorizeError::Redirect(redirect.with_error(&e));
+
+    if params.response_type.as_deref() != Some("code") {
+        return Err(error(OAuthError::UnsupportedResponseType));
+    }
+
+    let scope = Scope::parse(params.scope.as_deref().unwrap_or_default())
+        .map_err(|_| error(OAuthError::InvalidScope("Invalid scope")))?;
+
+    if params.code_challenge_method.as_deref() != Some(CODE_CHALLENGE_METHOD_S256) {
+        return Err(error(OAuthError::InvalidRequest(
+            "PKCE with code_challenge_method S256 is required",
+        )));
+    }
+
+    let code_challenge = params
+        .code_challenge
+        .and_then(|challenge| CodeChallenge::parse(challenge).ok())
+        .ok_or_else(|| error(OAuthError::InvalidRequest("Invalid code_challenge")))?;
+
+    let prompt: Vec<&str> = params.prompt.as_deref().unwrap_or_default().split(' ').collect();
+    let prompt_none = prompt.contains(&"none");
+
+    if prompt_none && prompt.len() > 1 {
+        return Err(error(OAuthError::InvalidRequest("prompt=none can't be combined")));
+    }
+
+    let max_age = match params.max_age {
+        Some(max_age) => match max_age.parse::<i64>() {
+            Ok(max_age) if max_age >= 0 => Some(max_age),
+            _ => return Err(error(OAuthError::InvalidRequest("Invalid max_age"))),
+        },
+        None => None,
+    };
+
+    Ok(AuthorizationRequest {
+        client,
+        redirect,
+        scope,
+        code_challenge,
+        nonce: params.nonce,
+        prompt_none,
+        max_age,
+    })
+}
+
+// The user logged in with the jwt cookie, unless they have to log in again because the login
+// is older than max_age or happened before logins were recorded
+async fn get_logged_in_user(
+    state: &AppState,
+    jar: &CookieJar,
+    request: &AuthorizationRequest,
+) -> Option<(Email, Authentication)> {
+    let claims = get_authenticated_claims(jar, &state.banned_token_store).await.ok()?;
+
+    let authentication = claims.authentication()?;
+
+    if let Some(max_age) = request.max_age {
+        if Utc::now().timestamp() - authentication.time > max_age {
+            return None;
+        }
+    }
+
+    Some((Email::parse(claims.sub).ok()?, authentication))
+}
+
+async fn issue_authorization_code(
+    state: &AppState,
+    request: AuthorizationRequest,
+    email: Email,
+    authentication: Authentication,
+) -> Result<String, AuthorizeError> {
+    let code = AuthorizationCode::default();
+
+    let grant = AuthorizationGrant {
+        client_id: request.client.client_id.clone(),
+        redirect_uri: request.redirect.redirect_uri.clone(),
+        email,
+        scope: request.scope.clone(),
+        code_challenge: request.code_challenge.clone(),
+        nonce: request.nonce.clone(),
+        authentication,
+    };
+
+    if let Err(e) = state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(code.clone(), grant)
+        .await
+    {
+        return Err(AuthorizeError::Invalid(OAuthError::UnexpectedError(e.into())));
+    }
+
+    Ok(request.redirect.with_params(&[("code", code.as_ref())]))
+}
+
+// The auth service's own UI, which handles login and consent for /authorize
+fn login_page_url(params: &[(&str, &str)]) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    format!("/?{}", query)
+}
+
+// Everything is optional, so missing parameters are reported as OAuth errors
+#[derive(Deserialize)]
+pub struct AuthorizeParams {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    // OpenID Connect parameters, see OpenID Connect Core section 3.1.2.1
+    pub nonce: Option<String>,
+    pub prompt: Option<String>,
+    pub max_age: Option<String>,
+}
+
+// The parameters of the authorization request the consent page was shown for, and the answer
+#[derive(Deserialize)]
+pub struct ConsentRequest {
+    #[serde(flatten)]
+    pub params: AuthorizeParams,
+    pub approved: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsentResponse {
+    #[serde(rename = "redirectTo")]
+    pub redirect_to: String,
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub code_verifier: Option<String>,
+    // Client credentials grant
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Issued when the openid scope was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
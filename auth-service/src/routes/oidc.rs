@@ -0,0 +1,152 @@
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, OAuthError, Scope, UserStoreError, CODE_CHALLENGE_METHOD_S256,
+        EMAIL_SCOPE, OPENID_SCOPE,
+    },
+    utils::{read_key_ring, validate_token, AUTH_SERVICE_URL},
+};
+
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+// Lets OpenID Connect clients configure themselves from the issuer URL alone, see OpenID Connect
+// Discovery 1.0 section 4
+pub async fn openid_configuration() -> Result<impl IntoResponse, AuthAPIError> {
+    let algorithm = read_key_ring()
+        .map_err(AuthAPIError::UnexpectedError)?
+        .active()
+        .algorithm();
+
+    let issuer = AUTH_SERVICE_URL.clone();
+
+    Ok(Json(OpenIdConfiguration {
+        authorization_endpoint: format!("{}/authorize", issuer),
+        token_endpoint: format!("{}/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        introspection_endpoint: format!("{}/introspect", issuer),
+        revocation_endpoint: format!("{}/revoke", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        issuer,
+        scopes_supported: vec![OPENID_SCOPE.to_owned(), EMAIL_SCOPE.to_owned()],
+        response_types_supported: vec!["code".to_owned()],
+        response_modes_supported: vec!["query".to_owned()],
+        grant_types_supported: vec![
+            "authorization_code".to_owned(),
+            "client_credentials".to_owned(),
+        ],
+        subject_types_supported: vec!["public".to_owned()],
+        id_token_signing_alg_values_supported: vec![algorithm],
+        token_endpoint_auth_methods_supported: vec![
+            "none".to_owned(),
+            "client_secret_basic".to_owned(),
+            "client_secret_post".to_owned(),
+        ],
+        code_challenge_methods_supported: vec![CODE_CHALLENGE_METHOD_S256.to_owned()],
+        claims_supported: [
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "amr",
+            "email",
+            "email_verified",
+        ]
+        .map(str::to_owned)
+        .to_vec(),
+    }))
+}
+
+#[tracing::instrument(name = "User info", skip_all)]
+// Claims about the user an access token was issued for, see OpenID Connect Core section 5.3.
+// The email claims are only returned when the client was granted the email scope.
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OAuthError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(OAuthError::InvalidToken("Missing access token"))?;
+
+    let claims = validate_token(token, state.banned_token_store.clone())
+        .await
+        .map_err(|_| OAuthError::InvalidToken("The access token is invalid or expired"))?;
+
+    // Auth tokens from the jwt cookie carry no scope, they were never issued to a client. Service
+    // accounts can't be granted the openid scope.
+    let scope = claims
+        .scope
+        .as_deref()
+        .map(Scope::parse)
+        .transpose()
+        .map_err(|_| OAuthError::InvalidToken("The access token has an invalid scope"))?
+        .filter(|scope| {
+            claims.principal.is_user() && claims.client_id.is_some() && scope.has(OPENID_SCOPE)
+        })
+        .ok_or(OAuthError::InsufficientScope(
+            "The openid scope is required",
+        ))?;
+
+    let email = Email::parse(claims.sub)
+        .map_err(|_| OAuthError::InvalidToken("The access token has an invalid subject"))?;
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => {
+            return Err(OAuthError::InvalidToken("The user no longer exists"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    let (email, email_verified) = match scope.has(EMAIL_SCOPE) {
+        true => (Some(user.email.as_ref().to_owned()), Some(user.verified)),
+        false => (None, None),
+    };
+
+    Ok(Json(UserInfoResponse {
+        sub: user.email.as_ref().to_owned(),
+        email,
+        email_verified,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub response_modes_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
@@ -0,0 +1,133 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, Password, PasswordResetToken, PasswordResetTokenStoreError,
+        UserStoreError,
+    },
+    utils::{auth::PASSWORD_RESET_TOKEN_TTL_SECONDS, AUTH_SERVICE_URL},
+};
+
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // The response must not reveal whether an account exists for the email,
+    // so failures past this point are only logged.
+    if let Err(e) = send_password_reset_email(&state, email).await {
+        tracing::error!("Failed to send password reset email: {:?}", e);
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Send password reset email", skip_all)]
+async fn send_password_reset_email(state: &AppState, email: Email) -> Result<()> {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    let token = PasswordResetToken::default();
+
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(token.clone(), email.clone())
+        .await
+        .wrap_err("failed to store password reset token")?;
+
+    let content = format!(
+        "Use the following link to choose a new password: {}/?password_reset_token={}\n\
+         The link expires in {} minutes. If you did not request a password reset, you can ignore this email.",
+        *AUTH_SERVICE_URL,
+        token.as_ref(),
+        PASSWORD_RESET_TOKEN_TTL_SECONDS / 60
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(&email, "Reset your password", &content)
+        .await
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Check the new password before redeeming the single-use token
+    let password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let email = match state
+        .password_reset_token_store
+        .write()
+        .await
+        .take_token(&token)
+        .await
+    {
+        Ok(email) => email,
+        Err(PasswordResetTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    match state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, password)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Whoever knew the old password may still hold a session, end all of them
+    if let Err(e) = state
+        .banned_token_store
+        .write()
+        .await
+        .ban_user_tokens(&email)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_user_sessions(&email)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
@@ -0,0 +1,43 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RecoveryCode, RECOVERY_CODE_COUNT},
+    utils::get_authenticated_email,
+};
+
+#[tracing::instrument(name = "Generate recovery codes", skip_all)]
+// Create a new set of single-use recovery codes, replacing the previous set.
+// The codes are only stored hashed, this response is the only time they are shown.
+pub async fn generate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect();
+
+    if let Err(e) = state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(&email, codes.clone())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    Ok(Json(RecoveryCodesResponse {
+        recovery_codes: codes.iter().map(|code| code.as_ref().to_owned()).collect(),
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
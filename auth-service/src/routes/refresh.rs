@@ -0,0 +1,110 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use chrono::Utc;
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError},
+    utils::{
+        generate_auth_cookie, generate_refresh_cookie, get_token_generation,
+        REFRESH_TOKEN_COOKIE_NAME,
+    },
+};
+
+#[tracing::instrument(name = "Refresh", skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let cookie = match jar.get(REFRESH_TOKEN_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(cookie.value().to_owned()) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Hold the write lock until the token is marked as used,
+    // so two concurrent requests cannot both rotate the same token.
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.get_token(&token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    if record.revoked || record.is_expired() {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    // Banning a user's tokens, e.g. on password reset, covers their refresh tokens as well
+    match get_token_generation(&record.email, &state.banned_token_store).await {
+        Ok(generation) if record.generation < generation => {
+            return (jar, Err(AuthAPIError::InvalidToken))
+        }
+        Ok(_) => {}
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    }
+
+    // A token that was already rotated is being replayed. Either the client or an
+    // attacker holds a stolen copy, so nothing issued from this login can be trusted.
+    if record.used {
+        tracing::warn!("Refresh token reuse detected, revoking token family");
+
+        if let Err(e) = refresh_token_store.revoke_family(&record.family_id).await {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    if let Err(e) = refresh_token_store.mark_token_used(&token).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    drop(refresh_token_store);
+
+    // Sessions started before the session registry existed have no entry to update
+    match state
+        .session_store
+        .write()
+        .await
+        .touch_session(&record.family_id, Utc::now().timestamp_millis())
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let auth_cookie = match generate_auth_cookie(
+        &record.email,
+        &record.family_id,
+        &record.authentication,
+        state.banned_token_store.clone(),
+    )
+    .await
+    {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &record.email,
+        record.family_id,
+        record.authentication,
+        state.banned_token_store.clone(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK))
+}
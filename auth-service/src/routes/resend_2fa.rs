@@ -0,0 +1,95 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode, TwoFACodeStoreError, TwoFAMethod},
+};
+
+// Minimum time between two codes sent for the same login attempt
+pub const TWO_FA_RESEND_COOLDOWN_SECONDS: u64 = 30;
+// Codes sent per login attempt after the first one
+pub const MAX_TWO_FA_RESENDS: u32 = 3;
+
+// Sends a new code for a pending login attempt, in case the emailed one got lost. The password
+// was checked when the attempt started, so knowing the attempt id is enough.
+#[tracing::instrument(name = "Resend 2FA code", skip_all)]
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.two_fa_code_store.read().await.get_code(&login_attempt_id).await {
+        Ok((stored_email, _)) if stored_email == email => {}
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Only emailed codes can get lost, the others are never sent
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let cooldown = state
+        .cooldown_store
+        .write()
+        .await
+        .start_cooldown(
+            &format!("resend_2fa:{}", login_attempt_id.as_ref()),
+            TWO_FA_RESEND_COOLDOWN_SECONDS,
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if let Some(retry_after_seconds) = cooldown {
+        return Err(AuthAPIError::TooManyRequests { retry_after_seconds });
+    }
+
+    // A new code rather than the old one, in case the lost email turns up somewhere
+    let two_fa_code = TwoFACode::default();
+
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .replace_code(&login_attempt_id, two_fa_code.clone(), MAX_TWO_FA_RESENDS)
+        .await
+    {
+        Ok(()) => {}
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(TwoFACodeStoreError::TooManyResends) => return Err(AuthAPIError::TooManyTwoFAResends),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(&email, "2FA Code", two_fa_code.as_ref())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resend2FARequest {
+    email: String,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+}
@@ -0,0 +1,89 @@
+use axum::{extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse, Form};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        ApiKey, ApiKeyStoreError, OAuthError, RefreshToken, RefreshTokenStoreError,
+        API_KEY_PREFIX,
+    },
+    utils::{ban_token, validate_token},
+};
+
+use super::authenticate_service_account;
+
+#[tracing::instrument(name = "Revoke", skip_all)]
+// Revokes an auth token, refresh token or personal API key, see RFC 7009. Any service account
+// may revoke any token it holds, e.g. one it was sent by a user. Unknown or already invalid
+// tokens are not an error, the caller only wants the token to stop working.
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<RevocationRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate_service_account(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    // Every kind of token is told apart by its format, so `token_type_hint` is not needed
+    let token = request.token;
+
+    if token.starts_with(API_KEY_PREFIX) {
+        revoke_api_key(&state, token).await?;
+    } else if let Ok(refresh_token) = RefreshToken::parse(token.clone()) {
+        revoke_refresh_token(&state, &refresh_token).await?;
+    } else if let Ok(claims) = validate_token(&token, state.banned_token_store.clone()).await {
+        ban_token(&claims, &state.banned_token_store)
+            .await
+            .map_err(OAuthError::UnexpectedError)?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+// Revoking a refresh token ends the whole session, like logging out does
+async fn revoke_refresh_token(state: &AppState, token: &RefreshToken) -> Result<(), OAuthError> {
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.get_token(token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Ok(()),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    refresh_token_store
+        .revoke_family(&record.family_id)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))
+}
+
+async fn revoke_api_key(state: &AppState, token: String) -> Result<(), OAuthError> {
+    let Ok(key) = ApiKey::parse(token) else {
+        return Ok(());
+    };
+
+    let mut api_key_store = state.api_key_store.write().await;
+
+    let record = match api_key_store.get_key_by_hash(&key.hash()).await {
+        Ok(record) => record,
+        Err(ApiKeyStoreError::KeyNotFound) => return Ok(()),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    match api_key_store.revoke_key(&record.email, &record.id).await {
+        Ok(()) | Err(ApiKeyStoreError::KeyNotFound) => Ok(()),
+        Err(e) => Err(OAuthError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RevocationRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
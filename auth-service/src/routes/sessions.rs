@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::{AppState, SessionLimitPolicy},
+    domain::{
+        AuthAPIError, Authentication, ClientInfo, Email, RefreshTokenFamilyId, Session,
+        SessionStoreError,
+    },
+    utils::{
+        auth::{auth_token_expiry, ban_expiry},
+        generate_auth_cookie, generate_refresh_cookie, get_authenticated_claims,
+    },
+};
+
+#[tracing::instrument(name = "List sessions", skip_all)]
+// Where the user is logged in, oldest session first
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state.banned_token_store).await?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .list_sessions(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(ListSessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, claims.sid.as_deref()))
+            .collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Revoke session", skip_all)]
+// Logs the user out on one device. Its auth token stops working right away.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state.banned_token_store).await?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let id = RefreshTokenFamilyId::parse(id).map_err(|_| AuthAPIError::SessionNotFound)?;
+
+    match state.session_store.write().await.remove_session(&email, &id).await {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => return Err(AuthAPIError::SessionNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    end_session(&state, &id).await.map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Register a new session for a user that just logged in, and issue the cookies of its first
+// tokens. Every successful login starts a new session, with a new refresh token family.
+// With a session limit configured, a login beyond it is rejected or ends the user's oldest
+// sessions, depending on the limit's policy.
+pub(crate) async fn start_session(
+    state: &AppState,
+    email: &Email,
+    authentication: Authentication,
+    client: ClientInfo,
+) -> Result<(Cookie<'static>, Cookie<'static>), AuthAPIError> {
+    let session = Session::new(RefreshTokenFamilyId::default(), email.clone(), client);
+
+    // Hold the write lock until the session is added, so concurrent logins cannot all slip
+    // in under the limit
+    let mut session_store = state.session_store.write().await;
+
+    if let Some(limit) = state.session_limit {
+        let sessions = session_store
+            .list_sessions(email)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        let excess = (sessions.len() + 1).saturating_sub(limit.max_sessions);
+
+        if excess > 0 {
+            if limit.policy == SessionLimitPolicy::Reject {
+                return Err(AuthAPIError::TooManySessions);
+            }
+
+            // Sessions are listed oldest first
+            for oldest in sessions.iter().take(excess) {
+                match session_store.remove_session(email, &oldest.id).await {
+                    Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+                    Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+                }
+
+                end_session(state, &oldest.id)
+                    .await
+                    .map_err(AuthAPIError::UnexpectedError)?;
+            }
+        }
+    }
+
+    session_store
+        .add_session(session.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(session_store);
+
+    let auth_cookie = generate_auth_cookie(
+        email,
+        &session.id,
+        &authentication,
+        state.banned_token_store.clone(),
+    )
+    .await
+    .map_err(AuthAPIError::UnexpectedError)?;
+
+    let refresh_cookie = generate_refresh_cookie(
+        email,
+        session.id,
+        authentication,
+        state.banned_token_store.clone(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok((auth_cookie, refresh_cookie))
+}
+
+// Make the tokens of a session stop working: its refresh tokens are revoked and its auth tokens
+// banned until the last one issued has expired, leeway included. Removing it from the session
+// store is up to the caller.
+pub(crate) async fn end_session(state: &AppState, id: &RefreshTokenFamilyId) -> Result<()> {
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(id)
+        .await?;
+
+    // No auth token of the session expires later than one issued right now
+    let last_token_expiry = auth_token_expiry()? as i64;
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_session(id.as_ref().to_owned(), ban_expiry(last_token_expiry))
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListSessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    // Whether this is the session the request was made from
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: Option<&str>) -> Self {
+        Self {
+            current: current_session_id == Some(session.id.as_ref()),
+            id: session.id.as_ref().to_owned(),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use color_eyre::eyre::eyre;
 use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::User};
+use crate::{app_state::AppState, domain::{TwoFAMethod, User}};
+
+use super::verify_email::{send_verification_email, start_resend_cooldown};
 
 use crate::domain::{AuthAPIError, Email, Password};
 
@@ -23,8 +24,12 @@ pub async fn signup(
     let user = User {
         email,
         password,
-        requires_2fa: request.requires_2fa
+        requires_2fa: request.requires_2fa,
+        verified: false,
+        two_fa_method: TwoFAMethod::Email,
+        magic_link_enabled: request.magic_link_enabled,
     };
+    let email = user.email.clone();
 
     let mut user_store = state.user_store.write().await;
 
@@ -38,6 +43,16 @@ pub async fn signup(
         return Err(AuthAPIError::UnexpectedError(e.into()));
     }
 
+    drop(user_store);
+
+    // The signup email counts towards the resend cooldown. A failed send is not fatal,
+    // the user can ask for another email through /verify-email/resend.
+    start_resend_cooldown(&state, &email).await?;
+
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!("Failed to send verification email: {:?}", e);
+    }
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
     });
@@ -56,4 +71,7 @@ pub struct SignupRequest {
     pub password: String,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    // Opt in to logging in through /login/magic-link
+    #[serde(rename = "magicLinkEnabled", default)]
+    pub magic_link_enabled: bool,
 }
\ No newline at end of file
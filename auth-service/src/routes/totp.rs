@@ -0,0 +1,122 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TotpCode, TotpSecret, TotpStoreError, TwoFAMethod},
+    utils::{
+        decrypt_secret, encrypt_secret, get_authenticated_email, qr_code_png, qr_code_svg,
+        TOTP_ISSUER,
+    },
+};
+
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let secret = TotpSecret::default();
+    let encrypted_secret =
+        encrypt_secret(secret.as_bytes()).map_err(AuthAPIError::UnexpectedError)?;
+
+    match state
+        .totp_store
+        .write()
+        .await
+        .add_pending_secret(&email, encrypted_secret)
+        .await
+    {
+        Ok(()) => {}
+        Err(TotpStoreError::AlreadyConfirmed) => return Err(AuthAPIError::TotpAlreadyEnabled),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let otpauth_uri = secret.otpauth_uri(TOTP_ISSUER, &email);
+    let qr_code_png = qr_code_png(&otpauth_uri).map_err(AuthAPIError::UnexpectedError)?;
+    let qr_code_svg = qr_code_svg(&otpauth_uri).map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(EnrollTotpResponse {
+        secret: secret.to_base32(),
+        otpauth_uri,
+        qr_code_png: STANDARD.encode(qr_code_png),
+        qr_code_svg,
+    }))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+// TOTP only replaces email codes once the user proved their authenticator app produces valid codes
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let code = TotpCode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut totp_store = state.totp_store.write().await;
+
+    let record = match totp_store.get_secret(&email).await {
+        Ok(record) => record,
+        Err(TotpStoreError::SecretNotFound) => return Err(AuthAPIError::InvalidCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if record.confirmed {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    let secret = decrypt_secret(&record.encrypted_secret)
+        .and_then(TotpSecret::from_bytes)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let step = secret
+        .verify(&code, Utc::now().timestamp())
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    // The confirmation code can't be used to log in afterwards
+    if let Err(e) = totp_store.use_step(&email, step).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    if let Err(e) = totp_store.confirm_secret(&email).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    drop(totp_store);
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .enable_2fa(&email, TwoFAMethod::Totp)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    // Base32 encoded, for users who can't scan the QR code
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    // Base64 encoded PNG image
+    #[serde(rename = "qrCodePng")]
+    pub qr_code_png: String,
+    #[serde(rename = "qrCodeSvg")]
+    pub qr_code_svg: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
@@ -2,13 +2,17 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 use serde::Deserialize;
 
-use color_eyre::eyre::Error;
+use chrono::Utc;
+use color_eyre::eyre::Result;
 
-use crate::{app_state::AppState, domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode}, utils::generate_auth_cookie};
+use crate::{app_state::AppState, domain::{AuthAPIError, AuthMethod, Authentication, ClientInfo, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError, TotpCode, TotpSecret, TotpStoreError, TwoFACode, TwoFACodeStoreError, TwoFAMethod}, utils::decrypt_secret};
+
+use super::start_session;
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -22,42 +26,150 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     }; // Validate the login attempt ID in `request`
 
-    let two_fa_code = match TwoFACode::parse(request.two_fa_code) {
-        Ok(two_fa_code) => two_fa_code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
-    };
+    // A recovery code can be used in place of the emailed or TOTP code
+    let recovery_code = RecoveryCode::parse(request.two_fa_code.clone()).ok();
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    // Email and TOTP codes are both 6 digits, which code is expected depends on the user
+    if recovery_code.is_none() && TotpCode::parse(request.two_fa_code.clone()).is_err() {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    }
+
+    let method = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user.two_fa_method,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
 
     // Call `two_fa_code_store.get_code`. If the call fails
     // return a `AuthAPIError::IncorrectCredentials`.
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
-        Ok((login_attempt_id, two_fa_code)) => (login_attempt_id, two_fa_code),
+    let code_tuple = match state.two_fa_code_store.read().await.get_code(&login_attempt_id).await {
+        Ok((email, two_fa_code)) => (email, two_fa_code),
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    // TODO: Validate that the `login_attempt_id` and `two_fa_code`
-    // in the request body matches values in the `code_tuple`. 
-    // If not, return a `AuthAPIError::IncorrectCredentials`.
-    if code_tuple != (login_attempt_id, two_fa_code) {
+    // The login attempt has to be one of the user's own
+    if code_tuple.0 != email {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    let is_recovery_code = recovery_code.is_some();
+
+    // Checking recovery codes takes several password hashes, the 2FA code store isn't locked
+    // meanwhile
+    let used_recovery_code = match recovery_code {
+        Some(recovery_code) => match state
+            .recovery_code_store
+            .write()
+            .await
+            .use_code(&email, &recovery_code)
+            .await
+        {
+            Ok(()) => Some(recovery_code),
+            Err(RecoveryCodeStoreError::CodeNotFound) => None,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        },
+        None => None,
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    // A concurrent request with the same code may have finished the login attempt meanwhile,
+    // the recovery code used for nothing is put back
+    let Ok((_, two_fa_code)) = two_fa_code_store.get_code(&login_attempt_id).await else {
+        drop(two_fa_code_store);
+
+        if let Some(recovery_code) = used_recovery_code {
+            if let Err(e) = state
+                .recovery_code_store
+                .write()
+                .await
+                .restore_code(&email, recovery_code)
+                .await
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+        }
+
         return (jar, Err(AuthAPIError::IncorrectCredentials));
+    };
+
+    // Used TOTP steps can't be put back, so TOTP codes are only checked for a pending attempt
+    let code_valid = match (is_recovery_code, method) {
+        (true, _) => used_recovery_code.is_some(),
+        (false, TwoFAMethod::Email) => TwoFACode::parse(request.two_fa_code)
+            .is_ok_and(|code| two_fa_code.verify(&code)),
+        (false, TwoFAMethod::Totp) => match verify_totp_code(&state, &email, request.two_fa_code).await {
+            Ok(valid) => valid,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+        },
+        // Passkey logins are finished at /webauthn/login/finish, only recovery codes work here
+        (false, TwoFAMethod::Webauthn) => false,
+    };
+
+    // Wrong codes only count against this login attempt, the user's other pending ones (e.g.
+    // in another browser) are unaffected
+    if !code_valid {
+        let max_attempts = state.max_two_fa_attempts;
+
+        return match two_fa_code_store
+            .record_failed_attempt(&login_attempt_id, max_attempts)
+            .await
+        {
+            Ok(()) => (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(TwoFACodeStoreError::TooManyAttempts) => {
+                (jar, Err(AuthAPIError::TooManyTwoFAAttempts))
+            }
+            Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
     }
 
-    if let Err(e) = two_fa_code_store.remove_code(&email).await {
+    if let Err(e) = two_fa_code_store.remove_code(&login_attempt_id).await {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    drop(two_fa_code_store);
+
+    // The first factor may have been a password or a magic link, only the second one is known
+    let authentication = Authentication::now(&[AuthMethod::OneTimePassword, AuthMethod::MultiFactor]);
+
+    let (cookie, refresh_cookie) = match start_session(&state, &email, authentication, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
 
-    let updated_jar = jar.add(cookie);
+    let updated_jar = jar.add(cookie).add(refresh_cookie);
 
      // Validate the 2FA code in `request`
     (updated_jar, Ok(StatusCode::OK.into_response()))
 }
 
+#[tracing::instrument(name = "Verify TOTP code", skip_all)]
+// Check the code against the user's confirmed TOTP secret. A valid code is used up right away.
+async fn verify_totp_code(state: &AppState, email: &Email, code: String) -> Result<bool> {
+    let Ok(code) = TotpCode::parse(code) else {
+        return Ok(false);
+    };
+
+    let mut totp_store = state.totp_store.write().await;
+
+    let record = match totp_store.get_secret(email).await {
+        Ok(record) if record.confirmed => record,
+        Ok(_) | Err(TotpStoreError::SecretNotFound) => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    let secret = TotpSecret::from_bytes(decrypt_secret(&record.encrypted_secret)?)?;
+
+    let Some(step) = secret.verify(&code, Utc::now().timestamp()) else {
+        return Ok(false);
+    };
+
+    match totp_store.use_step(email, step).await {
+        Ok(()) => Ok(true),
+        Err(TotpStoreError::CodeAlreadyUsed) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Verify2FARequest {
     email: String,
@@ -0,0 +1,106 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError},
+    utils::{
+        auth::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, generate_email_verification_token,
+        validate_email_verification_token, AUTH_SERVICE_URL,
+    },
+};
+
+// Minimum time between two verification emails to the same address
+pub const EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS: u64 = 60;
+
+#[tracing::instrument(name = "Verify email", skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email =
+        validate_email_verification_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state.user_store.write().await.mark_verified(&email).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[tracing::instrument(name = "Resend verification email", skip_all)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // The cooldown applies whether or not an account exists, so the response can't be used
+    // to find out which emails are registered
+    if let Some(retry_after_seconds) = start_resend_cooldown(&state, &email).await? {
+        return Err(AuthAPIError::TooManyRequests { retry_after_seconds });
+    }
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => Some(user),
+        Err(UserStoreError::UserNotFound) => None,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if let Some(user) = user.filter(|user| !user.verified) {
+        send_verification_email(&state, &user.email)
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+// Returns the seconds left if a verification email was sent to `email` too recently
+pub(crate) async fn start_resend_cooldown(
+    state: &AppState,
+    email: &Email,
+) -> Result<Option<u64>, AuthAPIError> {
+    state
+        .cooldown_store
+        .write()
+        .await
+        .start_cooldown(
+            &format!("email_verification:{}", email.as_ref()),
+            EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS,
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+#[tracing::instrument(name = "Send verification email", skip_all)]
+pub(crate) async fn send_verification_email(state: &AppState, email: &Email) -> Result<()> {
+    let token = generate_email_verification_token(email)
+        .wrap_err("failed to create email verification token")?;
+
+    let content = format!(
+        "Use the following link to verify your email address: {}/?email_verification_token={}\n\
+         The link expires in {} hours. If you did not create an account, you can ignore this email.",
+        *AUTH_SERVICE_URL,
+        token,
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 60 / 60
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Verify your email address", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
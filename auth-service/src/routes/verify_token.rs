@@ -1,20 +1,111 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use serde::Deserialize;
+use axum::{
+    extract::{rejection::JsonRejection, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{ApiKeyRecord, AuthAPIError, PrincipalType, API_KEY_PREFIX},
+    utils::{validate_access_token, validate_api_key, Claims},
+};
+
+use super::IntrospectionResponse;
 
 #[tracing::instrument(name = "Verify token", skip_all)]
+// Tells other services whether a token is valid and who it belongs to, a user or a service account.
+// The token is taken from `Authorization: Bearer` if present, so scripts can send API keys the
+// way they would call any other API. With `?introspect=true` valid tokens are described like
+// `/introspect` does, see `IntrospectionResponse`.
 pub async fn verify_token(
         State(state): State<AppState>,
-        Json(auth_token): Json<TokenToBeVerified>
-    ) -> Result<impl IntoResponse, AuthAPIError> {
-    match validate_token(&auth_token.token, state.banned_token_store.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
-        Err(_) => return Err(AuthAPIError::InvalidToken),
+        Query(params): Query<VerifyTokenParams>,
+        headers: HeaderMap,
+        auth_token: Result<Json<TokenToBeVerified>, JsonRejection>
+    ) -> Response {
+    let bearer_token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let token = match (bearer_token, auth_token) {
+        (Some(token), _) => token.to_owned(),
+        (None, Ok(Json(auth_token))) => auth_token.token,
+        (None, Err(rejection)) => return rejection.into_response(),
+    };
+
+    let verified = match verify_any_token(&state, &token).await {
+        Some(verified) => verified,
+        None => return AuthAPIError::InvalidToken.into_response(),
+    };
+
+    match params.introspect {
+        true => Json(IntrospectionResponse::from(&verified)).into_response(),
+        false => Json(VerifyTokenResponse::from(verified)).into_response(),
+    }
+}
+
+// A token that passed validation, either a JWT issued by us or a personal API key
+pub(crate) enum VerifiedToken {
+    Jwt(Claims),
+    ApiKey(ApiKeyRecord),
+}
+
+// Shared by `verify_token` and `introspect`. Callers only learn whether the token is valid, not
+// why it isn't.
+pub(crate) async fn verify_any_token(state: &AppState, token: &str) -> Option<VerifiedToken> {
+    if token.starts_with(API_KEY_PREFIX) {
+        return validate_api_key(token, &state.api_key_store)
+            .await
+            .ok()
+            .map(VerifiedToken::ApiKey);
     }
+
+    validate_access_token(
+        token,
+        state.banned_token_store.clone(),
+        &state.service_account_store,
+    )
+    .await
+    .ok()
+    .map(VerifiedToken::Jwt)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyTokenParams {
+    #[serde(default)]
+    pub introspect: bool,
 }
 
 #[derive(Deserialize)]
 pub struct TokenToBeVerified {
     pub token: String
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyTokenResponse {
+    pub principal: PrincipalType,
+    // The user's email, or the service account's client id
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl From<VerifiedToken> for VerifyTokenResponse {
+    fn from(verified: VerifiedToken) -> Self {
+        match verified {
+            VerifiedToken::Jwt(claims) => Self {
+                principal: claims.principal,
+                sub: claims.sub,
+                scope: claims.scope,
+            },
+            VerifiedToken::ApiKey(record) => Self {
+                principal: PrincipalType::User,
+                sub: record.email.as_ref().to_owned(),
+                scope: Some(record.scope.to_string()).filter(|scope| !scope.is_empty()),
+            },
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::{AppState, UnverifiedLoginPolicy},
+    domain::{
+        verify_assertion, verify_registration, webauthn_user_handle, AuthAPIError, AuthMethod,
+        Authentication, ClientData, ClientInfo,
+        CredentialPublicKey, Email, LoginAttemptId, RelyingParty,
+        TwoFAMethod, WebauthnCeremony, WebauthnChallenge, WebauthnChallengeStoreError,
+        WebauthnCredential, WebauthnCredentialStoreError, COSE_ALGORITHM_ES256,
+        WEBAUTHN_CHALLENGE_TTL_SECONDS,
+    },
+    routes::{start_session, RegularAuthResponse, UNVERIFIED_EMAIL_WARNING},
+    utils::{
+        get_authenticated_email, WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME,
+    },
+};
+
+#[tracing::instrument(name = "Start WebAuthn registration", skip_all)]
+// Returns the options for navigator.credentials.create(), to add a passkey to the logged in user
+pub async fn start_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    // Stops the browser from registering a second credential on the same authenticator
+    let exclude_credentials = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credentials(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .iter()
+        .map(CredentialDescriptor::from)
+        .collect();
+
+    let challenge = WebauthnChallenge::default();
+
+    state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .add_challenge(
+            challenge.clone(),
+            WebauthnCeremony::Registration {
+                email: email.clone(),
+            },
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(RegistrationOptionsResponse {
+        public_key: CreationOptions {
+            challenge: challenge.as_ref().to_owned(),
+            rp: RelyingPartyEntity {
+                id: WEBAUTHN_RP_ID.clone(),
+                name: WEBAUTHN_RP_NAME.to_owned(),
+            },
+            user: UserEntity {
+                id: webauthn_user_handle(&email),
+                name: email.as_ref().to_owned(),
+                display_name: email.as_ref().to_owned(),
+            },
+            pub_key_cred_params: vec![CredentialParameters {
+                credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+                alg: COSE_ALGORITHM_ES256,
+            }],
+            timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+            attestation: "direct".to_owned(),
+            exclude_credentials,
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "preferred".to_owned(),
+                user_verification: "preferred".to_owned(),
+            },
+        },
+    }))
+}
+
+#[tracing::instrument(name = "Finish WebAuthn registration", skip_all)]
+pub async fn finish_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<FinishWebauthnRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state.banned_token_store).await?;
+
+    let raw_id = decode(&request.credential.raw_id)?;
+    let client_data_json = decode(&request.credential.response.client_data_json)?;
+    let attestation_object = decode(&request.credential.response.attestation_object)?;
+
+    let client_data = ClientData::parse(&client_data_json)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match take_ceremony(&state, &client_data).await? {
+        WebauthnCeremony::Registration { email: expected } if expected == email => {}
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    let credential = verify_registration(&relying_party(), &client_data, &attestation_object)
+        .map_err(|e| {
+            tracing::info!("Rejected WebAuthn registration: {}", e);
+            AuthAPIError::IncorrectCredentials
+        })?;
+
+    if credential.credential_id != raw_id {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    match state
+        .webauthn_credential_store
+        .write()
+        .await
+        .add_credential(WebauthnCredential {
+            credential_id: credential.credential_id,
+            email: email.clone(),
+            public_key: credential.public_key.to_sec1_bytes(),
+            sign_count: credential.sign_count,
+            attestation_format: credential.attestation_format,
+        })
+        .await
+    {
+        Ok(()) => {}
+        Err(WebauthnCredentialStoreError::CredentialAlreadyExists) => {
+            return Err(AuthAPIError::PasskeyAlreadyRegistered)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    if request.enable_2fa {
+        state
+            .user_store
+            .write()
+            .await
+            .enable_2fa(&email, TwoFAMethod::Webauthn)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Start WebAuthn login", skip_all)]
+// Returns the options for navigator.credentials.get(). Without an email the browser offers
+// every passkey it has for this site. With a login attempt ID the passkey is the second
+// factor of a password login.
+pub async fn start_webauthn_login(
+    State(state): State<AppState>,
+    Json(request): Json<StartWebauthnLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = request
+        .email
+        .map(Email::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_attempt_id = request
+        .login_attempt_id
+        .map(LoginAttemptId::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if let Some(login_attempt_id) = &login_attempt_id {
+        let Some(email) = &email else {
+            return Err(AuthAPIError::InvalidCredentials);
+        };
+
+        match state.two_fa_code_store.read().await.get_code(login_attempt_id).await {
+            Ok((stored_email, _)) if &stored_email == email => {}
+            _ => return Err(AuthAPIError::IncorrectCredentials),
+        }
+    }
+
+    let allow_credentials = match &email {
+        Some(email) => state
+            .webauthn_credential_store
+            .read()
+            .await
+            .get_credentials(email)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+            .iter()
+            .map(CredentialDescriptor::from)
+            .collect(),
+        None => vec![],
+    };
+
+    // A passkey on its own has to prove who is holding it. As a second factor the password
+    // already did that, the passkey only has to be present.
+    let user_verification = match login_attempt_id {
+        Some(_) => "discouraged",
+        None => "required",
+    }
+    .to_owned();
+
+    let challenge = WebauthnChallenge::default();
+
+    state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .add_challenge(
+            challenge.clone(),
+            WebauthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            },
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(LoginOptionsResponse {
+        public_key: RequestOptions {
+            challenge: challenge.as_ref().to_owned(),
+            rp_id: WEBAUTHN_RP_ID.clone(),
+            timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+            allow_credentials,
+            user_verification,
+        },
+    }))
+}
+
+#[tracing::instrument(name = "Finish WebAuthn login", skip_all)]
+pub async fn finish_webauthn_login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<FinishWebauthnLoginRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, warning, authentication) = match verify_login(&state, request).await {
+        Ok(result) => result,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &email, authentication, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (
+        updated_jar,
+        Ok((StatusCode::OK, Json(RegularAuthResponse { warning }))),
+    )
+}
+
+// Checks the assertion and returns who logged in, the unverified email warning if any, and
+// how the user authenticated
+async fn verify_login(
+    state: &AppState,
+    request: FinishWebauthnLoginRequest,
+) -> Result<(Email, Option<String>, Authentication), AuthAPIError> {
+    let raw_id = decode(&request.credential.raw_id)?;
+    let client_data_json = decode(&request.credential.response.client_data_json)?;
+    let authenticator_data = decode(&request.credential.response.authenticator_data)?;
+    let signature = decode(&request.credential.response.signature)?;
+
+    let client_data = ClientData::parse(&client_data_json)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let WebauthnCeremony::Authentication {
+        email: expected_email,
+        login_attempt_id,
+    } = take_ceremony(state, &client_data).await?
+    else {
+        return Err(AuthAPIError::IncorrectCredentials);
+    };
+
+    let credential = match state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&raw_id)
+        .await
+    {
+        Ok(credential) => credential,
+        Err(WebauthnCredentialStoreError::CredentialNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if expected_email.is_some_and(|email| email != credential.email) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let public_key = CredentialPublicKey::from_sec1_bytes(&credential.public_key)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let sign_count = verify_assertion(
+        &relying_party(),
+        &client_data,
+        &authenticator_data,
+        &signature,
+        &public_key,
+        login_attempt_id.is_none(),
+    )
+    .map_err(|e| {
+        tracing::info!("Rejected WebAuthn assertion: {}", e);
+        AuthAPIError::IncorrectCredentials
+    })?;
+
+    match state
+        .webauthn_credential_store
+        .write()
+        .await
+        .update_sign_count(&raw_id, sign_count)
+        .await
+    {
+        Ok(()) => {}
+        Err(WebauthnCredentialStoreError::SignCountNotIncreased) => {
+            tracing::warn!("WebAuthn signature counter went backwards, the authenticator may be cloned");
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let email = credential.email;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    // Either way the user proved two factors, see `start_webauthn_login`
+    let authentication = Authentication::now(&[AuthMethod::HardwareKey, AuthMethod::MultiFactor]);
+
+    match login_attempt_id {
+        // Second factor, the login route already applied the unverified login policy
+        Some(login_attempt_id) => {
+            let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+            match two_fa_code_store.get_code(&login_attempt_id).await {
+                Ok((stored_email, _)) if stored_email == email => {}
+                _ => return Err(AuthAPIError::IncorrectCredentials),
+            }
+
+            two_fa_code_store
+                .remove_code(&login_attempt_id)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            Ok((email, None, authentication))
+        }
+        // A verified passkey counts as both factors, so no 2FA is asked for
+        None => match (user.verified, state.unverified_login_policy) {
+            (true, _) => Ok((email, None, authentication)),
+            (false, UnverifiedLoginPolicy::Block) => Err(AuthAPIError::EmailNotVerified),
+            (false, UnverifiedLoginPolicy::Warn) => {
+                Ok((email, Some(UNVERIFIED_EMAIL_WARNING.to_owned()), authentication))
+            }
+        },
+    }
+}
+
+// Challenges are looked up by value, which also makes sure they were issued by us and are
+// answered only once
+async fn take_ceremony(
+    state: &AppState,
+    client_data: &ClientData,
+) -> Result<WebauthnCeremony, AuthAPIError> {
+    let challenge = client_data
+        .challenge()
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    match state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .take_challenge(&challenge)
+        .await
+    {
+        Ok(ceremony) => Ok(ceremony),
+        Err(WebauthnChallengeStoreError::ChallengeNotFound) => {
+            Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+fn relying_party() -> RelyingParty {
+    RelyingParty {
+        id: WEBAUTHN_RP_ID.clone(),
+        origin: WEBAUTHN_ORIGIN.clone(),
+    }
+}
+
+// Binary values are base64url encoded, some clients pad them
+fn decode(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+// The response bodies mirror the WebAuthn PublicKeyCredentialCreationOptions and
+// PublicKeyCredentialRequestOptions, with binary values base64url encoded
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistrationOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: CreationOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: i64,
+    pub attestation: String,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+impl From<&WebauthnCredential> for CredentialDescriptor {
+    fn from(credential: &WebauthnCredential) -> Self {
+        Self {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: URL_SAFE_NO_PAD.encode(&credential.credential_id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: RequestOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: i64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinishWebauthnRegistrationRequest {
+    credential: RegistrationCredential,
+    // Use passkeys as the second factor of password logins from now on
+    #[serde(rename = "enable2FA", default)]
+    enable_2fa: bool,
+}
+
+#[derive(Deserialize)]
+struct RegistrationCredential {
+    #[serde(rename = "rawId")]
+    raw_id: String,
+    response: AttestationResponse,
+}
+
+#[derive(Deserialize)]
+struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    attestation_object: String,
+}
+
+#[derive(Deserialize)]
+pub struct StartWebauthnLoginRequest {
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(rename = "loginAttemptId", default)]
+    login_attempt_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FinishWebauthnLoginRequest {
+    credential: AssertionCredential,
+}
+
+#[derive(Deserialize)]
+struct AssertionCredential {
+    #[serde(rename = "rawId")]
+    raw_id: String,
+    response: AssertionResponse,
+}
+
+#[derive(Deserialize)]
+struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    authenticator_data: String,
+    signature: String,
+}
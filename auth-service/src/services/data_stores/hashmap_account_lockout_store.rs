@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{AccountLockId, AccountLockoutStore, AccountLockoutStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapAccountLockoutStore {
+    // Keyed by email, values are the number of failed logins and the unix timestamp their
+    // window ends at
+    failed_logins: HashMap<Email, (u32, i64)>,
+    // Keyed by email, values are the current lock and the unix timestamp it ends at
+    locks: HashMap<Email, (AccountLockId, i64)>,
+}
+
+#[async_trait::async_trait]
+impl AccountLockoutStore for HashmapAccountLockoutStore {
+    async fn record_failed_login(
+        &mut self,
+        email: &Email,
+        window_seconds: u64,
+    ) -> Result<u32, AccountLockoutStoreError> {
+        let now = Utc::now().timestamp();
+
+        let entry = self
+            .failed_logins
+            .entry(email.clone())
+            .or_insert((0, now + window_seconds as i64));
+
+        if entry.1 <= now {
+            *entry = (0, now + window_seconds as i64);
+        }
+
+        entry.0 += 1;
+        Ok(entry.0)
+    }
+
+    async fn clear_failed_logins(&mut self, email: &Email) -> Result<(), AccountLockoutStoreError> {
+        self.failed_logins.remove(email);
+        Ok(())
+    }
+
+    async fn lock_account(
+        &mut self,
+        email: &Email,
+        lock_id: AccountLockId,
+        seconds: u64,
+    ) -> Result<(), AccountLockoutStoreError> {
+        let locked_until = Utc::now().timestamp() + seconds as i64;
+        self.locks.insert(email.clone(), (lock_id, locked_until));
+        Ok(())
+    }
+
+    async fn get_lock(&self, email: &Email) -> Result<Option<u64>, AccountLockoutStoreError> {
+        let now = Utc::now().timestamp();
+
+        Ok(self
+            .locks
+            .get(email)
+            .filter(|(_, locked_until)| *locked_until > now)
+            .map(|(_, locked_until)| (locked_until - now) as u64))
+    }
+
+    async fn unlock_account(
+        &mut self,
+        email: &Email,
+        lock_id: &AccountLockId,
+    ) -> Result<(), AccountLockoutStoreError> {
+        match self.locks.get(email) {
+            Some((stored_id, locked_until))
+                if stored_id == lock_id && Utc::now().timestamp() < *locked_until =>
+            {
+                self.locks.remove(email);
+                self.failed_logins.remove(email);
+                Ok(())
+            }
+            _ => Err(AccountLockoutStoreError::LockNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("test@example.com".to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_login() {
+        let mut store = HashmapAccountLockoutStore::default();
+
+        assert_eq!(store.record_failed_login(&email(), 60).await.unwrap(), 1);
+        assert_eq!(store.record_failed_login(&email(), 60).await.unwrap(), 2);
+
+        // The count starts over once its window ended
+        store.failed_logins.get_mut(&email()).unwrap().1 = Utc::now().timestamp() - 1;
+        assert_eq!(store.record_failed_login(&email(), 60).await.unwrap(), 1);
+
+        store.clear_failed_logins(&email()).await.unwrap();
+        assert_eq!(store.record_failed_login(&email(), 60).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lock_account_until_timeout() {
+        let mut store = HashmapAccountLockoutStore::default();
+
+        assert_eq!(store.get_lock(&email()).await.unwrap(), None);
+
+        store
+            .lock_account(&email(), AccountLockId::default(), 60)
+            .await
+            .unwrap();
+
+        let remaining = store.get_lock(&email()).await.unwrap().unwrap();
+        assert!(remaining > 0 && remaining <= 60);
+
+        store.locks.get_mut(&email()).unwrap().1 = Utc::now().timestamp() - 1;
+        assert_eq!(store.get_lock(&email()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_unlock_account() {
+        let mut store = HashmapAccountLockoutStore::default();
+        let lock_id = AccountLockId::default();
+
+        store.record_failed_login(&email(), 60).await.unwrap();
+        store
+            .lock_account(&email(), lock_id.clone(), 60)
+            .await
+            .unwrap();
+
+        // Only the current lock can be lifted
+        assert_eq!(
+            store.unlock_account(&email(), &AccountLockId::default()).await,
+            Err(AccountLockoutStoreError::LockNotFound)
+        );
+
+        store.unlock_account(&email(), &lock_id).await.unwrap();
+        assert_eq!(store.get_lock(&email()).await.unwrap(), None);
+        assert_eq!(store.record_failed_login(&email(), 60).await.unwrap(), 1);
+
+        assert_eq!(
+            store.unlock_account(&email(), &lock_id).await,
+            Err(AccountLockoutStoreError::LockNotFound)
+        );
+    }
+}
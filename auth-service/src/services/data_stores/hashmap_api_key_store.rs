@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{ApiKeyStore, ApiKeyStoreError},
+    ApiKeyId, ApiKeyRecord, Email,
+};
+
+#[derive(Default)]
+pub struct HashmapApiKeyStore {
+    // Keyed by API key ID
+    keys: HashMap<String, ApiKeyRecord>,
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for HashmapApiKeyStore {
+    async fn add_key(&mut self, record: ApiKeyRecord) -> Result<(), ApiKeyStoreError> {
+        self.keys.insert(record.id.as_ref().to_owned(), record);
+        Ok(())
+    }
+
+    async fn get_key_by_hash(&self, key_hash: &str) -> Result<ApiKeyRecord, ApiKeyStoreError> {
+        self.keys
+            .values()
+            .find(|record| record.key_hash == key_hash)
+            .cloned()
+            .ok_or(ApiKeyStoreError::KeyNotFound)
+    }
+
+    async fn list_keys(&self, email: &Email) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError> {
+        let mut keys: Vec<ApiKeyRecord> = self
+            .keys
+            .values()
+            .filter(|record| record.email == *email)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|record| record.created_at);
+        Ok(keys)
+    }
+
+    async fn revoke_key(&mut self, email: &Email, id: &ApiKeyId) -> Result<(), ApiKeyStoreError> {
+        match self.keys.get(id.as_ref()) {
+            Some(record) if record.email == *email => {
+                self.keys.remove(id.as_ref());
+                Ok(())
+            }
+            _ => Err(ApiKeyStoreError::KeyNotFound),
+        }
+    }
+
+    async fn record_use(&mut self, id: &ApiKeyId, used_at: i64) -> Result<(), ApiKeyStoreError> {
+        let record = self
+            .keys
+            .get_mut(id.as_ref())
+            .ok_or(ApiKeyStoreError::KeyNotFound)?;
+        record.last_used_at = Some(used_at);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ApiKey, Scope};
+
+    fn record(email: &Email) -> (ApiKey, ApiKeyRecord) {
+        let key = ApiKey::default();
+        let record = ApiKeyRecord::new(&key, email.clone(), "CI".to_owned(), Scope::default(), None);
+        (key, record)
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_key_by_hash() {
+        let mut store = HashmapApiKeyStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (key, record) = record(&email);
+
+        store.add_key(record.clone()).await.unwrap();
+
+        assert_eq!(store.get_key_by_hash(&key.hash()).await, Ok(record.clone()));
+        assert_eq!(
+            store.get_key_by_hash(&ApiKey::default().hash()).await,
+            Err(ApiKeyStoreError::KeyNotFound)
+        );
+        assert_eq!(store.list_keys(&email).await, Ok(vec![record]));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_key_of_user_only() {
+        let mut store = HashmapApiKeyStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+        let (key, record) = record(&email);
+        store.add_key(record.clone()).await.unwrap();
+
+        assert_eq!(
+            store.revoke_key(&other_email, &record.id).await,
+            Err(ApiKeyStoreError::KeyNotFound)
+        );
+        assert_eq!(store.revoke_key(&email, &record.id).await, Ok(()));
+        assert_eq!(
+            store.get_key_by_hash(&key.hash()).await,
+            Err(ApiKeyStoreError::KeyNotFound)
+        );
+        assert_eq!(store.list_keys(&email).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_record_use() {
+        let mut store = HashmapApiKeyStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (key, record) = record(&email);
+        store.add_key(record.clone()).await.unwrap();
+
+        store.record_use(&record.id, 1_700_000_000).await.unwrap();
+
+        let stored = store.get_key_by_hash(&key.hash()).await.unwrap();
+        assert_eq!(stored.last_used_at, Some(1_700_000_000));
+    }
+}
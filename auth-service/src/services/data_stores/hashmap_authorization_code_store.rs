@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{AuthorizationCodeStore, AuthorizationCodeStoreError},
+    AuthorizationCode, AuthorizationGrant, AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    // Keyed by the hash of the code, values are the grant and the expiry unix timestamp
+    codes: HashMap<String, (AuthorizationGrant, i64)>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let expires_at = Utc::now().timestamp() + AUTHORIZATION_CODE_TTL_SECONDS;
+        self.codes.insert(code.hash(), (grant, expires_at));
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        match self.codes.remove(&code.hash()) {
+            Some((grant, expires_at)) if Utc::now().timestamp() < expires_at => Ok(grant),
+            _ => Err(AuthorizationCodeStoreError::CodeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        AuthMethod, Authentication, CodeChallenge, Email, OAuthClientId, RedirectUri, Scope,
+    };
+
+    fn grant() -> AuthorizationGrant {
+        AuthorizationGrant {
+            client_id: OAuthClientId::default(),
+            redirect_uri: RedirectUri::parse("https://app.example.com/callback".to_owned())
+                .unwrap(),
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+            scope: Scope::parse("email").unwrap(),
+            code_challenge: CodeChallenge::from_verifier(
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            ),
+            nonce: None,
+            authentication: Authentication::now(&[AuthMethod::Password]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+        let grant = grant();
+
+        store.add_code(code.clone(), grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await, Ok(grant));
+
+        // Codes can only be exchanged once
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_take_expired_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+
+        store
+            .codes
+            .insert(code.hash(), (grant(), Utc::now().timestamp() - 1));
+
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::data_stores::{CooldownStore, CooldownStoreError};
+
+#[derive(Default)]
+pub struct HashmapCooldownStore {
+    // Unix timestamp at which the cooldown of each key ends
+    cooldowns: HashMap<String, i64>,
+}
+
+#[async_trait::async_trait]
+impl CooldownStore for HashmapCooldownStore {
+    async fn start_cooldown(
+        &mut self,
+        key: &str,
+        seconds: u64,
+    ) -> Result<Option<u64>, CooldownStoreError> {
+        let now = Utc::now().timestamp();
+
+        if let Some(&ends_at) = self.cooldowns.get(key) {
+            if ends_at > now {
+                return Ok(Some((ends_at - now) as u64));
+            }
+        }
+
+        self.cooldowns.insert(key.to_owned(), now + seconds as i64);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_cooldown() {
+        let mut store = HashmapCooldownStore::default();
+
+        assert_eq!(store.start_cooldown("key", 60).await.unwrap(), None);
+
+        let remaining = store.start_cooldown("key", 60).await.unwrap().unwrap();
+        assert!(remaining > 0 && remaining <= 60);
+
+        // Other keys are independent
+        assert_eq!(store.start_cooldown("other_key", 60).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_start_cooldown_after_previous_one_ended() {
+        let mut store = HashmapCooldownStore::default();
+
+        store
+            .cooldowns
+            .insert("key".to_owned(), Utc::now().timestamp() - 1);
+
+        assert_eq!(store.start_cooldown("key", 60).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{
+        data_stores::{MagicLinkId, MagicLinkStore, MagicLinkStoreError},
+        Email,
+    },
+    utils::auth::MAGIC_LINK_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    // Keyed by email, values are the latest link sent to the user and its expiry unix timestamp
+    links: HashMap<Email, (MagicLinkId, i64)>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_link(&mut self, email: Email, link_id: MagicLinkId) -> Result<(), MagicLinkStoreError> {
+        let expires_at = Utc::now().timestamp() + MAGIC_LINK_TTL_SECONDS;
+        self.links.insert(email, (link_id, expires_at));
+        Ok(())
+    }
+
+    async fn use_link(&mut self, email: &Email, link_id: &MagicLinkId) -> Result<(), MagicLinkStoreError> {
+        match self.links.get(email) {
+            Some((stored_id, expires_at))
+                if stored_id == link_id && Utc::now().timestamp() < *expires_at =>
+            {
+                self.links.remove(email);
+                Ok(())
+            }
+            _ => Err(MagicLinkStoreError::LinkNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_use_link() {
+        let mut store = HashmapMagicLinkStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let link_id = MagicLinkId::default();
+
+        store.add_link(email.clone(), link_id.clone()).await.unwrap();
+
+        assert_eq!(store.use_link(&email, &link_id).await, Ok(()));
+
+        // Links can only be used once
+        assert_eq!(
+            store.use_link(&email, &link_id).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_link_replaces_old_one() {
+        let mut store = HashmapMagicLinkStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let old_link_id = MagicLinkId::default();
+        let new_link_id = MagicLinkId::default();
+
+        store.add_link(email.clone(), old_link_id.clone()).await.unwrap();
+        store.add_link(email.clone(), new_link_id.clone()).await.unwrap();
+
+        assert_eq!(
+            store.use_link(&email, &old_link_id).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+        assert_eq!(store.use_link(&email, &new_link_id).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_use_expired_link() {
+        let mut store = HashmapMagicLinkStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let link_id = MagicLinkId::default();
+
+        store
+            .links
+            .insert(email.clone(), (link_id.clone(), Utc::now().timestamp() - 1));
+
+        assert_eq!(
+            store.use_link(&email, &link_id).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+    }
+}
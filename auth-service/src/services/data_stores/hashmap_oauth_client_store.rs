@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{OAuthClientStore, OAuthClientStoreError},
+    OAuthClient, OAuthClientId,
+};
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    // Keyed by client ID
+    clients: HashMap<String, OAuthClient>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        if self.clients.contains_key(client.client_id.as_ref()) {
+            return Err(OAuthClientStoreError::ClientAlreadyExists);
+        }
+
+        self.clients
+            .insert(client.client_id.as_ref().to_owned(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, client_id: &OAuthClientId) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients
+            .get(client_id.as_ref())
+            .cloned()
+            .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::RedirectUri;
+
+    #[tokio::test]
+    async fn test_add_and_get_client() {
+        let mut store = HashmapOAuthClientStore::default();
+        let client = OAuthClient {
+            client_id: OAuthClientId::default(),
+            name: "App".to_owned(),
+            redirect_uris: vec![
+                RedirectUri::parse("https://app.example.com/callback".to_owned()).unwrap()
+            ],
+        };
+
+        assert_eq!(store.add_client(client.clone()).await, Ok(()));
+        assert_eq!(
+            store.add_client(client.clone()).await,
+            Err(OAuthClientStoreError::ClientAlreadyExists)
+        );
+
+        assert_eq!(store.get_client(&client.client_id).await, Ok(client));
+        assert_eq!(
+            store.get_client(&OAuthClientId::default()).await,
+            Err(OAuthClientStoreError::ClientNotFound)
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{OAuthConsentStore, OAuthConsentStoreError},
+    Email, OAuthClientId, Scope,
+};
+
+#[derive(Default)]
+pub struct HashmapOAuthConsentStore {
+    // Keyed by user and client ID, values are the scopes granted so far
+    consents: HashMap<(Email, String), Scope>,
+}
+
+#[async_trait::async_trait]
+impl OAuthConsentStore for HashmapOAuthConsentStore {
+    async fn grant_consent(
+        &mut self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<(), OAuthConsentStoreError> {
+        let granted = self
+            .consents
+            .entry((email.clone(), client_id.as_ref().to_owned()))
+            .or_default();
+        *granted = granted.union(scope);
+        Ok(())
+    }
+
+    async fn has_consent(
+        &self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<bool, OAuthConsentStoreError> {
+        Ok(self
+            .consents
+            .get(&(email.clone(), client_id.as_ref().to_owned()))
+            .is_some_and(|granted| granted.contains(scope)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consent_is_per_client_and_scope() {
+        let mut store = HashmapOAuthConsentStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let client_id = OAuthClientId::default();
+        let email_scope = Scope::parse("email").unwrap();
+        let profile_scope = Scope::parse("profile").unwrap();
+        let both_scopes = Scope::parse("email profile").unwrap();
+
+        assert_eq!(store.has_consent(&email, &client_id, &email_scope).await, Ok(false));
+
+        store.grant_consent(&email, &client_id, &email_scope).await.unwrap();
+
+        assert_eq!(store.has_consent(&email, &client_id, &email_scope).await, Ok(true));
+        assert_eq!(store.has_consent(&email, &client_id, &both_scopes).await, Ok(false));
+        assert_eq!(
+            store.has_consent(&email, &OAuthClientId::default(), &email_scope).await,
+            Ok(false)
+        );
+
+        // Later grants add to the earlier ones
+        store.grant_consent(&email, &client_id, &profile_scope).await.unwrap();
+
+        assert_eq!(store.has_consent(&email, &client_id, &both_scopes).await, Ok(true));
+    }
+}
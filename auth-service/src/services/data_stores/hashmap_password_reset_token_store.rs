@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{
+        data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+        Email,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    // Keyed by the hash of the token, values are the email and the expiry unix timestamp
+    tokens: HashMap<String, (Email, i64)>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let expires_at = Utc::now().timestamp() + PASSWORD_RESET_TOKEN_TTL_SECONDS;
+        self.tokens.insert(token.hash(), (email, expires_at));
+        Ok(())
+    }
+
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, expires_at)) if Utc::now().timestamp() < expires_at => Ok(email),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+
+        let result = store.take_token(&token).await;
+        assert_eq!(result.unwrap(), email);
+
+        // Tokens can only be used once
+        let result = store.take_token(&token).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_take_unknown_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+
+        let result = store.take_token(&PasswordResetToken::default()).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_take_expired_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = PasswordResetToken::default();
+
+        store
+            .tokens
+            .insert(token.hash(), (email, Utc::now().timestamp() - 1));
+
+        let result = store.take_token(&token).await;
+        assert_eq!(result, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+}
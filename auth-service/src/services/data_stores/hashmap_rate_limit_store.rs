@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{RateLimitStore, RateLimitStoreError},
+    RateLimit,
+};
+
+#[derive(Default)]
+pub struct HashmapRateLimitStore {
+    buckets: HashMap<String, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    // Unix timestamps in milliseconds: when the tokens were counted, and when the bucket is full
+    // again. A full bucket is no different from a missing one.
+    updated_at: i64,
+    full_at: i64,
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashmapRateLimitStore {
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+    ) -> Result<Option<u64>, RateLimitStoreError> {
+        let now = Utc::now().timestamp_millis();
+        let capacity = limit.burst as f64;
+        let interval_ms = limit.refill_interval_ms().max(1) as f64;
+
+        // Forget buckets that have refilled since, like Redis expires them, or every new key
+        // would be kept forever
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+
+        let bucket = self.buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            full_at: now,
+        });
+
+        let refilled = (now - bucket.updated_at).max(0) as f64 / interval_ms;
+        bucket.tokens = (bucket.tokens + refilled).min(capacity);
+        bucket.updated_at = now;
+
+        let taken = bucket.tokens >= 1.0;
+        if taken {
+            bucket.tokens -= 1.0;
+        }
+
+        bucket.full_at = now + ((capacity - bucket.tokens) * interval_ms).ceil() as i64;
+
+        if taken {
+            return Ok(None);
+        }
+
+        let retry_after_ms = ((1.0 - bucket.tokens) * interval_ms).ceil() as u64;
+        Ok(Some(retry_after_ms.div_ceil(1000)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        burst: 2,
+        refill_interval_seconds: 10,
+    };
+
+    #[tokio::test]
+    async fn test_take_token_until_bucket_is_empty() {
+        let mut store = HashmapRateLimitStore::default();
+
+        assert_eq!(store.take_token("key", &LIMIT).await.unwrap(), None);
+        assert_eq!(store.take_token("key", &LIMIT).await.unwrap(), None);
+
+        let retry_after = store.take_token("key", &LIMIT).await.unwrap().unwrap();
+        assert!(retry_after > 0 && retry_after <= 10);
+
+        // Buckets are independent of each other
+        assert_eq!(store.take_token("other key", &LIMIT).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_refill_bucket_over_time() {
+        let mut store = HashmapRateLimitStore::default();
+
+        for _ in 0..2 {
+            store.take_token("key", &LIMIT).await.unwrap();
+        }
+
+        // One refill interval later a single token is back
+        store.buckets.get_mut("key").unwrap().updated_at -= 10_000;
+
+        assert_eq!(store.take_token("key", &LIMIT).await.unwrap(), None);
+        assert!(store.take_token("key", &LIMIT).await.unwrap().is_some());
+
+        // The bucket never holds more than the burst
+        store.buckets.get_mut("key").unwrap().updated_at -= 1_000_000;
+
+        assert_eq!(store.take_token("key", &LIMIT).await.unwrap(), None);
+        assert_eq!(store.take_token("key", &LIMIT).await.unwrap(), None);
+        assert!(store.take_token("key", &LIMIT).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_forget_refilled_buckets() {
+        let mut store = HashmapRateLimitStore::default();
+
+        store.take_token("key", &LIMIT).await.unwrap();
+        store.take_token("other key", &LIMIT).await.unwrap();
+
+        // One refill interval later "key" is full again
+        store.buckets.get_mut("key").unwrap().full_at -= 10_000;
+
+        store.take_token("third key", &LIMIT).await.unwrap();
+
+        assert!(!store.buckets.contains_key("key"));
+        assert!(store.buckets.contains_key("other key"));
+        assert!(store.buckets.contains_key("third key"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    codes: HashMap<Email, Vec<RecoveryCode>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.insert(email.clone(), codes);
+        Ok(())
+    }
+
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let codes = self
+            .codes
+            .get_mut(email)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        let index = codes
+            .iter()
+            .position(|stored| stored == code)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        codes.remove(index);
+        Ok(())
+    }
+
+    async fn restore_code(
+        &mut self,
+        email: &Email,
+        code: RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.entry(email.clone()).or_default().push(code);
+        Ok(())
+    }
+
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.get(email).map_or(0, Vec::len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_use_code() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let codes = vec![RecoveryCode::default(), RecoveryCode::default()];
+
+        store.replace_codes(&email, codes.clone()).await.unwrap();
+        assert_eq!(store.count_codes(&email).await, Ok(2));
+
+        // Codes are accepted regardless of case
+        let code = RecoveryCode::parse(codes[0].as_ref().to_uppercase()).unwrap();
+        assert_eq!(store.use_code(&email, &code).await, Ok(()));
+        assert_eq!(store.count_codes(&email).await, Ok(1));
+
+        // But only once
+        assert_eq!(
+            store.use_code(&email, &code).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_code() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let code = RecoveryCode::default();
+
+        store.replace_codes(&email, vec![code.clone()]).await.unwrap();
+        store.use_code(&email, &code).await.unwrap();
+        store.restore_code(&email, code.clone()).await.unwrap();
+
+        assert_eq!(store.use_code(&email, &code).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_replace_codes_invalidates_old_codes() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let old_code = RecoveryCode::default();
+
+        store.replace_codes(&email, vec![old_code.clone()]).await.unwrap();
+        store
+            .replace_codes(&email, vec![RecoveryCode::default()])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.use_code(&email, &old_code).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.count_codes(&email).await, Ok(1));
+    }
+}
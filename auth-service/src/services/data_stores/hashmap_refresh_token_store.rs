@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::data_stores::{
+    RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+    RefreshTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    // Keyed by the hash of the refresh token
+    tokens: HashMap<String, RefreshTokenRecord>,
+    revoked_families: HashSet<RefreshTokenFamilyId>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.insert(token.hash(), record);
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        match self.tokens.get(&token.hash()) {
+            Some(record) => Ok(RefreshTokenRecord {
+                revoked: record.revoked || self.revoked_families.contains(&record.family_id),
+                ..record.clone()
+            }),
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        match self.tokens.get_mut(&token.hash()) {
+            Some(record) => {
+                record.used = true;
+                Ok(())
+            }
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.revoked_families.insert(family_id.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{AuthMethod, Authentication, Email};
+
+    use super::*;
+
+    fn new_record(family_id: RefreshTokenFamilyId) -> RefreshTokenRecord {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        RefreshTokenRecord::new(
+            email,
+            family_id,
+            chrono::Utc::now().timestamp() + 60,
+            0,
+            Authentication::now(&[AuthMethod::Password]),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let record = new_record(RefreshTokenFamilyId::default());
+
+        let result = store.add_token(token.clone(), record.clone()).await;
+        assert!(result.is_ok());
+
+        let result = store.get_token(&token).await;
+        assert_eq!(result, Ok(record));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_not_found() {
+        let store = HashmapRefreshTokenStore::default();
+
+        let result = store.get_token(&RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_mark_token_used() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        store
+            .tokens
+            .insert(token.hash(), new_record(RefreshTokenFamilyId::default()));
+
+        let result = store.mark_token_used(&token).await;
+        assert!(result.is_ok());
+        assert!(store.get_token(&token).await.unwrap().used);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let family_id = RefreshTokenFamilyId::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        store.tokens.insert(first.hash(), new_record(family_id.clone()));
+        store.tokens.insert(second.hash(), new_record(family_id.clone()));
+        store
+            .tokens
+            .insert(other.hash(), new_record(RefreshTokenFamilyId::default()));
+
+        let result = store.revoke_family(&family_id).await;
+        assert!(result.is_ok());
+
+        assert!(store.get_token(&first).await.unwrap().revoked);
+        assert!(store.get_token(&second).await.unwrap().revoked);
+        assert!(!store.get_token(&other).await.unwrap().revoked);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{ServiceAccountStore, ServiceAccountStoreError},
+    ServiceAccount, ServiceAccountId,
+};
+
+#[derive(Default)]
+pub struct HashmapServiceAccountStore {
+    // Keyed by service account ID
+    accounts: HashMap<String, ServiceAccount>,
+}
+
+impl HashmapServiceAccountStore {
+    fn get_account_mut(
+        &mut self,
+        id: &ServiceAccountId,
+    ) -> Result<&mut ServiceAccount, ServiceAccountStoreError> {
+        self.accounts
+            .get_mut(id.as_ref())
+            .ok_or(ServiceAccountStoreError::AccountNotFound)
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceAccountStore for HashmapServiceAccountStore {
+    async fn add_account(&mut self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError> {
+        if self.accounts.contains_key(account.id.as_ref()) {
+            return Err(ServiceAccountStoreError::AccountAlreadyExists);
+        }
+
+        self.accounts.insert(account.id.as_ref().to_owned(), account);
+        Ok(())
+    }
+
+    async fn get_account(
+        &self,
+        id: &ServiceAccountId,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        self.accounts
+            .get(id.as_ref())
+            .cloned()
+            .ok_or(ServiceAccountStoreError::AccountNotFound)
+    }
+
+    async fn update_secret(
+        &mut self,
+        id: &ServiceAccountId,
+        secret_hash: String,
+    ) -> Result<(), ServiceAccountStoreError> {
+        self.get_account_mut(id)?.secret_hash = secret_hash;
+        Ok(())
+    }
+
+    async fn set_disabled(
+        &mut self,
+        id: &ServiceAccountId,
+        disabled: bool,
+    ) -> Result<(), ServiceAccountStoreError> {
+        self.get_account_mut(id)?.disabled = disabled;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Scope, ServiceAccountSecret};
+
+    fn account() -> (ServiceAccount, ServiceAccountSecret) {
+        let secret = ServiceAccountSecret::default();
+        let scope = Scope::parse("reports:read").unwrap();
+        (ServiceAccount::new("Job".to_owned(), scope, &secret), secret)
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_account() {
+        let mut store = HashmapServiceAccountStore::default();
+        let (account, _) = account();
+
+        assert_eq!(store.add_account(account.clone()).await, Ok(()));
+        assert_eq!(
+            store.add_account(account.clone()).await,
+            Err(ServiceAccountStoreError::AccountAlreadyExists)
+        );
+
+        assert_eq!(store.get_account(&account.id).await, Ok(account));
+        assert_eq!(
+            store.get_account(&ServiceAccountId::default()).await,
+            Err(ServiceAccountStoreError::AccountNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_secret_and_disable() {
+        let mut store = HashmapServiceAccountStore::default();
+        let (account, secret) = account();
+        store.add_account(account.clone()).await.unwrap();
+
+        let new_secret = ServiceAccountSecret::default();
+        store.update_secret(&account.id, new_secret.hash()).await.unwrap();
+        store.set_disabled(&account.id, true).await.unwrap();
+
+        let stored = store.get_account(&account.id).await.unwrap();
+        assert!(!stored.verify_secret(secret.as_ref()));
+        assert!(stored.verify_secret(new_secret.as_ref()));
+        assert!(stored.disabled);
+
+        assert_eq!(
+            store.set_disabled(&ServiceAccountId::default(), true).await,
+            Err(ServiceAccountStoreError::AccountNotFound)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{SessionStore, SessionStoreError},
+    Email, RefreshTokenFamilyId, Session,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    // Keyed by session ID
+    sessions: HashMap<String, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions
+            .insert(session.id.as_ref().to_owned(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id.as_ref())
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &RefreshTokenFamilyId,
+        last_seen_at: i64,
+    ) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id.as_ref())
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = last_seen_at;
+        Ok(())
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &RefreshTokenFamilyId,
+    ) -> Result<(), SessionStoreError> {
+        match self.sessions.get(id.as_ref()) {
+            Some(session) if session.email == *email => {
+                self.sessions.remove(id.as_ref());
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ClientInfo;
+
+    fn new_session(email: &Email) -> Session {
+        Session::new(
+            RefreshTokenFamilyId::default(),
+            email.clone(),
+            ClientInfo {
+                user_agent: Some("Firefox".to_owned()),
+                ip_address: Some("127.0.0.1".to_owned()),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_list_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        let mut first = new_session(&email);
+        first.created_at -= 60_000;
+        let second = new_session(&email);
+
+        store.add_session(second.clone()).await.unwrap();
+        store.add_session(first.clone()).await.unwrap();
+        store.add_session(new_session(&other_email)).await.unwrap();
+
+        assert_eq!(
+            store.list_sessions(&email).await.unwrap(),
+            vec![first.clone(), second]
+        );
+        assert_eq!(store.get_session(&first.id).await.unwrap(), first);
+
+        store
+            .touch_session(&first.id, first.last_seen_at + 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_session(&first.id).await.unwrap().last_seen_at,
+            first.last_seen_at + 10
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        let session = new_session(&email);
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(
+            store.remove_session(&other_email, &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+
+        store.remove_session(&email, &session.id).await.unwrap();
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_user_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_session(new_session(&email)).await.unwrap();
+        store.add_session(new_session(&email)).await.unwrap();
+        store.add_session(new_session(&other_email)).await.unwrap();
+
+        store.remove_user_sessions(&email).await.unwrap();
+
+        assert!(store.list_sessions(&email).await.unwrap().is_empty());
+        assert_eq!(store.list_sessions(&other_email).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{SigningKeyRecord, SigningKeyStore, SigningKeyStoreError};
+
+#[derive(Default)]
+pub struct HashmapSigningKeyStore {
+    keys: HashMap<String, SigningKeyRecord>,
+}
+
+#[async_trait::async_trait]
+impl SigningKeyStore for HashmapSigningKeyStore {
+    async fn add_key(&mut self, record: SigningKeyRecord) -> Result<(), SigningKeyStoreError> {
+        self.keys.insert(record.kid.clone(), record);
+        Ok(())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<SigningKeyRecord>, SigningKeyStoreError> {
+        Ok(self.keys.values().cloned().collect())
+    }
+
+    async fn remove_key(&mut self, kid: &str) -> Result<(), SigningKeyStoreError> {
+        self.keys.remove(kid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::Algorithm;
+
+    use super::*;
+
+    fn record(kid: &str, activates_at: i64) -> SigningKeyRecord {
+        SigningKeyRecord {
+            kid: kid.to_owned(),
+            algorithm: Algorithm::ES256,
+            encrypted_key: vec![1, 2, 3],
+            activates_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_get_and_remove_keys() {
+        let mut store = HashmapSigningKeyStore::default();
+
+        store.add_key(record("first", 10)).await.unwrap();
+        store.add_key(record("second", 20)).await.unwrap();
+
+        let mut keys = store.get_keys().await.unwrap();
+        keys.sort_by_key(|key| key.activates_at);
+        assert_eq!(keys, vec![record("first", 10), record("second", 20)]);
+
+        store.remove_key("first").await.unwrap();
+        assert_eq!(store.get_keys().await.unwrap(), vec![record("second", 20)]);
+
+        // Removing an unknown key is not an error
+        store.remove_key("first").await.unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{TotpSecretRecord, TotpStore, TotpStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapTotpStore {
+    secrets: HashMap<Email, TotpEntry>,
+}
+
+struct TotpEntry {
+    record: TotpSecretRecord,
+    last_used_step: Option<u64>,
+}
+
+#[async_trait::async_trait]
+impl TotpStore for HashmapTotpStore {
+    async fn add_pending_secret(
+        &mut self,
+        email: &Email,
+        encrypted_secret: Vec<u8>,
+    ) -> Result<(), TotpStoreError> {
+        if matches!(self.secrets.get(email), Some(entry) if entry.record.confirmed) {
+            return Err(TotpStoreError::AlreadyConfirmed);
+        }
+
+        self.secrets.insert(
+            email.clone(),
+            TotpEntry {
+                record: TotpSecretRecord {
+                    encrypted_secret,
+                    confirmed: false,
+                },
+                last_used_step: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecretRecord, TotpStoreError> {
+        self.secrets
+            .get(email)
+            .map(|entry| entry.record.clone())
+            .ok_or(TotpStoreError::SecretNotFound)
+    }
+
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpStoreError> {
+        let entry = self
+            .secrets
+            .get_mut(email)
+            .ok_or(TotpStoreError::SecretNotFound)?;
+
+        entry.record.confirmed = true;
+        Ok(())
+    }
+
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        let entry = self
+            .secrets
+            .get_mut(email)
+            .ok_or(TotpStoreError::SecretNotFound)?;
+
+        if entry.last_used_step.is_some_and(|last| last >= step) {
+            return Err(TotpStoreError::CodeAlreadyUsed);
+        }
+
+        entry.last_used_step = Some(step);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_pending_secret() {
+        let mut store = HashmapTotpStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        assert_eq!(
+            store.get_secret(&email).await,
+            Err(TotpStoreError::SecretNotFound)
+        );
+
+        store.add_pending_secret(&email, vec![1]).await.unwrap();
+
+        // An unconfirmed secret can be replaced
+        store.add_pending_secret(&email, vec![2]).await.unwrap();
+
+        let record = store.get_secret(&email).await.unwrap();
+        assert_eq!(record.encrypted_secret, vec![2]);
+        assert!(!record.confirmed);
+
+        store.confirm_secret(&email).await.unwrap();
+        assert!(store.get_secret(&email).await.unwrap().confirmed);
+
+        // A confirmed one can't
+        assert_eq!(
+            store.add_pending_secret(&email, vec![3]).await,
+            Err(TotpStoreError::AlreadyConfirmed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_use_step_rejects_replayed_and_older_steps() {
+        let mut store = HashmapTotpStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        store.add_pending_secret(&email, vec![1]).await.unwrap();
+
+        assert_eq!(store.use_step(&email, 10).await, Ok(()));
+        assert_eq!(
+            store.use_step(&email, 10).await,
+            Err(TotpStoreError::CodeAlreadyUsed)
+        );
+        assert_eq!(
+            store.use_step(&email, 9).await,
+            Err(TotpStoreError::CodeAlreadyUsed)
+        );
+        assert_eq!(store.use_step(&email, 11).await, Ok(()));
+    }
+}
@@ -1,16 +1,24 @@
 use std::collections::HashMap;
 
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::{self, Email},
+    data_stores::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+    email::Email,
 };
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<LoginAttemptId, (Email, TwoFACode)>,
+    // Pending login attempts of each user, oldest first
+    login_attempts: HashMap<Email, Vec<LoginAttemptId>>,
+    // Wrong codes entered for each login attempt
+    failed_attempts: HashMap<LoginAttemptId, u32>,
+    // Times the code of each login attempt was replaced
+    resends: HashMap<LoginAttemptId, u32>,
 }
 
-// TODO: implement TwoFACodeStore for HashmapTwoFACodeStore
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
@@ -19,85 +27,145 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let login_attempts = self.login_attempts.entry(email.clone()).or_default();
+        login_attempts.push(login_attempt_id.clone());
+
+        let excess = login_attempts.len().saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS);
+        for dropped_id in login_attempts.drain(..excess) {
+            self.codes.remove(&dropped_id);
+            self.failed_attempts.remove(&dropped_id);
+            self.resends.remove(&dropped_id);
+        }
+
+        self.codes.insert(login_attempt_id, (email, code));
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes.remove(&email);
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        if let Some((email, _)) = self.codes.remove(login_attempt_id) {
+            if let Some(login_attempts) = self.login_attempts.get_mut(&email) {
+                login_attempts.retain(|id| id != login_attempt_id);
+            }
+        }
+
+        self.failed_attempts.remove(login_attempt_id);
+        self.resends.remove(login_attempt_id);
         Ok(())
     }
     
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let value = self.codes.get(&email);
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        match self.codes.get(login_attempt_id) {
+            Some((email, code)) => Ok((email.clone(), code.clone())),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
 
-        match value {
-            Some((id, ref code)) => return Ok((id.clone(), code.clone())),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let failed_attempts = self
+            .failed_attempts
+            .entry(login_attempt_id.clone())
+            .or_default();
+        *failed_attempts += 1;
+
+        if *failed_attempts >= max_attempts {
+            self.remove_code(login_attempt_id).await?;
+            return Err(TwoFACodeStoreError::TooManyAttempts);
         }
+
+        Ok(())
+    }
+
+    async fn replace_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let Some((_, stored_code)) = self.codes.get_mut(login_attempt_id) else {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        };
+
+        let resends = self.resends.entry(login_attempt_id.clone()).or_default();
+
+        if *resends >= max_resends {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        *resends += 1;
+        *stored_code = code;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn email() -> Email {
+        Email::parse("test@example.com".to_string()).unwrap()
+    }
+
     #[tokio::test]
     async fn test_add_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com".to_string()).unwrap();
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
 
         let result = store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(email(), login_attempt_id.clone(), code.clone())
             .await;
 
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert_eq!(store.codes.get(&login_attempt_id), Some(&(email(), code)));
     }
 
     #[tokio::test]
     async fn test_remove_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com".to_string()).unwrap();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
 
         store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .add_code(email(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
 
-        let result = store.remove_code(&email).await;
+        let result = store.remove_code(&login_attempt_id).await;
 
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), None);
+        assert_eq!(store.codes.get(&login_attempt_id), None);
+        assert_eq!(store.login_attempts.get(&email()), Some(&vec![]));
     }
 
     #[tokio::test]
     async fn test_get_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com".to_string()).unwrap();
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
         store
             .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .insert(login_attempt_id.clone(), (email(), code.clone()));
 
-        let result = store.get_code(&email).await;
+        let result = store.get_code(&login_attempt_id).await;
 
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), (login_attempt_id, code));
+        assert_eq!(result.unwrap(), (email(), code));
     }
 
     #[tokio::test]
     async fn test_get_code_not_found() {
         let store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com".to_string()).unwrap();
 
-        let result = store.get_code(&email).await;
+        let result = store.get_code(&LoginAttemptId::default()).await;
 
         assert!(result.is_err());
         assert_eq!(
@@ -105,4 +173,96 @@ mod tests {
             TwoFACodeStoreError::LoginAttemptIdNotFound
         );
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_keep_several_login_attempts_up_to_limit() {
+        let mut store = HashmapTwoFACodeStore::default();
+
+        let login_attempt_ids: Vec<_> = (0..=MAX_PENDING_LOGIN_ATTEMPTS)
+            .map(|_| LoginAttemptId::default())
+            .collect();
+
+        for login_attempt_id in &login_attempt_ids {
+            store
+                .add_code(email(), login_attempt_id.clone(), TwoFACode::default())
+                .await
+                .unwrap();
+        }
+
+        // The oldest login attempt made room for the newest
+        assert_eq!(
+            store.get_code(&login_attempt_ids[0]).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        for login_attempt_id in &login_attempt_ids[1..] {
+            assert!(store.get_code(login_attempt_id).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
+        let other_login_attempt_id = LoginAttemptId::default();
+
+        for id in [&login_attempt_id, &other_login_attempt_id] {
+            store
+                .add_code(email(), id.clone(), TwoFACode::default())
+                .await
+                .unwrap();
+        }
+
+        assert!(store.record_failed_attempt(&login_attempt_id, 2).await.is_ok());
+
+        // Each login attempt has its own count
+        assert!(store
+            .record_failed_attempt(&other_login_attempt_id, 2)
+            .await
+            .is_ok());
+
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt_id, 2).await,
+            Err(TwoFACodeStoreError::TooManyAttempts)
+        );
+        assert_eq!(
+            store.get_code(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert!(store.get_code(&other_login_attempt_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(
+            store
+                .replace_code(&login_attempt_id, TwoFACode::default(), 1)
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(email(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        let code = TwoFACode::default();
+        store
+            .replace_code(&login_attempt_id, code.clone(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_code(&login_attempt_id).await.unwrap(), (email(), code.clone()));
+
+        assert_eq!(
+            store
+                .replace_code(&login_attempt_id, TwoFACode::default(), 1)
+                .await,
+            Err(TwoFACodeStoreError::TooManyResends)
+        );
+        assert_eq!(store.get_code(&login_attempt_id).await.unwrap(), (email(), code));
+    }
+}
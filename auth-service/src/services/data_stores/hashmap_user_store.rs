@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{Email, Password, TwoFAMethod, User, UserStore, UserStoreError};
 
 // Create a new struct called `HashmapUserStore` containing a `users` field
 // which stores a `HashMap`` of email `String`s mapped to `User` objects.
@@ -55,6 +55,47 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn enable_2fa(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.requires_2fa = true;
+                user.two_fa_method = method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_magic_link_enabled(&mut self, email: &Email, enabled: bool) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.magic_link_enabled = enabled;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -68,6 +109,9 @@ mod tests {
             email: Email::parse("test@example.com".to_owned()).unwrap(),
             password: Password::parse("password".to_owned()).unwrap(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
+            magic_link_enabled: false,
         };
 
         // Test adding a new user
@@ -88,6 +132,9 @@ mod tests {
             email: email.clone(),
             password: Password::parse("password".to_owned()).unwrap(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
+            magic_link_enabled: false,
         };
 
         // Test getting a user that exists
@@ -113,6 +160,9 @@ mod tests {
             email: email.clone(),
             password: password.clone(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
+            magic_link_enabled: false,
         };
 
         // Test validating a user that exists with correct password
@@ -135,4 +185,106 @@ mod tests {
 
         assert_eq!(result, Err(UserStoreError::UserNotFound));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let old_password = Password::parse("password".to_owned()).unwrap();
+        let new_password = Password::parse("new_password".to_owned()).unwrap();
+
+        let user = User {
+            email: email.clone(),
+            password: old_password.clone(),
+            requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
+            magic_link_enabled: false,
+        };
+
+        user_store.users.insert(email.clone(), user);
+
+        let result = user_store.update_password(&email, new_password.clone()).await;
+        assert_eq!(result, Ok(()));
+
+        assert_eq!(
+            user_store.validate_user(&email, &old_password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(user_store.validate_user(&email, &new_password).await, Ok(()));
+
+        // Test updating the password of a user that doesn't exist
+        let result = user_store
+            .update_password(
+                &Email::parse("nonexistent@example.com".to_owned()).unwrap(),
+                new_password,
+            )
+            .await;
+
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_mark_verified() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+
+        user_store
+            .add_user(User::new(email.clone(), password, false))
+            .await
+            .unwrap();
+
+        assert!(!user_store.get_user(&email).await.unwrap().verified);
+
+        let result = user_store.mark_verified(&email).await;
+        assert_eq!(result, Ok(()));
+        assert!(user_store.get_user(&email).await.unwrap().verified);
+
+        let result = user_store
+            .mark_verified(&Email::parse("nonexistent@example.com".to_owned()).unwrap())
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_enable_2fa() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+
+        user_store
+            .add_user(User::new(email.clone(), password, false))
+            .await
+            .unwrap();
+
+        let result = user_store.enable_2fa(&email, TwoFAMethod::Totp).await;
+        assert_eq!(result, Ok(()));
+
+        let user = user_store.get_user(&email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_set_magic_link_enabled() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+
+        user_store
+            .add_user(User::new(email.clone(), password, false))
+            .await
+            .unwrap();
+
+        assert!(!user_store.get_user(&email).await.unwrap().magic_link_enabled);
+
+        let result = user_store.set_magic_link_enabled(&email, true).await;
+        assert_eq!(result, Ok(()));
+        assert!(user_store.get_user(&email).await.unwrap().magic_link_enabled);
+
+        let result = user_store.set_magic_link_enabled(&email, false).await;
+        assert_eq!(result, Ok(()));
+        assert!(!user_store.get_user(&email).await.unwrap().magic_link_enabled);
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{WebauthnCeremony, WebauthnChallengeStore, WebauthnChallengeStoreError},
+    WebauthnChallenge, WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapWebauthnChallengeStore {
+    // Keyed by the challenge, values are the ceremony and the expiry unix timestamp
+    challenges: HashMap<String, (WebauthnCeremony, i64)>,
+}
+
+#[async_trait::async_trait]
+impl WebauthnChallengeStore for HashmapWebauthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: WebauthnChallenge,
+        ceremony: WebauthnCeremony,
+    ) -> Result<(), WebauthnChallengeStoreError> {
+        let expires_at = Utc::now().timestamp() + WEBAUTHN_CHALLENGE_TTL_SECONDS;
+        self.challenges
+            .insert(challenge.as_ref().to_owned(), (ceremony, expires_at));
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &mut self,
+        challenge: &WebauthnChallenge,
+    ) -> Result<WebauthnCeremony, WebauthnChallengeStoreError> {
+        match self.challenges.remove(challenge.as_ref()) {
+            Some((ceremony, expires_at)) if Utc::now().timestamp() < expires_at => Ok(ceremony),
+            _ => Err(WebauthnChallengeStoreError::ChallengeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    #[tokio::test]
+    async fn test_take_challenge() {
+        let mut store = HashmapWebauthnChallengeStore::default();
+        let challenge = WebauthnChallenge::default();
+        let ceremony = WebauthnCeremony::Registration {
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+        };
+
+        store
+            .add_challenge(challenge.clone(), ceremony.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.take_challenge(&challenge).await, Ok(ceremony));
+
+        // Challenges can only be answered once
+        assert_eq!(
+            store.take_challenge(&challenge).await,
+            Err(WebauthnChallengeStoreError::ChallengeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_take_expired_challenge() {
+        let mut store = HashmapWebauthnChallengeStore::default();
+        let challenge = WebauthnChallenge::default();
+        let ceremony = WebauthnCeremony::Authentication {
+            email: None,
+            login_attempt_id: None,
+        };
+
+        store.challenges.insert(
+            challenge.as_ref().to_owned(),
+            (ceremony, Utc::now().timestamp() - 1),
+        );
+
+        assert_eq!(
+            store.take_challenge(&challenge).await,
+            Err(WebauthnChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
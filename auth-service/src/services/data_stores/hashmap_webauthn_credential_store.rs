@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{WebauthnCredential, WebauthnCredentialStore, WebauthnCredentialStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapWebauthnCredentialStore {
+    // Keyed by credential ID
+    credentials: HashMap<Vec<u8>, WebauthnCredential>,
+}
+
+#[async_trait::async_trait]
+impl WebauthnCredentialStore for HashmapWebauthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebauthnCredential,
+    ) -> Result<(), WebauthnCredentialStoreError> {
+        if self.credentials.contains_key(&credential.credential_id) {
+            return Err(WebauthnCredentialStoreError::CredentialAlreadyExists);
+        }
+
+        self.credentials
+            .insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<WebauthnCredential, WebauthnCredentialStoreError> {
+        self.credentials
+            .get(credential_id)
+            .cloned()
+            .ok_or(WebauthnCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError> {
+        Ok(self
+            .credentials
+            .values()
+            .filter(|credential| &credential.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), WebauthnCredentialStoreError> {
+        let credential = self
+            .credentials
+            .get_mut(credential_id)
+            .ok_or(WebauthnCredentialStoreError::CredentialNotFound)?;
+
+        if sign_count <= credential.sign_count && (sign_count, credential.sign_count) != (0, 0) {
+            return Err(WebauthnCredentialStoreError::SignCountNotIncreased);
+        }
+
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AttestationFormat;
+
+    fn credential(credential_id: &[u8], email: &Email, sign_count: u32) -> WebauthnCredential {
+        WebauthnCredential {
+            credential_id: credential_id.to_vec(),
+            email: email.clone(),
+            public_key: vec![4; 65],
+            sign_count,
+            attestation_format: AttestationFormat::None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credentials() {
+        let mut store = HashmapWebauthnCredentialStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_credential(credential(b"one", &email, 0)).await.unwrap();
+        store.add_credential(credential(b"two", &email, 0)).await.unwrap();
+        store.add_credential(credential(b"three", &other_email, 0)).await.unwrap();
+
+        let result = store.add_credential(credential(b"one", &other_email, 0)).await;
+        assert_eq!(result, Err(WebauthnCredentialStoreError::CredentialAlreadyExists));
+
+        assert_eq!(store.get_credential(b"one").await.unwrap().email, email);
+        assert_eq!(store.get_credentials(&email).await.unwrap().len(), 2);
+        assert_eq!(
+            store.get_credential(b"unknown").await,
+            Err(WebauthnCredentialStoreError::CredentialNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let mut store = HashmapWebauthnCredentialStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        // Authenticators without a counter keep reporting 0
+        store.add_credential(credential(b"counterless", &email, 0)).await.unwrap();
+        assert_eq!(store.update_sign_count(b"counterless", 0).await, Ok(()));
+
+        store.add_credential(credential(b"counting", &email, 5)).await.unwrap();
+        assert_eq!(store.update_sign_count(b"counting", 6).await, Ok(()));
+        assert_eq!(
+            store.update_sign_count(b"counting", 6).await,
+            Err(WebauthnCredentialStoreError::SignCountNotIncreased)
+        );
+        assert_eq!(
+            store.update_sign_count(b"counting", 0).await,
+            Err(WebauthnCredentialStoreError::SignCountNotIncreased)
+        );
+    }
+}
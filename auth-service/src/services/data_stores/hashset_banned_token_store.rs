@@ -1,26 +1,60 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
-use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+use chrono::Utc;
+
+use crate::domain::{BannedTokenStore, BannedTokenStoreError, Email};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
-    banned_token_store: HashSet<String>
+    // Banned jti to the expiry of its token
+    banned_token_store: HashMap<String, i64>,
+    token_generations: HashMap<Email, u64>,
+    // Banned session id to the expiry of its last auth token
+    banned_sessions: HashMap<String, i64>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
 
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>{
-        self.banned_token_store.insert(token);
+    async fn store_token(&mut self, jti: String, expires_at: i64) -> Result<(), BannedTokenStoreError>{
+        // Forget bans of tokens that have expired since, like Redis does
+        let now = Utc::now().timestamp();
+        self.banned_token_store.retain(|_, expires_at| *expires_at > now);
+
+        self.banned_token_store.insert(jti, expires_at);
         Ok(())
     }
 
-    async fn token_exists(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        // match self.banned_token_store.contains(token) {
-        //     true => return Ok(true),
-        //     false => return Err(false)
-        // };
-        Ok(self.banned_token_store.contains(token))
+    async fn token_exists(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self
+            .banned_token_store
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at > Utc::now().timestamp()))
+    }
+
+    async fn ban_user_tokens(&mut self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        let generation = self.token_generations.entry(email.clone()).or_default();
+        *generation += 1;
+        Ok(*generation)
+    }
+
+    async fn get_token_generation(&self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        Ok(self.token_generations.get(email).copied().unwrap_or_default())
+    }
+
+    async fn ban_session(&mut self, session_id: String, expires_at: i64) -> Result<(), BannedTokenStoreError> {
+        let now = Utc::now().timestamp();
+        self.banned_sessions.retain(|_, expires_at| *expires_at > now);
+
+        self.banned_sessions.insert(session_id, expires_at);
+        Ok(())
+    }
+
+    async fn session_banned(&self, session_id: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self
+            .banned_sessions
+            .get(session_id)
+            .is_some_and(|expires_at| *expires_at > Utc::now().timestamp()))
     }
 }
 
@@ -31,22 +65,67 @@ mod tests {
     #[tokio::test]
     async fn test_add_token() {
         let mut store = HashsetBannedTokenStore::default();
-        let token = "test_token".to_owned();
+        let jti = "test_jti".to_owned();
+        let expires_at = Utc::now().timestamp() + 600;
 
-        let result = store.store_token(token.clone()).await;
+        let result = store.store_token(jti.clone(), expires_at).await;
 
         assert!(result.is_ok());
-        assert!(store.banned_token_store.contains(&token));
+        assert_eq!(store.banned_token_store.get(&jti), Some(&expires_at));
     }
 
     #[tokio::test]
     async fn test_contains_token() {
         let mut store = HashsetBannedTokenStore::default();
-        let token = "test_token".to_owned();
-        store.banned_token_store.insert(token.clone());
+        let jti = "test_jti".to_owned();
+        store.banned_token_store.insert(jti.clone(), Utc::now().timestamp() + 600);
 
-        let result = store.token_exists(&token).await;
+        let result = store.token_exists(&jti).await;
 
         assert!(result.unwrap());
+        assert!(!store.token_exists("other_jti").await.unwrap());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_forgets_expired_bans() {
+        let mut store = HashsetBannedTokenStore::default();
+        let now = Utc::now().timestamp();
+
+        store.store_token("expired_jti".to_owned(), now - 1).await.unwrap();
+        assert!(!store.token_exists("expired_jti").await.unwrap());
+
+        store.store_token("test_jti".to_owned(), now + 600).await.unwrap();
+        assert!(!store.banned_token_store.contains_key("expired_jti"));
+    }
+
+    #[tokio::test]
+    async fn test_ban_session() {
+        let mut store = HashsetBannedTokenStore::default();
+        let now = Utc::now().timestamp();
+
+        store.ban_session("test_sid".to_owned(), now + 600).await.unwrap();
+        store.ban_session("expired_sid".to_owned(), now - 1).await.unwrap();
+
+        assert!(store.session_banned("test_sid").await.unwrap());
+        assert!(!store.session_banned("expired_sid").await.unwrap());
+        assert!(!store.session_banned("other_sid").await.unwrap());
+
+        // Sessions and tokens are banned separately
+        assert!(!store.token_exists("test_sid").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_user_tokens() {
+        let mut store = HashsetBannedTokenStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        assert_eq!(store.get_token_generation(&email).await.unwrap(), 0);
+
+        assert_eq!(store.ban_user_tokens(&email).await.unwrap(), 1);
+        assert_eq!(store.ban_user_tokens(&email).await.unwrap(), 2);
+
+        assert_eq!(store.get_token_generation(&email).await.unwrap(), 2);
+        assert_eq!(store.get_token_generation(&other_email).await.unwrap(), 0);
+    }
+}
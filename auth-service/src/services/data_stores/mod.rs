@@ -1,13 +1,83 @@
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
 pub mod hashmap_two_fa_code_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_password_reset_token_store;
+pub mod hashmap_cooldown_store;
+pub mod hashmap_totp_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_webauthn_credential_store;
+pub mod hashmap_webauthn_challenge_store;
+pub mod hashmap_magic_link_store;
+pub mod hashmap_oauth_client_store;
+pub mod hashmap_oauth_consent_store;
+pub mod hashmap_authorization_code_store;
+pub mod hashmap_service_account_store;
+pub mod hashmap_api_key_store;
+pub mod hashmap_session_store;
+pub mod hashmap_account_lockout_store;
+pub mod hashmap_rate_limit_store;
+pub mod hashmap_signing_key_store;
 pub mod postgres_user_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_totp_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_webauthn_credential_store;
+pub mod postgres_oauth_client_store;
+pub mod postgres_oauth_consent_store;
+pub mod postgres_service_account_store;
+pub mod postgres_api_key_store;
 pub mod redis_backed_token_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_refresh_token_store;
+pub mod redis_password_reset_token_store;
+pub mod redis_cooldown_store;
+pub mod redis_webauthn_challenge_store;
+pub mod redis_magic_link_store;
+pub mod redis_authorization_code_store;
+pub mod redis_session_store;
+pub mod redis_account_lockout_store;
+pub mod redis_rate_limit_store;
+pub mod redis_signing_key_store;
 
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
 pub use hashmap_two_fa_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_password_reset_token_store::*;
+pub use hashmap_cooldown_store::*;
+pub use hashmap_totp_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_webauthn_credential_store::*;
+pub use hashmap_webauthn_challenge_store::*;
+pub use hashmap_magic_link_store::*;
+pub use hashmap_oauth_client_store::*;
+pub use hashmap_oauth_consent_store::*;
+pub use hashmap_authorization_code_store::*;
+pub use hashmap_service_account_store::*;
+pub use hashmap_api_key_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_account_lockout_store::*;
+pub use hashmap_rate_limit_store::*;
+pub use hashmap_signing_key_store::*;
 pub use postgres_user_store::*;
+pub use postgres_refresh_token_store::*;
+pub use postgres_totp_store::*;
+pub use postgres_recovery_code_store::*;
+pub use postgres_webauthn_credential_store::*;
+pub use postgres_oauth_client_store::*;
+pub use postgres_oauth_consent_store::*;
+pub use postgres_service_account_store::*;
+pub use postgres_api_key_store::*;
 pub use redis_backed_token_store::*;
-pub use redis_two_fa_code_store::*;
\ No newline at end of file
+pub use redis_two_fa_code_store::*;
+pub use redis_refresh_token_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_cooldown_store::*;
+pub use redis_webauthn_challenge_store::*;
+pub use redis_magic_link_store::*;
+pub use redis_authorization_code_store::*;
+pub use redis_session_store::*;
+pub use redis_account_lockout_store::*;
+pub use redis_rate_limit_store::*;
+pub use redis_signing_key_store::*;
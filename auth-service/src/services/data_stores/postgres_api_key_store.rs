@@ -0,0 +1,171 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{ApiKeyStore, ApiKeyStoreError},
+    ApiKeyId, ApiKeyRecord, Email, Scope,
+};
+
+pub struct PostgresApiKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresApiKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for PostgresApiKeyStore {
+    #[tracing::instrument(name = "Adding API key to PostgreSQL", skip_all)]
+    async fn add_key(&mut self, record: ApiKeyRecord) -> Result<(), ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys
+                (id, email, name, key_hash, prefix, scopes, created_at, expires_at, last_used_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            record.id.as_ref(),
+            record.email.as_ref(),
+            &record.name,
+            &record.key_hash,
+            &record.prefix,
+            record.scope.tokens(),
+            record.created_at,
+            record.expires_at,
+            record.last_used_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from PostgreSQL", skip_all)]
+    async fn get_key_by_hash(&self, key_hash: &str) -> Result<ApiKeyRecord, ApiKeyStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, email, name, key_hash, prefix, scopes, created_at, expires_at, last_used_at
+            FROM api_keys
+            WHERE key_hash = $1
+            "#,
+            key_hash
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?
+        .ok_or(ApiKeyStoreError::KeyNotFound)?;
+
+        to_record(
+            row.id,
+            row.email,
+            row.name,
+            row.key_hash,
+            row.prefix,
+            row.scopes,
+            row.created_at,
+            row.expires_at,
+            row.last_used_at,
+        )
+    }
+
+    #[tracing::instrument(name = "Retrieving API keys of user from PostgreSQL", skip_all)]
+    async fn list_keys(&self, email: &Email) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, email, name, key_hash, prefix, scopes, created_at, expires_at, last_used_at
+            FROM api_keys
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|row| {
+            to_record(
+                row.id,
+                row.email,
+                row.name,
+                row.key_hash,
+                row.prefix,
+                row.scopes,
+                row.created_at,
+                row.expires_at,
+                row.last_used_at,
+            )
+        })
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Revoking API key in PostgreSQL", skip_all)]
+    async fn revoke_key(&mut self, email: &Email, id: &ApiKeyId) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM api_keys
+            WHERE id = $1 AND email = $2
+            "#,
+            id.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStoreError::KeyNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording API key use in PostgreSQL", skip_all)]
+    async fn record_use(&mut self, id: &ApiKeyId, used_at: i64) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys
+            SET last_used_at = $2
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+            used_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStoreError::KeyNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn to_record(
+    id: String,
+    email: String,
+    name: String,
+    key_hash: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: i64,
+    expires_at: Option<i64>,
+    last_used_at: Option<i64>,
+) -> Result<ApiKeyRecord, ApiKeyStoreError> {
+    Ok(ApiKeyRecord {
+        id: ApiKeyId::parse(id).map_err(ApiKeyStoreError::UnexpectedError)?,
+        email: Email::parse(email).map_err(ApiKeyStoreError::UnexpectedError)?,
+        name,
+        key_hash,
+        prefix,
+        scope: Scope::from_tokens(scopes).map_err(ApiKeyStoreError::UnexpectedError)?,
+        created_at,
+        expires_at,
+        last_used_at,
+    })
+}
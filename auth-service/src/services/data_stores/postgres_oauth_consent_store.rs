@@ -0,0 +1,78 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{OAuthConsentStore, OAuthConsentStoreError},
+    Email, OAuthClientId, Scope,
+};
+
+pub struct PostgresOAuthConsentStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthConsentStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthConsentStore for PostgresOAuthConsentStore {
+    #[tracing::instrument(name = "Granting OAuth consent in PostgreSQL", skip_all)]
+    async fn grant_consent(
+        &mut self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<(), OAuthConsentStoreError> {
+        // Merge with the scopes granted earlier, keeping them sorted and unique like `Scope`
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_consents (email, client_id, scopes)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (email, client_id) DO UPDATE
+            SET scopes = ARRAY(
+                SELECT DISTINCT unnest(oauth_consents.scopes || EXCLUDED.scopes) ORDER BY 1
+            ),
+            granted_at = NOW()
+            "#,
+            email.as_ref(),
+            client_id.as_ref(),
+            scope.tokens()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthConsentStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking OAuth consent in PostgreSQL", skip_all)]
+    async fn has_consent(
+        &self,
+        email: &Email,
+        client_id: &OAuthClientId,
+        scope: &Scope,
+    ) -> Result<bool, OAuthConsentStoreError> {
+        let granted = sqlx::query_scalar!(
+            r#"
+            SELECT scopes
+            FROM oauth_consents
+            WHERE email = $1 AND client_id = $2
+            "#,
+            email.as_ref(),
+            client_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthConsentStoreError::UnexpectedError(e.into()))?;
+
+        match granted {
+            Some(tokens) => {
+                let granted =
+                    Scope::from_tokens(tokens).map_err(OAuthConsentStoreError::UnexpectedError)?;
+                Ok(granted.contains(scope))
+            }
+            None => Ok(false),
+        }
+    }
+}
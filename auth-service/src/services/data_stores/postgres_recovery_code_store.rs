@@ -0,0 +1,179 @@
+use color_eyre::eyre::{eyre, Context};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError},
+    Email,
+};
+
+use super::postgres_user_store::{compute_password_hash, verify_password_hash};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // Recovery codes are hashed like passwords. Hash them in parallel, Argon2 is slow on purpose.
+        let tasks: Vec<_> = codes
+            .into_iter()
+            .map(|code| tokio::spawn(compute_password_hash(code.as_ref().to_owned())))
+            .collect();
+
+        let mut code_hashes = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let code_hash = task
+                .await
+                .wrap_err("recovery code hashing task failed")
+                .and_then(|result| result)
+                .map_err(RecoveryCodeStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash);
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        for code_hash in code_hashes {
+            sqlx::query!(
+                r#"
+                INSERT INTO recovery_codes (id, email, code_hash)
+                VALUES ($1, $2, $3)
+                "#,
+                Uuid::new_v4().to_string(),
+                email.as_ref(),
+                code_hash
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Using recovery code in PostgreSQL", skip_all)]
+    async fn use_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, code_hash
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        let mut matching_id = None;
+        for row in rows {
+            if verify_password_hash(row.code_hash, code.as_ref().to_owned())
+                .await
+                .is_ok()
+            {
+                matching_id = Some(row.id);
+                break;
+            }
+        }
+
+        let id = matching_id.ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        // Only one of two concurrent logins with the same code gets to delete it
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::CodeNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Restoring recovery code in PostgreSQL", skip_all)]
+    async fn restore_code(
+        &mut self,
+        email: &Email,
+        code: RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let code_hash = compute_password_hash(code.as_ref().to_owned())
+            .await
+            .map_err(RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (id, email, code_hash)
+            VALUES ($1, $2, $3)
+            "#,
+            Uuid::new_v4().to_string(),
+            email.as_ref(),
+            code_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Counting recovery codes in PostgreSQL", skip_all)]
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        usize::try_from(count)
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(eyre!(e)))
+    }
+}
@@ -0,0 +1,130 @@
+use color_eyre::eyre::{eyre, Context};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{
+        RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+        RefreshTokenStoreError,
+    },
+    Authentication, Email,
+};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let generation = i64::try_from(record.generation)
+            .wrap_err("failed to cast token generation to i64")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, email, family_id, expires_at, generation, auth_time, amr, used, revoked)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            token.hash(),
+            record.email.as_ref(),
+            record.family_id.as_ref(),
+            record.expires_at,
+            generation,
+            record.authentication.time,
+            &record.authentication.method_names(),
+            record.used,
+            record.revoked
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving refresh token from PostgreSQL", skip_all)]
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT email, family_id, expires_at, generation, auth_time, amr, used, revoked
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#,
+            token.hash()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            Ok(RefreshTokenRecord {
+                email: Email::parse(row.email)
+                    .map_err(|e| RefreshTokenStoreError::UnexpectedError(eyre!(e)))?,
+                family_id: RefreshTokenFamilyId::parse(row.family_id)
+                    .map_err(|e| RefreshTokenStoreError::UnexpectedError(eyre!(e)))?,
+                expires_at: row.expires_at,
+                generation: u64::try_from(row.generation)
+                    .wrap_err("failed to cast token generation to u64")
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?,
+                authentication: Authentication::from_parts(row.auth_time, &row.amr),
+                used: row.used,
+                revoked: row.revoked,
+            })
+        })
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?
+    }
+
+    #[tracing::instrument(name = "Marking refresh token used in PostgreSQL", skip_all)]
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET used = TRUE
+            WHERE token_hash = $1
+            "#,
+            token.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked = TRUE
+            WHERE family_id = $1
+            "#,
+            family_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
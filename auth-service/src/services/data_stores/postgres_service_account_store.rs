@@ -0,0 +1,124 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{ServiceAccountStore, ServiceAccountStoreError},
+    Scope, ServiceAccount, ServiceAccountId,
+};
+
+pub struct PostgresServiceAccountStore {
+    pool: PgPool,
+}
+
+impl PostgresServiceAccountStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceAccountStore for PostgresServiceAccountStore {
+    #[tracing::instrument(name = "Adding service account to PostgreSQL", skip_all)]
+    async fn add_account(&mut self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO service_accounts (id, name, secret_hash, scopes, disabled)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+            account.id.as_ref(),
+            &account.name,
+            &account.secret_hash,
+            account.scope.tokens(),
+            account.disabled
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceAccountStoreError::AccountAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving service account from PostgreSQL", skip_all)]
+    async fn get_account(
+        &self,
+        id: &ServiceAccountId,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, name, secret_hash, scopes, disabled
+            FROM service_accounts
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?
+        .ok_or(ServiceAccountStoreError::AccountNotFound)?;
+
+        Ok(ServiceAccount {
+            id: ServiceAccountId::parse(row.id).map_err(ServiceAccountStoreError::UnexpectedError)?,
+            name: row.name,
+            secret_hash: row.secret_hash,
+            scope: Scope::from_tokens(row.scopes)
+                .map_err(ServiceAccountStoreError::UnexpectedError)?,
+            disabled: row.disabled,
+        })
+    }
+
+    #[tracing::instrument(name = "Updating service account secret in PostgreSQL", skip_all)]
+    async fn update_secret(
+        &mut self,
+        id: &ServiceAccountId,
+        secret_hash: String,
+    ) -> Result<(), ServiceAccountStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE service_accounts
+            SET secret_hash = $2
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+            secret_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceAccountStoreError::AccountNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Disabling service account in PostgreSQL", skip_all)]
+    async fn set_disabled(
+        &mut self,
+        id: &ServiceAccountId,
+        disabled: bool,
+    ) -> Result<(), ServiceAccountStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE service_accounts
+            SET disabled = $2
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+            disabled
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceAccountStoreError::AccountNotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use color_eyre::eyre::Context;
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{TotpSecretRecord, TotpStore, TotpStoreError},
+    Email,
+};
+
+pub struct PostgresTotpStore {
+    pool: PgPool,
+}
+
+impl PostgresTotpStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpStore for PostgresTotpStore {
+    #[tracing::instrument(name = "Adding pending TOTP secret to PostgreSQL", skip_all)]
+    async fn add_pending_secret(
+        &mut self,
+        email: &Email,
+        encrypted_secret: Vec<u8>,
+    ) -> Result<(), TotpStoreError> {
+        // Only an unconfirmed secret may be overwritten
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, encrypted_secret, confirmed, last_used_step)
+            VALUES ($1, $2, FALSE, NULL)
+            ON CONFLICT (email) DO UPDATE
+            SET encrypted_secret = EXCLUDED.encrypted_secret, last_used_step = NULL
+            WHERE totp_secrets.confirmed = FALSE
+            "#,
+            email.as_ref(),
+            &encrypted_secret
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpStoreError::AlreadyConfirmed);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecretRecord, TotpStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT encrypted_secret, confirmed
+            FROM totp_secrets
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?
+        .map(|row| TotpSecretRecord {
+            encrypted_secret: row.encrypted_secret,
+            confirmed: row.confirmed,
+        })
+        .ok_or(TotpStoreError::SecretNotFound)
+    }
+
+    #[tracing::instrument(name = "Confirming TOTP secret in PostgreSQL", skip_all)]
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET confirmed = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpStoreError::SecretNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording used TOTP step in PostgreSQL", skip_all)]
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        let step = i64::try_from(step)
+            .wrap_err("failed to cast TOTP step to i64")
+            .map_err(TotpStoreError::UnexpectedError)?;
+
+        // A single conditional update, so two concurrent logins can't both use the same code
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET last_used_step = $1
+            WHERE email = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+            "#,
+            step,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            // Tell a replayed code apart from a missing secret
+            self.get_secret(email).await?;
+            return Err(TotpStoreError::CodeAlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
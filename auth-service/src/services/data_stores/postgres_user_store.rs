@@ -1,16 +1,14 @@
-use std::error::Error;
-
 use argon2::{
     password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
 };
 
 use sqlx::PgPool;
-use color_eyre::eyre::{self, eyre, Context, Result};
+use color_eyre::eyre::{eyre, Result};
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Email, Password, TwoFAMethod, User,
 };
 
 pub struct PostgresUserStore {
@@ -33,12 +31,15 @@ impl UserStore for PostgresUserStore {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, requires_2fa, verified, two_fa_method, magic_link_enabled)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
             user.email.as_ref(),
             &password_hash,
-            user.requires_2fa
+            user.requires_2fa,
+            user.verified,
+            user.two_fa_method.as_ref(),
+            user.magic_link_enabled
         )
         .execute(&self.pool)
         .await
@@ -51,7 +52,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, requires_2fa, verified, two_fa_method, magic_link_enabled
             FROM users
             WHERE email = $1
             "#,
@@ -66,6 +67,10 @@ impl UserStore for PostgresUserStore {
                 password: Password::parse(row.password_hash)
                     .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
                 requires_2fa: row.requires_2fa,
+                verified: row.verified,
+                two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                    .map_err(UserStoreError::UnexpectedError)?,
+                magic_link_enabled: row.magic_link_enabled,
             })
         })
         .ok_or(UserStoreError::UserNotFound)?
@@ -86,6 +91,101 @@ impl UserStore for PostgresUserStore {
         .await
         .map_err(|_| UserStoreError::InvalidCredentials)
     }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2
+            "#,
+            &password_hash,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user verified in PostgreSQL", skip_all)]
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Enabling 2FA in PostgreSQL", skip_all)]
+    async fn enable_2fa(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = TRUE, two_fa_method = $1
+            WHERE email = $2
+            "#,
+            method.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting magic link opt-in in PostgreSQL", skip_all)]
+    async fn set_magic_link_enabled(&mut self, email: &Email, enabled: bool) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET magic_link_enabled = $1
+            WHERE email = $2
+            "#,
+            enabled,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
 }
 
 // Helper function to verify if a given password matches an expected hash
@@ -94,7 +194,7 @@ impl UserStore for PostgresUserStore {
 // separate thread pool using tokio::task::spawn_blocking. Note that you
 // will need to update the input parameters to be String types instead of &str
 #[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: String,
     password_candidate: String,
 ) -> Result<()> {
@@ -115,7 +215,7 @@ async fn verify_password_hash(
 // separate thread pool using tokio::task::spawn_blocking. Note that you
 // will need to update the input parameters to be String types instead of &str
 #[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: String) -> Result<String> {
+pub(crate) async fn compute_password_hash(password: String) -> Result<String> {
     let result = tokio::task::spawn_blocking(move || {
         let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
@@ -0,0 +1,155 @@
+use color_eyre::eyre::{eyre, Context};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{WebauthnCredential, WebauthnCredentialStore, WebauthnCredentialStoreError},
+    AttestationFormat, Email,
+};
+
+pub struct PostgresWebauthnCredentialStore {
+    pool: PgPool,
+}
+
+impl PostgresWebauthnCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebauthnCredentialStore for PostgresWebauthnCredentialStore {
+    #[tracing::instrument(name = "Adding WebAuthn credential to PostgreSQL", skip_all)]
+    async fn add_credential(
+        &mut self,
+        credential: WebauthnCredential,
+    ) -> Result<(), WebauthnCredentialStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO webauthn_credentials (credential_id, email, public_key, sign_count, attestation_format)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (credential_id) DO NOTHING
+            "#,
+            &credential.credential_id,
+            credential.email.as_ref(),
+            &credential.public_key,
+            i64::from(credential.sign_count),
+            credential.attestation_format.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebauthnCredentialStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebauthnCredentialStoreError::CredentialAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving WebAuthn credential from PostgreSQL", skip_all)]
+    async fn get_credential(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<WebauthnCredential, WebauthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT credential_id, email, public_key, sign_count, attestation_format
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            "#,
+            credential_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| WebauthnCredentialStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            to_credential(
+                row.credential_id,
+                row.email,
+                row.public_key,
+                row.sign_count,
+                &row.attestation_format,
+            )
+        })
+        .ok_or(WebauthnCredentialStoreError::CredentialNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving WebAuthn credentials of user from PostgreSQL", skip_all)]
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT credential_id, email, public_key, sign_count, attestation_format
+            FROM webauthn_credentials
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| WebauthnCredentialStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|row| {
+            to_credential(
+                row.credential_id,
+                row.email,
+                row.public_key,
+                row.sign_count,
+                &row.attestation_format,
+            )
+        })
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating WebAuthn signature counter in PostgreSQL", skip_all)]
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), WebauthnCredentialStoreError> {
+        // A single conditional update, so a cloned authenticator can't race the original
+        let result = sqlx::query!(
+            r#"
+            UPDATE webauthn_credentials
+            SET sign_count = $1
+            WHERE credential_id = $2 AND (sign_count < $1 OR (sign_count = 0 AND $1 = 0))
+            "#,
+            i64::from(sign_count),
+            credential_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebauthnCredentialStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            // Tell a stale counter apart from a missing credential
+            self.get_credential(credential_id).await?;
+            return Err(WebauthnCredentialStoreError::SignCountNotIncreased);
+        }
+
+        Ok(())
+    }
+}
+
+fn to_credential(
+    credential_id: Vec<u8>,
+    email: String,
+    public_key: Vec<u8>,
+    sign_count: i64,
+    attestation_format: &str,
+) -> Result<WebauthnCredential, WebauthnCredentialStoreError> {
+    Ok(WebauthnCredential {
+        credential_id,
+        email: Email::parse(email)
+            .map_err(|e| WebauthnCredentialStoreError::UnexpectedError(eyre!(e)))?,
+        public_key,
+        sign_count: u32::try_from(sign_count)
+            .wrap_err("failed to cast signature counter to u32")
+            .map_err(WebauthnCredentialStoreError::UnexpectedError)?,
+        attestation_format: AttestationFormat::parse(attestation_format)
+            .map_err(WebauthnCredentialStoreError::UnexpectedError)?,
+    })
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{AccountLockId, AccountLockoutStore, AccountLockoutStoreError},
+    Email,
+};
+
+pub struct RedisAccountLockoutStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAccountLockoutStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AccountLockoutStore for RedisAccountLockoutStore {
+    #[tracing::instrument(name = "Record failed login", skip_all)]
+    async fn record_failed_login(
+        &mut self,
+        email: &Email,
+        window_seconds: u64,
+    ) -> Result<u32, AccountLockoutStoreError> {
+        let key = get_failed_logins_key(email);
+        let mut conn = self.conn.write().await;
+
+        let count: u32 = conn
+            .incr(&key, 1)
+            .wrap_err("failed to count failed login in Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        // The window starts with the first failure, Redis drops the count when it ends
+        if count == 1 {
+            let _: () = conn
+                .expire(&key, window_seconds as i64)
+                .wrap_err("failed to set expiry of failed logins in Redis")
+                .map_err(AccountLockoutStoreError::UnexpectedError)?;
+        }
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Clear failed logins", skip_all)]
+    async fn clear_failed_logins(&mut self, email: &Email) -> Result<(), AccountLockoutStoreError> {
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(get_failed_logins_key(email))
+            .wrap_err("failed to delete failed logins from Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Lock account", skip_all)]
+    async fn lock_account(
+        &mut self,
+        email: &Email,
+        lock_id: AccountLockId,
+        seconds: u64,
+    ) -> Result<(), AccountLockoutStoreError> {
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_lock_key(email), lock_id.as_ref(), seconds)
+            .wrap_err("failed to set account lock in Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get account lock", skip_all)]
+    async fn get_lock(&self, email: &Email) -> Result<Option<u64>, AccountLockoutStoreError> {
+        // Negative if the key does not exist, the lock has ended then
+        let remaining: i64 = self
+            .conn
+            .write()
+            .await
+            .ttl(get_lock_key(email))
+            .wrap_err("failed to get account lock TTL from Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        // A lock about to end is reported as one second either way
+        Ok((remaining >= 0).then_some(remaining.max(1) as u64))
+    }
+
+    #[tracing::instrument(name = "Unlock account", skip_all)]
+    async fn unlock_account(
+        &mut self,
+        email: &Email,
+        lock_id: &AccountLockId,
+    ) -> Result<(), AccountLockoutStoreError> {
+        let key = get_lock_key(email);
+        let mut conn = self.conn.write().await;
+
+        let stored_id: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get account lock from Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        if stored_id.as_deref() != Some(lock_id.as_ref()) {
+            return Err(AccountLockoutStoreError::LockNotFound);
+        }
+
+        let _: () = conn
+            .del(&[key, get_failed_logins_key(email)])
+            .wrap_err("failed to delete account lock from Redis")
+            .map_err(AccountLockoutStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+const FAILED_LOGINS_KEY_PREFIX: &str = "failed_logins:";
+const ACCOUNT_LOCK_KEY_PREFIX: &str = "account_lock:";
+
+fn get_failed_logins_key(email: &Email) -> String {
+    format!("{}{}", FAILED_LOGINS_KEY_PREFIX, email.as_ref())
+}
+
+fn get_lock_key(email: &Email) -> String {
+    format!("{}{}", ACCOUNT_LOCK_KEY_PREFIX, email.as_ref())
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{AuthorizationCodeStore, AuthorizationCodeStoreError},
+    Authentication, AuthorizationCode, AuthorizationGrant, CodeChallenge, Email, OAuthClientId,
+    RedirectUri, Scope, AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    #[tracing::instrument(name = "Add authorization code", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let ttl: u64 = AUTHORIZATION_CODE_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast AUTHORIZATION_CODE_TTL_SECONDS to u64")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let json = serde_json::to_string(&StoredGrant::from(grant))
+            .wrap_err("failed to serialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&code), json, ttl)
+            .wrap_err("failed to set authorization code in Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Take authorization code", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        // GETDEL, so a code can't be exchanged twice by concurrent requests
+        let json: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(code))
+            .wrap_err("failed to take authorization code from Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let Some(json) = json else {
+            return Err(AuthorizationCodeStoreError::CodeNotFound);
+        };
+
+        let stored: StoredGrant = serde_json::from_str(&json)
+            .wrap_err("failed to deserialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        stored
+            .try_into()
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredGrant {
+    client_id: String,
+    redirect_uri: String,
+    email: String,
+    scope: String,
+    code_challenge: String,
+    nonce: Option<String>,
+    auth_time: i64,
+    amr: Vec<String>,
+}
+
+impl From<AuthorizationGrant> for StoredGrant {
+    fn from(grant: AuthorizationGrant) -> Self {
+        Self {
+            client_id: grant.client_id.as_ref().to_owned(),
+            redirect_uri: grant.redirect_uri.as_ref().to_owned(),
+            email: grant.email.as_ref().to_owned(),
+            scope: grant.scope.to_string(),
+            code_challenge: grant.code_challenge.as_ref().to_owned(),
+            nonce: grant.nonce,
+            auth_time: grant.authentication.time,
+            amr: grant.authentication.method_names(),
+        }
+    }
+}
+
+impl TryFrom<StoredGrant> for AuthorizationGrant {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(stored: StoredGrant) -> Result<Self, Self::Error> {
+        Ok(Self {
+            client_id: OAuthClientId::parse(stored.client_id)?,
+            redirect_uri: RedirectUri::parse(stored.redirect_uri)?,
+            email: Email::parse(stored.email)?,
+            scope: Scope::parse(&stored.scope)?,
+            code_challenge: CodeChallenge::parse(stored.code_challenge)?,
+            nonce: stored.nonce,
+            authentication: Authentication::from_parts(stored.auth_time, &stored.amr),
+        })
+    }
+}
+
+const AUTHORIZATION_CODE_KEY_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> String {
+    format!("{}{}", AUTHORIZATION_CODE_KEY_PREFIX, code.hash())
+}
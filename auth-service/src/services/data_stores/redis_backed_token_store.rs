@@ -1,13 +1,14 @@
 use std::sync::Arc;
 
-use redis::{Commands, Connection, RedisResult};
+use redis::{Commands, Connection};
 use tokio::sync::RwLock;
 
+use chrono::Utc;
 use color_eyre::eyre::{Context, Result};
 
-use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
+use crate::domain::{
+    data_stores::{BannedTokenStore, BannedTokenStoreError},
+    Email,
 };
 
 pub struct RedisBannedTokenStore {
@@ -23,34 +24,14 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "Store token", skip_all)]
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL). 
-        // The value should simply be a `true` (boolean value).
-        // The expiration time should be set to TOKEN_TTL_SECONDS.
-        // NOTE: The TTL is expected to be a u64 so you will have to cast TOKEN_TTL_SECONDS to a u64. 
-        // Return BannedTokenStoreError::UnexpectedError if casting fails or the call to set_ex fails.
-        
-        let key = get_key(&token);
-        
-        let ttl: u64 = TOKEN_TTL_SECONDS.try_into()
-            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
-            .map_err(BannedTokenStoreError::UnexpectedError)?;
-
-        let _: () = self.conn
-            .write()
-            .await
-            .set_ex(&key, true, ttl)
-            .wrap_err("failed to set banned token in Redis")
-            .map_err(BannedTokenStoreError::UnexpectedError)?;
-
-        Ok(())
+    async fn store_token(&mut self, jti: String, expires_at: i64) -> Result<(), BannedTokenStoreError> {
+        set_ban(&mut *self.conn.write().await, &get_key(&jti), expires_at)
     }
 
     #[tracing::instrument(name = "Token exists", skip_all)]
-    async fn token_exists(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
+    async fn token_exists(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
         // Check if the token exists by calling the exists method on the Redis connection
-        let key = get_key(&token);
+        let key = get_key(jti);
         let token_banned = self
             .conn
             .write()
@@ -61,12 +42,80 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         Ok(token_banned)
     }
+
+    #[tracing::instrument(name = "Ban user tokens", skip_all)]
+    async fn ban_user_tokens(&mut self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        // The counter never expires: refresh tokens outlive auth tokens by weeks,
+        // and a counter that silently reset to 0 would revive them.
+        let generation = self
+            .conn
+            .write()
+            .await
+            .incr(get_generation_key(email), 1)
+            .wrap_err("failed to increment token generation in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(generation)
+    }
+
+    #[tracing::instrument(name = "Get token generation", skip_all)]
+    async fn get_token_generation(&self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        let generation: Option<u64> = self
+            .conn
+            .write()
+            .await
+            .get(get_generation_key(email))
+            .wrap_err("failed to get token generation from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(generation.unwrap_or_default())
+    }
+
+    #[tracing::instrument(name = "Ban session", skip_all)]
+    async fn ban_session(&mut self, session_id: String, expires_at: i64) -> Result<(), BannedTokenStoreError> {
+        set_ban(&mut *self.conn.write().await, &get_session_key(&session_id), expires_at)
+    }
+
+    #[tracing::instrument(name = "Session banned", skip_all)]
+    async fn session_banned(&self, session_id: &str) -> Result<bool, BannedTokenStoreError> {
+        self.conn
+            .write()
+            .await
+            .exists(get_session_key(session_id))
+            .wrap_err("failed to check if session is banned in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
+}
+
+// The ban only has to outlive the tokens it applies to
+fn set_ban(conn: &mut Connection, key: &str, expires_at: i64) -> Result<(), BannedTokenStoreError> {
+    let ttl = expires_at - Utc::now().timestamp();
+    if ttl <= 0 {
+        return Ok(());
+    }
+
+    let _: () = conn
+        .set_ex(key, true, ttl as u64)
+        .wrap_err("failed to set ban in Redis")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+    Ok(())
 }
 
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const TOKEN_GENERATION_KEY_PREFIX: &str = "token_generation:";
+const BANNED_SESSION_KEY_PREFIX: &str = "banned_session:";
 
 #[tracing::instrument(name = "Get key", skip_all)]
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
-}
\ No newline at end of file
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
+
+fn get_generation_key(email: &Email) -> String {
+    format!("{}{}", TOKEN_GENERATION_KEY_PREFIX, email.as_ref())
+}
+
+fn get_session_key(session_id: &str) -> String {
+    format!("{}{}", BANNED_SESSION_KEY_PREFIX, session_id)
+}
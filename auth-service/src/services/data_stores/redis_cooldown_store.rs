@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::domain::data_stores::{CooldownStore, CooldownStoreError};
+
+pub struct RedisCooldownStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisCooldownStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl CooldownStore for RedisCooldownStore {
+    #[tracing::instrument(name = "Start cooldown", skip_all)]
+    async fn start_cooldown(
+        &mut self,
+        key: &str,
+        seconds: u64,
+    ) -> Result<Option<u64>, CooldownStoreError> {
+        let key = get_key(key);
+        let mut conn = self.conn.write().await;
+
+        // SET NX only succeeds if no cooldown is running, and Redis ends it for us via the TTL
+        let started: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query(&mut *conn)
+            .wrap_err("failed to start cooldown in Redis")
+            .map_err(CooldownStoreError::UnexpectedError)?;
+
+        if started.is_some() {
+            return Ok(None);
+        }
+
+        let remaining: i64 = conn
+            .ttl(&key)
+            .wrap_err("failed to get cooldown TTL from Redis")
+            .map_err(CooldownStoreError::UnexpectedError)?;
+
+        // The key may have expired in between, report at least one second either way
+        Ok(Some(remaining.max(1) as u64))
+    }
+}
+
+const COOLDOWN_KEY_PREFIX: &str = "cooldown:";
+
+fn get_key(key: &str) -> String {
+    format!("{}{}", COOLDOWN_KEY_PREFIX, key)
+}
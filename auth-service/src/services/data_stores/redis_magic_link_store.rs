@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{MagicLinkId, MagicLinkStore, MagicLinkStoreError},
+        Email,
+    },
+    utils::auth::MAGIC_LINK_TTL_SECONDS,
+};
+
+pub struct RedisMagicLinkStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    #[tracing::instrument(name = "Add magic link", skip_all)]
+    async fn add_link(&mut self, email: Email, link_id: MagicLinkId) -> Result<(), MagicLinkStoreError> {
+        let ttl: u64 = MAGIC_LINK_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast MAGIC_LINK_TTL_SECONDS to u64")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&email), link_id.as_ref(), ttl)
+            .wrap_err("failed to set magic link in Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Use magic link", skip_all)]
+    async fn use_link(&mut self, email: &Email, link_id: &MagicLinkId) -> Result<(), MagicLinkStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+
+        let stored_id: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get magic link from Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        if stored_id.as_deref() != Some(link_id.as_ref()) {
+            return Err(MagicLinkStoreError::LinkNotFound);
+        }
+
+        // Only the request that actually deleted the key gets to use the link, so two
+        // concurrent requests cannot both redeem it
+        let deleted: u64 = conn
+            .del(&key)
+            .wrap_err("failed to delete magic link from Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        if deleted == 0 {
+            return Err(MagicLinkStoreError::LinkNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+const MAGIC_LINK_KEY_PREFIX: &str = "magic_link:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", MAGIC_LINK_KEY_PREFIX, email.as_ref())
+}
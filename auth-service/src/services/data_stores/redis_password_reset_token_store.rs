@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+        Email,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    #[tracing::instrument(name = "Add password reset token", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let ttl: u64 = PASSWORD_RESET_TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast PASSWORD_RESET_TOKEN_TTL_SECONDS to u64")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&token), email.as_ref(), ttl)
+            .wrap_err("failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Take password reset token", skip_all)]
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        // GETDEL reads and removes the token in one step, so two concurrent
+        // requests cannot both redeem it
+        let email: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(token))
+            .wrap_err("failed to take password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        match email {
+            Some(email) => {
+                Email::parse(email).map_err(PasswordResetTokenStoreError::UnexpectedError)
+            }
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+const PASSWORD_RESET_TOKEN_KEY_PREFIX: &str = "password_reset_token:";
+
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_KEY_PREFIX, token.hash())
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use redis::{Connection, Script};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{RateLimitStore, RateLimitStoreError},
+    RateLimit,
+};
+
+// Refills and takes from the bucket in one step, so that instances sharing the Redis can't
+// both take its last token. Returns the milliseconds until a token is available, 0 if one
+// was taken.
+const TAKE_TOKEN_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local interval_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+local tokens = tonumber(bucket[1]) or capacity
+local updated_at = tonumber(bucket[2]) or now
+
+tokens = math.min(capacity, tokens + math.max(0, now - updated_at) / interval_ms)
+
+local retry_after_ms = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    retry_after_ms = math.ceil((1 - tokens) * interval_ms)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * interval_ms))
+
+return retry_after_ms
+"#;
+
+pub struct RedisRateLimitStore {
+    conn: Arc<RwLock<Connection>>,
+    script: Script,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self {
+            conn,
+            script: Script::new(TAKE_TOKEN_SCRIPT),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    #[tracing::instrument(name = "Take rate limit token", skip_all)]
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+    ) -> Result<Option<u64>, RateLimitStoreError> {
+        let retry_after_ms: u64 = self
+            .script
+            .key(get_key(key))
+            .arg(limit.burst)
+            .arg(limit.refill_interval_ms().max(1))
+            .arg(Utc::now().timestamp_millis())
+            .invoke(&mut *self.conn.write().await)
+            .wrap_err("failed to take rate limit token in Redis")
+            .map_err(RateLimitStoreError::UnexpectedError)?;
+
+        Ok((retry_after_ms > 0).then(|| retry_after_ms.div_ceil(1000)))
+    }
+}
+
+const RATE_LIMIT_KEY_PREFIX: &str = "rate_limit:";
+
+fn get_key(key: &str) -> String {
+    format!("{}{}", RATE_LIMIT_KEY_PREFIX, key)
+}
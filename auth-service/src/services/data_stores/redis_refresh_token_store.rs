@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{
+            RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+            RefreshTokenStoreError,
+        },
+        Authentication, Email,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Add refresh token", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        set_entry(&mut *self.conn.write().await, &token, &record)
+    }
+
+    #[tracing::instrument(name = "Retrieving refresh token from Redis", skip_all)]
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let json = match conn.get::<_, Option<String>>(get_key(token)) {
+            Ok(Some(json)) => json,
+            Ok(None) => return Err(RefreshTokenStoreError::TokenNotFound),
+            Err(e) => {
+                return Err(RefreshTokenStoreError::UnexpectedError(
+                    eyre!(e).wrap_err("failed to get refresh token from Redis"),
+                ))
+            }
+        };
+
+        let entry: RefreshTokenEntry = serde_json::from_str(&json)
+            .wrap_err("failed to deserialize refresh token entry")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let family_id = RefreshTokenFamilyId::parse(entry.family_id)
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let revoked: bool = conn
+            .exists(get_family_key(&family_id))
+            .wrap_err("failed to check if refresh token family is revoked in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(RefreshTokenRecord {
+            email: Email::parse(entry.email).map_err(RefreshTokenStoreError::UnexpectedError)?,
+            family_id,
+            expires_at: entry.expires_at,
+            generation: entry.generation,
+            authentication: Authentication::from_parts(entry.auth_time, &entry.amr),
+            used: entry.used,
+            revoked,
+        })
+    }
+
+    #[tracing::instrument(name = "Mark refresh token used", skip_all)]
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut record = self.get_token(token).await?;
+        record.used = true;
+
+        set_entry(&mut *self.conn.write().await, token, &record)
+    }
+
+    #[tracing::instrument(name = "Revoke refresh token family", skip_all)]
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // Every token in the family expires at most REFRESH_TOKEN_TTL_SECONDS from now,
+        // so the revocation marker does not need to outlive that.
+        let ttl: u64 = REFRESH_TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast REFRESH_TOKEN_TTL_SECONDS to u64")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_family_key(family_id), true, ttl)
+            .wrap_err("failed to revoke refresh token family in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn set_entry(
+    conn: &mut Connection,
+    token: &RefreshToken,
+    record: &RefreshTokenRecord,
+) -> Result<(), RefreshTokenStoreError> {
+    let entry = RefreshTokenEntry {
+        email: record.email.as_ref().to_owned(),
+        family_id: record.family_id.as_ref().to_owned(),
+        expires_at: record.expires_at,
+        generation: record.generation,
+        auth_time: record.authentication.time,
+        amr: record.authentication.method_names(),
+        used: record.used,
+    };
+
+    let json = serde_json::to_string(&entry)
+        .wrap_err("failed to serialize refresh token entry")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+    // Let Redis drop the entry once the token expires
+    let ttl = (record.expires_at - Utc::now().timestamp()).max(1) as u64;
+
+    let _: () = conn
+        .set_ex(get_key(token), json, ttl)
+        .wrap_err("failed to set refresh token in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenEntry {
+    email: String,
+    family_id: String,
+    expires_at: i64,
+    // Entries written before token generations existed belong to generation 0
+    #[serde(default)]
+    generation: u64,
+    // Entries written before logins were recorded have an unknown authentication
+    #[serde(default)]
+    auth_time: i64,
+    #[serde(default)]
+    amr: Vec<String>,
+    used: bool,
+}
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const REVOKED_FAMILY_KEY_PREFIX: &str = "refresh_token_family_revoked:";
+
+fn get_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, token.hash())
+}
+
+fn get_family_key(family_id: &RefreshTokenFamilyId) -> String {
+    format!("{}{}", REVOKED_FAMILY_KEY_PREFIX, family_id.as_ref())
+}
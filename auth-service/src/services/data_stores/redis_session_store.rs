@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{SessionStore, SessionStoreError},
+        Email, RefreshTokenFamilyId, Session,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    #[tracing::instrument(name = "Add session", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        set_entry(&mut conn, &session)?;
+
+        // Lets sessions be listed by user. Ids of expired sessions are dropped when listing.
+        let email_key = get_email_key(&session.email);
+
+        let _: () = conn
+            .sadd(&email_key, session.id.as_ref())
+            .wrap_err("failed to add session to the user's sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&email_key, REFRESH_TOKEN_TTL_SECONDS)
+            .wrap_err("failed to set expiry of the user's sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get session", skip_all)]
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError> {
+        get_entry(&mut *self.conn.write().await, id)?.ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    #[tracing::instrument(name = "List sessions", skip_all)]
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let email_key = get_email_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&email_key)
+            .wrap_err("failed to get the user's sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = vec![];
+
+        for id in ids {
+            let family_id = RefreshTokenFamilyId::parse(id.clone())
+                .map_err(SessionStoreError::UnexpectedError)?;
+
+            match get_entry(&mut conn, &family_id)? {
+                Some(session) => sessions.push(session),
+                None => {
+                    let _: () = conn
+                        .srem(&email_key, &id)
+                        .wrap_err("failed to drop expired session from Redis")
+                        .map_err(SessionStoreError::UnexpectedError)?;
+                }
+            }
+        }
+
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Touch session", skip_all)]
+    async fn touch_session(
+        &mut self,
+        id: &RefreshTokenFamilyId,
+        last_seen_at: i64,
+    ) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_entry(&mut conn, id)?.ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = last_seen_at;
+
+        set_entry(&mut conn, &session)
+    }
+
+    #[tracing::instrument(name = "Remove session", skip_all)]
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &RefreshTokenFamilyId,
+    ) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        match get_entry(&mut conn, id)? {
+            Some(session) if session.email == *email => delete_entry(&mut conn, &session),
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Remove user sessions", skip_all)]
+    async fn remove_user_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let email_key = get_email_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&email_key)
+            .wrap_err("failed to get the user's sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut keys: Vec<String> = ids
+            .iter()
+            .map(|id| format!("{}{}", SESSION_KEY_PREFIX, id))
+            .collect();
+        keys.push(email_key);
+
+        let _: () = conn
+            .del(keys)
+            .wrap_err("failed to delete the user's sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn get_entry(
+    conn: &mut Connection,
+    id: &RefreshTokenFamilyId,
+) -> Result<Option<Session>, SessionStoreError> {
+    let json = match conn.get::<_, Option<String>>(get_key(id)) {
+        Ok(Some(json)) => json,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            return Err(SessionStoreError::UnexpectedError(
+                eyre!(e).wrap_err("failed to get session from Redis"),
+            ))
+        }
+    };
+
+    let entry: SessionEntry = serde_json::from_str(&json)
+        .wrap_err("failed to deserialize session entry")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(Some(Session {
+        id: id.clone(),
+        email: Email::parse(entry.email).map_err(SessionStoreError::UnexpectedError)?,
+        user_agent: entry.user_agent,
+        ip_address: entry.ip_address,
+        created_at: entry.created_at,
+        last_seen_at: entry.last_seen_at,
+    }))
+}
+
+// Sessions expire along with their refresh tokens, which are rotated whenever the session is seen
+fn set_entry(conn: &mut Connection, session: &Session) -> Result<(), SessionStoreError> {
+    let entry = SessionEntry {
+        email: session.email.as_ref().to_owned(),
+        user_agent: session.user_agent.clone(),
+        ip_address: session.ip_address.clone(),
+        created_at: session.created_at,
+        last_seen_at: session.last_seen_at,
+    };
+
+    let json = serde_json::to_string(&entry)
+        .wrap_err("failed to serialize session entry")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .set_ex(get_key(&session.id), json, REFRESH_TOKEN_TTL_SECONDS as u64)
+        .wrap_err("failed to set session in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+fn delete_entry(conn: &mut Connection, session: &Session) -> Result<(), SessionStoreError> {
+    let _: () = conn
+        .del(get_key(&session.id))
+        .wrap_err("failed to delete session from Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .srem(get_email_key(&session.email), session.id.as_ref())
+        .wrap_err("failed to remove session from the user's sessions in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionEntry {
+    email: String,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    created_at: i64,
+    last_seen_at: i64,
+}
+
+const SESSION_KEY_PREFIX: &str = "session:";
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+fn get_key(id: &RefreshTokenFamilyId) -> String {
+    format!("{}{}", SESSION_KEY_PREFIX, id.as_ref())
+}
+
+fn get_email_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_KEY_PREFIX, email.as_ref())
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::Context;
+use jsonwebtoken::Algorithm;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::data_stores::{SigningKeyRecord, SigningKeyStore, SigningKeyStoreError};
+
+pub struct RedisSigningKeyStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSigningKeyStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SigningKeyStore for RedisSigningKeyStore {
+    #[tracing::instrument(name = "Add signing key", skip_all)]
+    async fn add_key(&mut self, record: SigningKeyRecord) -> Result<(), SigningKeyStoreError> {
+        let entry = SigningKeyEntry {
+            algorithm: record.algorithm,
+            encrypted_key: STANDARD.encode(&record.encrypted_key),
+            activates_at: record.activates_at,
+        };
+
+        let json = serde_json::to_string(&entry)
+            .wrap_err("failed to serialize signing key entry")
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .hset(SIGNING_KEYS_KEY, &record.kid, json)
+            .wrap_err("failed to add signing key to Redis")
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving signing keys from Redis", skip_all)]
+    async fn get_keys(&self) -> Result<Vec<SigningKeyRecord>, SigningKeyStoreError> {
+        let entries: Vec<(String, String)> = self
+            .conn
+            .write()
+            .await
+            .hgetall(SIGNING_KEYS_KEY)
+            .wrap_err("failed to get signing keys from Redis")
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        entries
+            .into_iter()
+            .map(|(kid, json)| {
+                let entry: SigningKeyEntry = serde_json::from_str(&json)
+                    .wrap_err("failed to deserialize signing key entry")
+                    .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+                let encrypted_key = STANDARD
+                    .decode(entry.encrypted_key)
+                    .wrap_err("failed to decode signing key")
+                    .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+                Ok(SigningKeyRecord {
+                    kid,
+                    algorithm: entry.algorithm,
+                    encrypted_key,
+                    activates_at: entry.activates_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Remove signing key", skip_all)]
+    async fn remove_key(&mut self, kid: &str) -> Result<(), SigningKeyStoreError> {
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .hdel(SIGNING_KEYS_KEY, kid)
+            .wrap_err("failed to remove signing key from Redis")
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SigningKeyEntry {
+    algorithm: Algorithm,
+    // Base64 encoded
+    encrypted_key: String,
+    activates_at: i64,
+}
+
+// Hash of the signing keys by kid
+const SIGNING_KEYS_KEY: &str = "signing_keys";
@@ -13,6 +13,7 @@
 
 use std::sync::Arc;
 
+use chrono::Utc;
 use color_eyre::eyre::Context;
 use redis::{Commands, Connection};
 // use secrecy::{ExposeSecret, Secret};
@@ -20,7 +21,10 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        MAX_PENDING_LOGIN_ATTEMPTS,
+    },
     Email,
 };
 
@@ -43,94 +47,233 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        // 1. Create a new key using the get_key helper function.
-        // 2. Create a TwoFATuple instance.
-        // 3. Use serde_json::to_string to serialize the TwoFATuple instance into a JSON string. 
-        // Return TwoFACodeStoreError::UnexpectedError if serialization fails.
-        // 4. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL). 
-        // The value should be the serialized 2FA tuple.
-        // The expiration time should be set to TEN_MINUTES_IN_SECONDS.
-        // Return TwoFACodeStoreError::UnexpectedError if casting fails or the call to set_ex fails.
-
-        /*1.*/ 
-        let key = get_key(&email);
-        /*2.*/
-        let two_fa_tuple = TwoFATuple(login_attempt_id.as_ref().to_owned(), code.as_ref().to_owned());
-        /*3.*/
+        let two_fa_tuple = TwoFATuple(email.as_ref().to_owned(), code.as_ref().to_owned());
         let json = serde_json::to_string(&two_fa_tuple)
             .wrap_err("failed to serialize 2FA tuple")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
-        /*4.*/
-        let _: () = self
-        .conn
-        .write()
-        .await
-        .set_ex(&key, json, TEN_MINUTES_IN_SECONDS)
-        .wrap_err("failed to set 2FA code in Redis")
-        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .set_ex(get_key(login_attempt_id.as_ref()), json, TEN_MINUTES_IN_SECONDS)
+            .wrap_err("failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // Indexes the user's login attempts by when they started, to find the oldest ones
+        let email_key = get_email_key(&email);
+
+        let _: () = conn
+            .hset(&email_key, login_attempt_id.as_ref(), Utc::now().timestamp_millis())
+            .wrap_err("failed to add login attempt to the user's login attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&email_key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("failed to set expiry of the user's login attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let login_attempts: Vec<(String, i64)> = conn
+            .hgetall(&email_key)
+            .wrap_err("failed to get the user's login attempts from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // Expired login attempts are dropped from the index along with the oldest pending ones
+        let mut pending = vec![];
+        for (id, started_at) in login_attempts {
+            let exists: bool = conn
+                .exists(get_key(&id))
+                .wrap_err("failed to check 2FA code in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+            if exists {
+                pending.push((started_at, id));
+            } else {
+                delete_login_attempt(&mut conn, &email, &id)?;
+            }
+        }
+
+        pending.sort();
+
+        let excess = pending.len().saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS);
+        for (_, id) in &pending[..excess] {
+            delete_login_attempt(&mut conn, &email, id)?;
+        }
 
         Ok(())
     }
 
     #[tracing::instrument(name = "Remove code", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the del command on the Redis connection to delete the 2FA code entry. 
-        // Return TwoFACodeStoreError::UnexpectedError if the operation fails.
-        let _: () = self
-        .conn
-        .write()
-        .await
-        .del(get_key(email))
-        .wrap_err("failed to delete 2FA code from Redis")
-        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
 
-        Ok(())
+        match get_entry(&mut conn, login_attempt_id)? {
+            Some((email, _)) => delete_login_attempt(&mut conn, &email, login_attempt_id.as_ref()),
+            None => Ok(()),
+        }
     }
 
     #[tracing::instrument(name = "Retrieving 2FA code from Redis", skip_all)]
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the get command on the Redis connection to get the value stored for the key. 
-        // Return TwoFACodeStoreError::LoginAttemptIdNotFound if the operation fails.
-        // If the operation succeeds, call serde_json::from_str to parse the JSON string into a TwoFATuple. 
-        // Then, parse the login attempt ID string and 2FA code string into a LoginAttemptId and TwoFACode type respectively.
-        // Return TwoFACodeStoreError::UnexpectedError if parsing fails.
-
-        let key = get_key(email);
-
-        match self
-        .conn
-        .write()
-        .await
-        .get::<_, String>(&key) {
-            Ok(json) => {
-                let data: TwoFATuple = serde_json::from_str(&json)
-                    .wrap_err("failed to deserialize 2FA tuple")
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
-                let v1 = LoginAttemptId::parse(data.0)
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
-                let v2 = TwoFACode::parse(data.1)
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
-
-                Ok((v1, v2))
-            },
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        get_entry(&mut *self.conn.write().await, login_attempt_id)?
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    #[tracing::instrument(name = "Record failed 2FA attempt", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let key = get_failed_attempts_key(login_attempt_id.as_ref());
+        let mut conn = self.conn.write().await;
+
+        // Counted atomically, so instances sharing the Redis can't grant extra attempts
+        let failed_attempts: u32 = conn
+            .incr(&key, 1)
+            .wrap_err("failed to count failed 2FA attempt in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if failed_attempts == 1 {
+            let _: () = conn
+                .expire(&key, TEN_MINUTES_IN_SECONDS as i64)
+                .wrap_err("failed to set expiry of failed 2FA attempts in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
         }
+
+        if failed_attempts >= max_attempts {
+            if let Some((email, _)) = get_entry(&mut conn, login_attempt_id)? {
+                delete_login_attempt(&mut conn, &email, login_attempt_id.as_ref())?;
+            }
+
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
+        Ok(())
     }
+
+    #[tracing::instrument(name = "Replace 2FA code", skip_all)]
+    async fn replace_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let Some((email, _)) = get_entry(&mut conn, login_attempt_id)? else {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        };
+
+        let resends_key = get_resends_key(login_attempt_id.as_ref());
+
+        let resends: u32 = conn
+            .incr(&resends_key, 1)
+            .wrap_err("failed to count 2FA code resend in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if resends > max_resends {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let two_fa_tuple = TwoFATuple(email.as_ref().to_owned(), code.as_ref().to_owned());
+        let json = serde_json::to_string(&two_fa_tuple)
+            .wrap_err("failed to serialize 2FA tuple")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(get_key(login_attempt_id.as_ref()), json, TEN_MINUTES_IN_SECONDS)
+            .wrap_err("failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // Everything kept about the login attempt lives as long as its new code
+        for key in [
+            resends_key,
+            get_failed_attempts_key(login_attempt_id.as_ref()),
+            get_email_key(&email),
+        ] {
+            let _: () = conn
+                .expire(&key, TEN_MINUTES_IN_SECONDS as i64)
+                .wrap_err("failed to refresh expiry of login attempt in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn get_entry(
+    conn: &mut Connection,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<Option<(Email, TwoFACode)>, TwoFACodeStoreError> {
+    let json = conn
+        .get::<_, Option<String>>(get_key(login_attempt_id.as_ref()))
+        .wrap_err("failed to get 2FA code from Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let Some(json) = json else {
+        return Ok(None);
+    };
+
+    let data: TwoFATuple = serde_json::from_str(&json)
+        .wrap_err("failed to deserialize 2FA tuple")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+    let email = Email::parse(data.0).map_err(TwoFACodeStoreError::UnexpectedError)?;
+    let code = TwoFACode::parse(data.1).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    Ok(Some((email, code)))
 }
 
+fn delete_login_attempt(
+    conn: &mut Connection,
+    email: &Email,
+    login_attempt_id: &str,
+) -> Result<(), TwoFACodeStoreError> {
+    let _: () = conn
+        .del(&[
+            get_key(login_attempt_id),
+            get_failed_attempts_key(login_attempt_id),
+            get_resends_key(login_attempt_id),
+        ])
+        .wrap_err("failed to delete 2FA code from Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .hdel(get_email_key(email), login_attempt_id)
+        .wrap_err("failed to remove login attempt from the user's login attempts in Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+// The email the code was sent to, and the code
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_FAILED_ATTEMPTS_PREFIX: &str = "two_fa_failed_attempts:";
+const TWO_FA_LOGIN_ATTEMPTS_PREFIX: &str = "two_fa_login_attempts:";
+const TWO_FA_RESENDS_PREFIX: &str = "two_fa_resends:";
 
 #[tracing::instrument(name = "Get key", skip_all)]
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
-}
\ No newline at end of file
+fn get_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id)
+}
+
+fn get_failed_attempts_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_FAILED_ATTEMPTS_PREFIX, login_attempt_id)
+}
+
+fn get_resends_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_RESENDS_PREFIX, login_attempt_id)
+}
+
+fn get_email_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_LOGIN_ATTEMPTS_PREFIX, email.as_ref())
+}
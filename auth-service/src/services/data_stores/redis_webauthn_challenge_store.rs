@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{
+        LoginAttemptId, WebauthnCeremony, WebauthnChallengeStore, WebauthnChallengeStoreError,
+    },
+    Email, WebauthnChallenge, WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisWebauthnChallengeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisWebauthnChallengeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebauthnChallengeStore for RedisWebauthnChallengeStore {
+    #[tracing::instrument(name = "Add WebAuthn challenge", skip_all)]
+    async fn add_challenge(
+        &mut self,
+        challenge: WebauthnChallenge,
+        ceremony: WebauthnCeremony,
+    ) -> Result<(), WebauthnChallengeStoreError> {
+        let ttl: u64 = WEBAUTHN_CHALLENGE_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast WEBAUTHN_CHALLENGE_TTL_SECONDS to u64")
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)?;
+
+        let json = serde_json::to_string(&StoredCeremony::from(ceremony))
+            .wrap_err("failed to serialize WebAuthn ceremony")
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&challenge), json, ttl)
+            .wrap_err("failed to set WebAuthn challenge in Redis")
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Take WebAuthn challenge", skip_all)]
+    async fn take_challenge(
+        &mut self,
+        challenge: &WebauthnChallenge,
+    ) -> Result<WebauthnCeremony, WebauthnChallengeStoreError> {
+        // GETDEL, so a challenge can't be answered twice by concurrent requests
+        let json: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(challenge))
+            .wrap_err("failed to take WebAuthn challenge from Redis")
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)?;
+
+        let Some(json) = json else {
+            return Err(WebauthnChallengeStoreError::ChallengeNotFound);
+        };
+
+        let stored: StoredCeremony = serde_json::from_str(&json)
+            .wrap_err("failed to deserialize WebAuthn ceremony")
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)?;
+
+        stored
+            .try_into()
+            .map_err(WebauthnChallengeStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StoredCeremony {
+    Registration {
+        email: String,
+    },
+    Authentication {
+        email: Option<String>,
+        login_attempt_id: Option<String>,
+    },
+}
+
+impl From<WebauthnCeremony> for StoredCeremony {
+    fn from(ceremony: WebauthnCeremony) -> Self {
+        match ceremony {
+            WebauthnCeremony::Registration { email } => Self::Registration {
+                email: email.as_ref().to_owned(),
+            },
+            WebauthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            } => Self::Authentication {
+                email: email.map(|email| email.as_ref().to_owned()),
+                login_attempt_id: login_attempt_id.map(|id| id.as_ref().to_owned()),
+            },
+        }
+    }
+}
+
+impl TryFrom<StoredCeremony> for WebauthnCeremony {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(stored: StoredCeremony) -> Result<Self, Self::Error> {
+        Ok(match stored {
+            StoredCeremony::Registration { email } => Self::Registration {
+                email: Email::parse(email)?,
+            },
+            StoredCeremony::Authentication {
+                email,
+                login_attempt_id,
+            } => Self::Authentication {
+                email: email.map(Email::parse).transpose()?,
+                login_attempt_id: login_attempt_id.map(LoginAttemptId::parse).transpose()?,
+            },
+        })
+    }
+}
+
+const WEBAUTHN_CHALLENGE_KEY_PREFIX: &str = "webauthn_challenge:";
+
+fn get_key(challenge: &WebauthnChallenge) -> String {
+    format!("{}{}", WEBAUTHN_CHALLENGE_KEY_PREFIX, challenge.as_ref())
+}
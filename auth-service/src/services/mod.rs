@@ -1,4 +1,6 @@
 pub mod mock_email_client;
+pub mod smtp_email_client;
 pub mod data_stores;
 
 pub use mock_email_client::*;
+pub use smtp_email_client::*;
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::{authentication::Credentials, PoolConfig},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::domain::{Email, EmailClient};
+
+// How the connection to the SMTP server is secured
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SmtpTls {
+    // Connect in plain text and upgrade with STARTTLS, port 587 by default
+    #[default]
+    StartTls,
+    // TLS from the first byte, port 465 by default
+    Implicit,
+    // No encryption at all, port 25 by default. Only meant for relays on the same host.
+    None,
+}
+
+impl std::str::FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Implicit),
+            "none" => Ok(Self::None),
+            _ => Err(format!("unknown SMTP TLS mode: {}", s)),
+        }
+    }
+}
+
+// Not Debug, so the password can't end up in logs
+#[derive(Clone)]
+pub struct SmtpCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    // Falls back to the usual port of the TLS mode
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub credentials: Option<SmtpCredentials>,
+    // The From address, e.g. "Auth Service <no-reply@example.com>"
+    pub sender: String,
+    // Applies to connecting, and to sending an email as a whole
+    pub timeout: Duration,
+    // Connections kept open and reused between emails
+    pub pool_max_size: u32,
+}
+
+// Sends emails through an SMTP server, over a pool of connections that are reused between
+// emails
+pub struct SmtpEmailClient {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+    timeout: Duration,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: SmtpSettings) -> Result<Self> {
+        let sender = settings
+            .sender
+            .parse()
+            .wrap_err("invalid SMTP sender address")?;
+
+        let builder = match settings.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                .wrap_err("failed to set up STARTTLS for the SMTP server")?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+                .wrap_err("failed to set up TLS for the SMTP server")?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+        };
+
+        let builder = builder
+            .timeout(Some(settings.timeout))
+            .pool_config(PoolConfig::new().max_size(settings.pool_max_size));
+
+        let builder = match settings.port {
+            Some(port) => builder.port(port),
+            None => builder,
+        };
+
+        let builder = match settings.credentials {
+            Some(credentials) => {
+                builder.credentials(Credentials::new(credentials.username, credentials.password))
+            }
+            None => builder,
+        };
+
+        Ok(Self {
+            mailer: builder.build(),
+            sender,
+            timeout: settings.timeout,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<()> {
+        let recipient: Mailbox = recipient
+            .as_ref()
+            .parse()
+            .wrap_err("invalid recipient address")?;
+
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(content.to_owned())
+            .wrap_err("failed to build email")?;
+
+        // lettre only times out connecting, a server that stops responding would hang the
+        // request otherwise. Rejections by the server come back as errors too.
+        tokio::time::timeout(self.timeout, self.mailer.send(message))
+            .await
+            .map_err(|_| eyre!("timed out sending email over SMTP"))?
+            .wrap_err("failed to send email over SMTP")?;
+
+        Ok(())
+    }
+}
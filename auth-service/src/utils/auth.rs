@@ -1,17 +1,375 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
-use serde::{Deserialize, Serialize};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use jsonwebtoken::{decode, decode_header, encode, jwk::JwkSet, Algorithm, Validation};
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
 
 use color_eyre::eyre::{eyre, Result, Context, ContextCompat};
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email, services::data_stores::HashsetBannedTokenStore};
-use super::{constants::JWT_COOKIE_NAME, JWT_SECRET};
+use crate::{
+    app_state::{
+        ApiKeyStoreType, BannedTokenStoreType, RefreshTokenStoreType, ServiceAccountStoreType,
+        SigningKeyStoreType,
+    },
+    domain::{
+        email::Email, AccountLockId, ApiKey, ApiKeyRecord, AuthAPIError, Authentication, AuthorizationGrant, MagicLinkId,
+        PrincipalType, RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, Scope,
+        ServiceAccount, ServiceAccountId, ServiceAccountStoreError, SigningKeyRecord,
+    },
+};
+use super::{
+    constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    encryption::{decrypt_secret, encrypt_secret},
+    signing_key::SigningKey,
+    AUTH_SERVICE_URL, JWT_SECRET, JWT_SIGNING_ALGORITHM, JWT_SIGNING_KEY_FILE, JWT_SIGNING_KEY_ID,
+};
+
+lazy_static! {
+    // Starts out with the configured key, see `load_signing_key`. Keys generated by
+    // `rotate_signing_key` are added at runtime, on other instances by `sync_key_ring`.
+    pub static ref KEY_RING: RwLock<KeyRing> = RwLock::new(KeyRing::new(
+        load_signing_key().expect("Failed to load JWT signing key")
+    ));
+}
+
+// Tokens are accepted up to this long after `exp`, see `Validation::leeway`
+pub const TOKEN_EXP_LEEWAY_SECONDS: i64 = 60;
+
+// Until when a ban of tokens that expire at `exp` has to hold. They are still accepted for
+// TOKEN_EXP_LEEWAY_SECONDS after that, so the ban has to outlast it. Callers pass this to the
+// `BannedTokenStore`, which forgets bans once they expire.
+pub fn ban_expiry(exp: i64) -> i64 {
+    exp + TOKEN_EXP_LEEWAY_SECONDS
+}
+
+// How often every instance loads the signing keys the others generated, see `run_key_ring_sync`
+pub const KEY_RING_SYNC_SECONDS: u64 = 10;
+
+// A rotated key only signs tokens once every instance had a few chances to load it, so no
+// instance is handed a token signed by a key it doesn't know yet
+const SIGNING_KEY_ACTIVATION_DELAY_SECONDS: i64 = 3 * KEY_RING_SYNC_SECONDS as i64;
+
+// Holds the keys tokens are signed and verified with. The key activated last signs new tokens.
+// Keys it replaced are kept for verification only, until every token they signed has expired.
+// Keys that activate later already verify tokens.
+pub struct KeyRing {
+    // Ordered by activation time
+    keys: Vec<RingKey>,
+}
+
+struct RingKey {
+    key: SigningKey,
+    // Unix timestamp from which the key signs new tokens
+    activates_at: i64,
+}
+
+impl KeyRing {
+    // The configured key, it signs tokens until a rotated key activates
+    pub fn new(key: SigningKey) -> Self {
+        Self {
+            keys: vec![RingKey {
+                key,
+                activates_at: 0,
+            }],
+        }
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        self.active_at(Utc::now().timestamp())
+    }
+
+    fn active_at(&self, now: i64) -> &SigningKey {
+        let active = self
+            .keys
+            .iter()
+            .rev()
+            .find(|key| key.activates_at <= now)
+            .unwrap_or(&self.keys[0]);
+
+        &active.key
+    }
+
+    // When the latest key was activated or activates
+    pub fn latest_activation(&self) -> i64 {
+        self.keys.last().map(|key| key.activates_at).unwrap_or_default()
+    }
+
+    pub fn contains(&self, kid: &str) -> bool {
+        self.keys.iter().any(|key| key.key.kid() == kid)
+    }
+
+    // Add a key that signs tokens from `activates_at` on. Keys already in the ring are ignored.
+    pub fn add(&mut self, key: SigningKey, activates_at: i64) {
+        self.add_at(key, activates_at, Utc::now().timestamp());
+    }
+
+    fn add_at(&mut self, key: SigningKey, activates_at: i64, now: i64) {
+        if self.contains(key.kid()) {
+            return;
+        }
+
+        let index = self.keys.partition_point(|key| key.activates_at <= activates_at);
+        self.keys.insert(index, RingKey { key, activates_at });
+
+        // Forget the keys no valid token can be signed with any more
+        let retired_until: Vec<_> = (0..self.keys.len()).map(|i| self.retired_until(i)).collect();
+        let mut retired_until = retired_until.into_iter();
+        self.keys
+            .retain(|_| retired_until.next().flatten().is_none_or(|until| until > now));
+    }
+
+    // Unix timestamp after which no token signed by the key at `index` can still be valid,
+    // `None` while no later key replaces it
+    fn retired_until(&self, index: usize) -> Option<i64> {
+        self.keys
+            .get(index + 1)
+            .map(|next| next.activates_at + SIGNED_TOKEN_MAX_TTL_SECONDS + TOKEN_EXP_LEEWAY_SECONDS)
+    }
+
+    // Keys that may have signed a valid token, or will sign one
+    fn valid_keys(&self, now: i64) -> impl Iterator<Item = &SigningKey> {
+        self.keys
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| self.retired_until(*index).is_none_or(|until| until > now))
+            .map(|(_, key)| &key.key)
+    }
+
+    // Look up a key that may have signed a valid token
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.valid_keys(Utc::now().timestamp())
+            .find(|key| key.kid() == kid)
+    }
+
+    // Public keys of the valid keys, symmetric keys are never published
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .valid_keys(Utc::now().timestamp())
+                .filter_map(|key| key.jwk().cloned())
+                .collect(),
+        }
+    }
+
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let key = self.active();
+
+        encode(&key.header(), claims, key.encoding_key()).wrap_err("failed to create token")
+    }
+
+    // Verify the token against the key named in its `kid` header. Tokens carrying an `aud`
+    // claim are only accepted when that audience is expected, so they can't be used as auth tokens.
+    fn verify<T: DeserializeOwned>(&self, token: &str, audience: Option<&str>) -> Result<T> {
+        let header = decode_header(token).wrap_err("failed to decode token header")?;
+
+        let key = header
+            .kid
+            .as_deref()
+            .and_then(|kid| self.find(kid))
+            .ok_or(eyre!("token was not signed by a known key"))?;
+
+        let mut validation = Validation::new(key.algorithm());
+        validation.leeway = TOKEN_EXP_LEEWAY_SECONDS as u64;
+
+        if let Some(audience) = audience {
+            validation.set_audience(&[audience]);
+            validation.set_required_spec_claims(&["exp", "aud"]);
+        }
+
+        decode::<T>(token, key.decoding_key(), &validation)
+            .map(|data| data.claims)
+            .wrap_err("failed to decode token")
+    }
+}
+
+#[tracing::instrument(name = "Rotate signing key", skip_all)]
+// Generate a signing key of the same algorithm as the active one, and share it with the other
+// instances through the signing key store. Returns its kid and when it starts signing tokens.
+pub async fn rotate_signing_key(signing_key_store: &SigningKeyStoreType) -> Result<(String, i64)> {
+    let algorithm = read_key_ring()?.active().algorithm();
+
+    // Key generation is CPU bound, RSA keys take a while
+    let key = tokio::task::spawn_blocking(move || SigningKey::generate(algorithm))
+        .await
+        .wrap_err("signing key generation panicked")??;
+
+    let kid = key.kid().to_owned();
+    let activates_at = Utc::now().timestamp() + SIGNING_KEY_ACTIVATION_DELAY_SECONDS;
+
+    let record = SigningKeyRecord {
+        kid: kid.clone(),
+        algorithm,
+        encrypted_key: encrypt_secret(key.export())?,
+        activates_at,
+    };
+
+    signing_key_store
+        .write()
+        .await
+        .add_key(record)
+        .await
+        .wrap_err("failed to store signing key")?;
+
+    write_key_ring()?.add(key, activates_at);
+
+    tracing::info!("Rotated JWT signing key, new kid: {}", kid);
+
+    Ok((kid, activates_at))
+}
+
+#[tracing::instrument(name = "Sync key ring", skip_all)]
+// Add the keys other instances generated to the key ring, and remove stored keys that can't have
+// signed a valid token any more
+pub async fn sync_key_ring(signing_key_store: &SigningKeyStoreType) -> Result<()> {
+    let records = signing_key_store
+        .read()
+        .await
+        .get_keys()
+        .await
+        .wrap_err("failed to get signing keys")?;
+
+    let new_records: Vec<_> = {
+        let key_ring = read_key_ring()?;
+        records
+            .iter()
+            .filter(|record| !key_ring.contains(&record.kid))
+            .collect()
+    };
+
+    // Decode outside the lock, so tokens can be verified meanwhile
+    let keys = new_records
+        .into_iter()
+        .map(|record| {
+            let exported = decrypt_secret(&record.encrypted_key)?;
+            let key = SigningKey::import(record.algorithm, record.kid.clone(), &exported)?;
+            Ok((key, record.activates_at))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let expired: Vec<_> = {
+        let mut key_ring = write_key_ring()?;
+
+        for (key, activates_at) in keys {
+            key_ring.add(key, activates_at);
+        }
+
+        records
+            .into_iter()
+            .filter(|record| !key_ring.contains(&record.kid))
+            .map(|record| record.kid)
+            .collect()
+    };
+
+    for kid in expired {
+        signing_key_store
+            .write()
+            .await
+            .remove_key(&kid)
+            .await
+            .wrap_err("failed to remove expired signing key")?;
+    }
+
+    Ok(())
+}
+
+// Load the keys other instances generated every `period`, see `sync_key_ring`
+pub async fn run_key_ring_sync(signing_key_store: SigningKeyStoreType, period: std::time::Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = sync_key_ring(&signing_key_store).await {
+            tracing::error!("Signing key sync failed: {:?}", e);
+        }
+    }
+}
+
+// Rotate the signing key every `period`, for deployments that set JWT_SIGNING_KEY_ROTATION_SECONDS.
+// Every instance runs the schedule, but only rotates if no other instance did so in the period.
+pub async fn run_scheduled_key_rotation(
+    signing_key_store: SigningKeyStoreType,
+    period: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(period);
+
+    // The first tick completes immediately, keep the startup key for a full period
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = sync_key_ring(&signing_key_store).await {
+            tracing::error!("Scheduled signing key rotation failed: {:?}", e);
+            continue;
+        }
+
+        let rotated_at = match read_key_ring() {
+            Ok(key_ring) => key_ring.latest_activation() - SIGNING_KEY_ACTIVATION_DELAY_SECONDS,
+            Err(e) => {
+                tracing::error!("Scheduled signing key rotation failed: {:?}", e);
+                continue;
+            }
+        };
+
+        if rotated_at + period.as_secs() as i64 > Utc::now().timestamp() {
+            continue;
+        }
+
+        if let Err(e) = rotate_signing_key(&signing_key_store).await {
+            tracing::error!("Scheduled signing key rotation failed: {:?}", e);
+        }
+    }
+}
+
+pub fn read_key_ring() -> Result<RwLockReadGuard<'static, KeyRing>> {
+    KEY_RING
+        .read()
+        .map_err(|_| eyre!("signing key ring lock is poisoned"))
+}
+
+fn write_key_ring() -> Result<RwLockWriteGuard<'static, KeyRing>> {
+    KEY_RING
+        .write()
+        .map_err(|_| eyre!("signing key ring lock is poisoned"))
+}
+
+// Use the PEM key from JWT_SIGNING_KEY_FILE when configured, otherwise fall back to signing with JWT_SECRET
+fn load_signing_key() -> Result<SigningKey> {
+    let path = match JWT_SIGNING_KEY_FILE.as_ref() {
+        Some(path) => path,
+        None => {
+            tracing::warn!("JWT_SIGNING_KEY_FILE is not set, signing tokens with JWT_SECRET");
+            return Ok(SigningKey::from_secret(JWT_SECRET.as_bytes()));
+        }
+    };
+
+    let algorithm: Algorithm = JWT_SIGNING_ALGORITHM
+        .parse()
+        .wrap_err(format!("invalid JWT signing algorithm: {}", *JWT_SIGNING_ALGORITHM))?;
+
+    let pem = std::fs::read_to_string(path)
+        .wrap_err(format!("failed to read JWT signing key from {}", path))?;
+
+    SigningKey::from_pem(algorithm, &pem, JWT_SIGNING_KEY_ID.clone())
+}
 
 #[tracing::instrument(name = "Generate auth cookie", skip_all)]
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token for the given session, see `Session`
+pub async fn generate_auth_cookie(
+    email: &Email,
+    session_id: &RefreshTokenFamilyId,
+    authentication: &Authentication,
+    banned_token_store: BannedTokenStoreType,
+) -> Result<Cookie<'static>> {
+    let generation = get_token_generation(email, &banned_token_store).await?;
+    let token = generate_auth_token(email, generation, authentication, Some(session_id))?;
     Ok(create_auth_cookie(token))
 }
 
@@ -27,6 +385,43 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+#[tracing::instrument(name = "Generate refresh cookie", skip_all)]
+// Issue a new refresh token in the given family, persist it and wrap it in a cookie
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    family_id: RefreshTokenFamilyId,
+    authentication: Authentication,
+    banned_token_store: BannedTokenStoreType,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>> {
+    let token = RefreshToken::default();
+    let expires_at = Utc::now().timestamp() + REFRESH_TOKEN_TTL_SECONDS;
+    let generation = get_token_generation(email, &banned_token_store).await?;
+
+    refresh_token_store
+        .write()
+        .await
+        .add_token(
+            token.clone(),
+            RefreshTokenRecord::new(email.clone(), family_id, expires_at, generation, authentication),
+        )
+        .await
+        .wrap_err("failed to store refresh token")?;
+
+    Ok(create_refresh_cookie(token.as_ref().to_owned()))
+}
+
+#[tracing::instrument(name = "Create refresh cookie", skip_all)]
+// Unlike the auth cookie, the refresh cookie has to survive a browser restart
+fn create_refresh_cookie(token: String) -> Cookie<'static> {
+    Cookie::build((REFRESH_TOKEN_COOKIE_NAME, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        .build()
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -36,9 +431,179 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+// This value determines how long a refresh token can be exchanged for a new auth token
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 30; // 30 days
+
+// This value determines how long an emailed password reset link can be used
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 60 * 30; // 30 minutes
+
+// This value determines how long an emailed email verification link can be used
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24; // 24 hours
+
+// Audience of email verification tokens, keeps them apart from auth tokens
+const EMAIL_VERIFICATION_AUDIENCE: &str = "email-verification";
+
+// This value determines how long an emailed magic login link can be used
+pub const MAGIC_LINK_TTL_SECONDS: i64 = 60 * 15; // 15 minutes
+
+// Audience of magic link tokens, keeps them apart from auth and email verification tokens
+const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+
+// This value determines how long an account stays locked after too many failed logins,
+// which is also how long the emailed unlock link can be used
+pub const ACCOUNT_LOCK_SECONDS: i64 = 60 * 15; // 15 minutes
+
+// Audience of account unlock tokens, keeps them apart from the other emailed tokens
+const ACCOUNT_UNLOCK_AUDIENCE: &str = "account-unlock";
+
+// This value determines how long an ID token is accepted by the OAuth client it was issued to
+pub const ID_TOKEN_TTL_SECONDS: i64 = 60 * 10; // 10 minutes
+
+// Longest any token signed by the key ring stays valid, retired keys are kept this long
+const SIGNED_TOKEN_MAX_TTL_SECONDS: i64 = max_seconds(&[
+    TOKEN_TTL_SECONDS,
+    ID_TOKEN_TTL_SECONDS,
+    EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    MAGIC_LINK_TTL_SECONDS,
+    ACCOUNT_LOCK_SECONDS,
+]);
+
+const fn max_seconds(values: &[i64]) -> i64 {
+    let mut max = 0;
+    let mut i = 0;
+
+    while i < values.len() {
+        if values[i] > max {
+            max = values[i];
+        }
+        i += 1;
+    }
+
+    max
+}
+
+#[tracing::instrument(name = "Get token generation", skip_all)]
+// Current token generation of the user, tokens issued in an earlier generation are banned
+pub async fn get_token_generation(
+    email: &Email,
+    banned_token_store: &BannedTokenStoreType,
+) -> Result<u64> {
+    banned_token_store
+        .read()
+        .await
+        .get_token_generation(email)
+        .await
+        .wrap_err("failed to get token generation")
+}
+
 #[tracing::instrument(name = "Generate auth token", skip_all)]
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(
+    email: &Email,
+    generation: u64,
+    authentication: &Authentication,
+    session_id: Option<&RefreshTokenFamilyId>,
+) -> Result<String> {
+    create_token(&Claims {
+        sid: session_id.map(|id| id.as_ref().to_owned()),
+        ..auth_token_claims(email, generation, authentication)?
+    })
+}
+
+#[tracing::instrument(name = "Generate access token", skip_all)]
+// Create the JWT handed to an OAuth client at /token. It is an auth token that also names the
+// client and the scope the user consented to.
+pub async fn generate_access_token(
+    grant: &AuthorizationGrant,
+    banned_token_store: BannedTokenStoreType,
+) -> Result<String> {
+    let generation = get_token_generation(&grant.email, &banned_token_store).await?;
+
+    let claims = Claims {
+        client_id: Some(grant.client_id.as_ref().to_owned()),
+        scope: Some(grant.scope.to_string()).filter(|scope| !scope.is_empty()),
+        ..auth_token_claims(&grant.email, generation, &grant.authentication)?
+    };
+
+    create_token(&claims)
+}
+
+#[tracing::instrument(name = "Generate ID token", skip_all)]
+// Create the OpenID Connect ID token handed to an OAuth client at /token along with the access
+// token. Its audience is the client, so it can't be used as an auth token.
+pub fn generate_id_token(grant: &AuthorizationGrant) -> Result<String> {
+    let iat = Utc::now().timestamp();
+
+    let claims = IdTokenClaims {
+        iss: AUTH_SERVICE_URL.clone(),
+        sub: grant.email.as_ref().to_owned(),
+        aud: grant.client_id.as_ref().to_owned(),
+        exp: iat + ID_TOKEN_TTL_SECONDS,
+        iat,
+        auth_time: grant.authentication.time,
+        nonce: grant.nonce.clone(),
+        amr: grant.authentication.method_names(),
+    };
+
+    read_key_ring()?.sign(&claims)
+}
+
+#[tracing::instrument(name = "Validate ID token", skip_all)]
+// Check an ID token the way the OAuth client it was issued to would
+pub fn validate_id_token(token: &str, client_id: &str) -> Result<IdTokenClaims> {
+    let claims: IdTokenClaims = read_key_ring()?.verify(token, Some(client_id))?;
+
+    if claims.iss != *AUTH_SERVICE_URL {
+        return Err(eyre!("ID token was issued by another issuer"));
+    }
+
+    Ok(claims)
+}
+
+#[tracing::instrument(name = "Generate service account token", skip_all)]
+// Create the JWT handed to a service account at /token. Service accounts have no email or
+// token generation, the token names the account and the granted scope instead.
+pub fn generate_service_account_token(account: &ServiceAccount, scope: &Scope) -> Result<String> {
+    create_token(&Claims {
+        sub: account.id.as_ref().to_owned(),
+        exp: auth_token_expiry()?,
+        iat: Some(Utc::now().timestamp()),
+        jti: Some(Uuid::new_v4().to_string()),
+        sid: None,
+        generation: 0,
+        auth_time: None,
+        amr: vec![],
+        client_id: Some(account.id.as_ref().to_owned()),
+        scope: Some(scope.to_string()).filter(|scope| !scope.is_empty()),
+        principal: PrincipalType::ServiceAccount,
+    })
+}
+
+// Claims of an auth token for the user that expire TOKEN_TTL_SECONDS from now
+fn auth_token_claims(
+    email: &Email,
+    generation: u64,
+    authentication: &Authentication,
+) -> Result<Claims> {
+    let sub = email.as_ref().to_owned();
+
+    Ok(Claims {
+        sub,
+        exp: auth_token_expiry()?,
+        iat: Some(Utc::now().timestamp()),
+        jti: Some(Uuid::new_v4().to_string()),
+        sid: None,
+        generation,
+        auth_time: Some(authentication.time),
+        amr: authentication.method_names(),
+        client_id: None,
+        scope: None,
+        principal: PrincipalType::User,
+    })
+}
+
+// Expiry of an auth token issued now
+pub fn auth_token_expiry() -> Result<usize> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS).wrap_err("failed ot create 10 minutes time delta")?;
 
     // Create JWT expiration time
@@ -48,56 +613,327 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         .timestamp();
 
     // Cast exp to a usize, which is what Claims expects
-    let exp: usize = exp.try_into().wrap_err(format!(
+    exp.try_into().wrap_err(format!(
         "failed to cast exp time to usize. exp time: {}",
         exp
-    ))?;
-
-    let sub = email.as_ref().to_owned();
-
-    let claims = Claims { sub, exp };
-
-    create_token(&claims)
+    ))
 }
 
 #[tracing::instrument(name = "Validate token", skip_all)]
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Check if JWT auth token is valid by verifying it against the signing key named in its header
 pub async fn validate_token(token: &str, banned_token_store: BannedTokenStoreType) -> Result<Claims> {
-    match banned_token_store.read().await.token_exists(token).await {
-        Ok(value) => {
-            if value {
-                return Err(eyre!("token is banned"));
+    let claims: Claims = read_key_ring()?.verify(token, None)?;
+
+    if let Some(jti) = &claims.jti {
+        match banned_token_store.read().await.token_exists(jti).await {
+            Ok(value) => {
+                if value {
+                    return Err(eyre!("token is banned"));
+                }
+            }
+            Err(e) => {
+                return Err(e.into());
             }
         }
-        Err(e) => {
-            return Err(e.into());
+    }
+
+    // Reject tokens of sessions the user ended, see `Session`
+    if let Some(sid) = &claims.sid {
+        if banned_token_store.read().await.session_banned(sid).await? {
+            return Err(eyre!("token belongs to an ended session"));
+        }
+    }
+
+    // Service accounts have no token generation, see `validate_access_token`
+    if claims.principal == PrincipalType::ServiceAccount {
+        return Ok(claims);
+    }
+
+    // Reject tokens issued before the user's tokens were last banned, e.g. by a password reset
+    let email = Email::parse(claims.sub.clone()).wrap_err("token subject is not an email")?;
+
+    if claims.generation < get_token_generation(&email, &banned_token_store).await? {
+        return Err(eyre!("token was issued before the user's tokens were banned"));
+    }
+
+    Ok(claims)
+}
+
+#[tracing::instrument(name = "Ban token", skip_all)]
+// Ban a validated token until it expires. Tokens without a jti can't be banned one by one, they
+// run out within TOKEN_TTL_SECONDS.
+pub async fn ban_token(claims: &Claims, banned_token_store: &BannedTokenStoreType) -> Result<()> {
+    let Some(jti) = &claims.jti else {
+        return Ok(());
+    };
+
+    banned_token_store
+        .write()
+        .await
+        .store_token(jti.clone(), ban_expiry(claims.exp as i64))
+        .await
+        .wrap_err("failed to ban token")
+}
+
+#[tracing::instrument(name = "Validate access token", skip_all)]
+// Like `validate_token`, but also rejects tokens of service accounts that were disabled after
+// the token was issued. For routes that accept tokens of any principal.
+pub async fn validate_access_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    service_account_store: &ServiceAccountStoreType,
+) -> Result<Claims> {
+    let claims = validate_token(token, banned_token_store).await?;
+
+    if claims.principal == PrincipalType::ServiceAccount {
+        let id = ServiceAccountId::parse(claims.sub.clone())?;
+
+        match service_account_store.read().await.get_account(&id).await {
+            Ok(account) if !account.disabled => {}
+            Ok(_) => return Err(eyre!("service account is disabled")),
+            Err(ServiceAccountStoreError::AccountNotFound) => {
+                return Err(eyre!("service account no longer exists"))
+            }
+            Err(e) => return Err(e.into()),
         }
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    Ok(claims)
+}
+
+#[tracing::instrument(name = "Validate API key", skip_all)]
+// Look up a personal API key and record that it was used. Expired keys are rejected.
+pub async fn validate_api_key(key: &str, api_key_store: &ApiKeyStoreType) -> Result<ApiKeyRecord> {
+    let key = ApiKey::parse(key.to_owned())?;
+
+    let record = api_key_store
+        .read()
+        .await
+        .get_key_by_hash(&key.hash())
+        .await
+        .wrap_err("API key not found")?;
+
+    if record.is_expired() {
+        return Err(eyre!("API key has expired"));
+    }
+
+    api_key_store
+        .write()
+        .await
+        .record_use(&record.id, Utc::now().timestamp())
+        .await
+        .wrap_err("failed to record API key use")?;
+
+    Ok(record)
+}
+
+#[tracing::instrument(name = "Get authenticated email", skip_all)]
+// Email of the user logged in with the jwt cookie, for routes that act on the current user
+pub async fn get_authenticated_email(
+    jar: &CookieJar,
+    banned_token_store: &BannedTokenStoreType,
+) -> Result<Email, AuthAPIError> {
+    get_authenticated_claims(jar, banned_token_store)
+        .await
+        .and_then(|claims| Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken))
+}
+
+#[tracing::instrument(name = "Get authenticated claims", skip_all)]
+// Claims of the jwt cookie, for routes that also need to know how the user logged in
+pub async fn get_authenticated_claims(
+    jar: &CookieJar,
+    banned_token_store: &BannedTokenStoreType,
+) -> Result<Claims, AuthAPIError> {
+    let token = jar
+        .get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value()
+        .to_owned();
+
+    let claims = validate_token(&token, banned_token_store.clone())
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Access tokens of OAuth clients and service accounts only grant their scope, they can't
+    // stand in for the user's own login
+    if claims.client_id.is_some() || claims.scope.is_some() {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(claims)
+}
+
+#[tracing::instrument(name = "Generate email verification token", skip_all)]
+// Create the signed token sent in the email verification link
+pub fn generate_email_verification_token(email: &Email) -> Result<String> {
+    let exp = Utc::now().timestamp() + EMAIL_VERIFICATION_TOKEN_TTL_SECONDS;
+
+    let claims = EmailVerificationClaims {
+        sub: email.as_ref().to_owned(),
+        exp: exp.try_into().wrap_err(format!(
+            "failed to cast exp time to usize. exp time: {}",
+            exp
+        ))?,
+        aud: EMAIL_VERIFICATION_AUDIENCE.to_owned(),
+    };
+
+    read_key_ring()?.sign(&claims)
+}
+
+#[tracing::instrument(name = "Validate email verification token", skip_all)]
+// Check the email verification token and return the email address it verifies
+pub fn validate_email_verification_token(token: &str) -> Result<Email> {
+    let claims: EmailVerificationClaims = read_key_ring()?
+        .verify(token, Some(EMAIL_VERIFICATION_AUDIENCE))?;
+
+    Email::parse(claims.sub).wrap_err("token subject is not an email")
+}
+
+#[tracing::instrument(name = "Generate magic link token", skip_all)]
+// Create the signed token sent in the magic login link
+pub fn generate_magic_link_token(email: &Email, link_id: &MagicLinkId) -> Result<String> {
+    let exp = Utc::now().timestamp() + MAGIC_LINK_TTL_SECONDS;
+
+    let claims = MagicLinkClaims {
+        sub: email.as_ref().to_owned(),
+        exp: exp.try_into().wrap_err(format!(
+            "failed to cast exp time to usize. exp time: {}",
+            exp
+        ))?,
+        aud: MAGIC_LINK_AUDIENCE.to_owned(),
+        jti: link_id.as_ref().to_owned(),
+    };
+
+    read_key_ring()?.sign(&claims)
+}
+
+#[tracing::instrument(name = "Validate magic link token", skip_all)]
+// Check the magic link token and return the user it logs in, along with the link it belongs to.
+// The signature only proves the link was issued by us, use `MagicLinkStore::use_link` to make
+// sure it is used once.
+pub fn validate_magic_link_token(token: &str) -> Result<(Email, MagicLinkId)> {
+    let claims: MagicLinkClaims = read_key_ring()?.verify(token, Some(MAGIC_LINK_AUDIENCE))?;
+
+    let email = Email::parse(claims.sub).wrap_err("token subject is not an email")?;
+    let link_id = MagicLinkId::parse(claims.jti)?;
+
+    Ok((email, link_id))
+}
+
+#[tracing::instrument(name = "Generate account unlock token", skip_all)]
+// Create the signed token sent in the link that unlocks a locked account
+pub fn generate_account_unlock_token(email: &Email, lock_id: &AccountLockId) -> Result<String> {
+    let exp = Utc::now().timestamp() + ACCOUNT_LOCK_SECONDS;
+
+    let claims = AccountUnlockClaims {
+        sub: email.as_ref().to_owned(),
+        exp: exp.try_into().wrap_err(format!(
+            "failed to cast exp time to usize. exp time: {}",
+            exp
+        ))?,
+        aud: ACCOUNT_UNLOCK_AUDIENCE.to_owned(),
+        jti: lock_id.as_ref().to_owned(),
+    };
+
+    read_key_ring()?.sign(&claims)
+}
+
+#[tracing::instrument(name = "Validate account unlock token", skip_all)]
+// Check the account unlock token and return the account and the lock it lifts. Whether that
+// lock is still in place is up to `AccountLockoutStore::unlock_account`.
+pub fn validate_account_unlock_token(token: &str) -> Result<(Email, AccountLockId)> {
+    let claims: AccountUnlockClaims =
+        read_key_ring()?.verify(token, Some(ACCOUNT_UNLOCK_AUDIENCE))?;
+
+    let email = Email::parse(claims.sub).wrap_err("token subject is not an email")?;
+    let lock_id = AccountLockId::parse(claims.jti)?;
+
+    Ok((email, lock_id))
 }
 
 #[tracing::instrument(name = "Create token", skip_all)]
-// Create JWT auth token by encoding claims using the JWT secret
+// Create JWT auth token by signing the claims with the active signing key
 fn create_token(claims: &Claims) -> Result<String> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
-    .wrap_err("failed to create token")
+    read_key_ring()?.sign(claims)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    // Missing on tokens issued before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    // Unique id the token is revoked by, see `BannedTokenStore`. Missing on tokens issued before
+    // tokens could be revoked one by one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    // The session the token belongs to, see `Session`. Missing on tokens of service accounts and
+    // OAuth clients, and on tokens issued before sessions were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    // See `BannedTokenStore::ban_user_tokens`
+    #[serde(rename = "gen", default)]
+    pub generation: u64,
+    // When and how the user logged in, see `Authentication`. Missing on tokens issued before
+    // logins were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>,
+    // Set on access tokens issued to OAuth clients, see `generate_access_token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Missing on tokens issued to users, see `generate_service_account_token`
+    #[serde(rename = "pty", default, skip_serializing_if = "PrincipalType::is_user")]
+    pub principal: PrincipalType,
+}
+
+impl Claims {
+    pub fn authentication(&self) -> Option<Authentication> {
+        self.auth_time
+            .map(|time| Authentication::from_parts(time, &self.amr))
+    }
+}
+
+// See OpenID Connect Core section 2
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub auth_time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailVerificationClaims {
+    sub: String,
+    exp: usize,
+    aud: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MagicLinkClaims {
+    sub: String,
+    exp: usize,
+    aud: String,
+    jti: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountUnlockClaims {
+    sub: String,
+    exp: usize,
+    aud: String,
+    jti: String,
 }
 
 #[cfg(test)]
@@ -106,12 +942,28 @@ mod tests {
 
     use tokio::sync::RwLock;
 
+    use jsonwebtoken::DecodingKey;
+
+    use crate::{
+        domain::{
+            AuthMethod, CodeChallenge, OAuthClientId, RedirectUri, RefreshTokenStore,
+            ServiceAccountSecret,
+        },
+        services::data_stores::{
+            HashmapApiKeyStore, HashmapRefreshTokenStore, HashmapServiceAccountStore,
+            HashmapSigningKeyStore, HashsetBannedTokenStore,
+        },
+    };
+
     use super::*;
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let cookie = generate_auth_cookie(&email, &RefreshTokenFamilyId::default(), &Authentication::default(), banned_token_store)
+            .await
+            .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -130,17 +982,50 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_generate_refresh_cookie() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let family_id = RefreshTokenFamilyId::default();
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let cookie = generate_refresh_cookie(
+            &email,
+            family_id.clone(),
+            Authentication::default(),
+            banned_token_store,
+            refresh_token_store.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cookie.name(), REFRESH_TOKEN_COOKIE_NAME);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        );
+
+        let token = RefreshToken::parse(cookie.value().to_owned()).unwrap();
+        let record = refresh_token_store.read().await.get_token(&token).await.unwrap();
+        assert_eq!(record.email, email);
+        assert_eq!(record.family_id, family_id);
+        assert!(!record.used);
+        assert!(!record.is_expired());
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
         let result = validate_token(&token, banned_token_store).await.unwrap();
         assert_eq!(result.sub, "test@example.com");
@@ -153,6 +1038,105 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_banned_by_jti() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store: BannedTokenStoreType =
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+        let other_token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+
+        let claims = validate_token(&token, banned_token_store.clone()).await.unwrap();
+        let other_claims = validate_token(&other_token, banned_token_store.clone()).await.unwrap();
+        assert!(claims.jti.is_some());
+        assert_ne!(claims.jti, other_claims.jti);
+
+        ban_token(&claims, &banned_token_store).await.unwrap();
+
+        assert!(validate_token(&token, banned_token_store.clone()).await.is_err());
+        assert!(validate_token(&other_token, banned_token_store).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ban_token_within_exp_leeway() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store: BannedTokenStoreType =
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        // Expired, but still accepted within the leeway, so it has to stay banned
+        let claims = Claims {
+            exp: (Utc::now().timestamp() - 1) as usize,
+            ..auth_token_claims(&email, 0, &Authentication::default()).unwrap()
+        };
+        let token = create_token(&claims).unwrap();
+        assert!(validate_token(&token, banned_token_store.clone()).await.is_ok());
+
+        ban_token(&claims, &banned_token_store).await.unwrap();
+
+        assert!(validate_token(&token, banned_token_store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_banned_session() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store: BannedTokenStoreType =
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let session_id = RefreshTokenFamilyId::default();
+        let token =
+            generate_auth_token(&email, 0, &Authentication::default(), Some(&session_id)).unwrap();
+        let other_token = generate_auth_token(
+            &email,
+            0,
+            &Authentication::default(),
+            Some(&RefreshTokenFamilyId::default()),
+        )
+        .unwrap();
+
+        let claims = validate_token(&token, banned_token_store.clone()).await.unwrap();
+        assert_eq!(claims.sid.as_deref(), Some(session_id.as_ref()));
+
+        banned_token_store
+            .write()
+            .await
+            .ban_session(session_id.as_ref().to_owned(), claims.exp as i64)
+            .await
+            .unwrap();
+
+        assert!(validate_token(&token, banned_token_store.clone()).await.is_err());
+        assert!(validate_token(&other_token, banned_token_store).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_before_user_tokens_banned() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store: BannedTokenStoreType =
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let old_cookie = generate_auth_cookie(&email, &RefreshTokenFamilyId::default(), &Authentication::default(), banned_token_store.clone())
+            .await
+            .unwrap();
+
+        banned_token_store
+            .write()
+            .await
+            .ban_user_tokens(&email)
+            .await
+            .unwrap();
+
+        let result = validate_token(old_cookie.value(), banned_token_store.clone()).await;
+        assert!(result.is_err());
+
+        // Tokens issued afterwards are unaffected
+        let new_cookie = generate_auth_cookie(&email, &RefreshTokenFamilyId::default(), &Authentication::default(), banned_token_store.clone())
+            .await
+            .unwrap();
+
+        let result = validate_token(new_cookie.value(), banned_token_store).await.unwrap();
+        assert_eq!(result.generation, 1);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
@@ -160,4 +1144,413 @@ mod tests {
         let result = validate_token(&token, banned_token_store).await;
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_generate_auth_token_names_signing_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+        let header = decode_header(&token).unwrap();
+        let key_ring = read_key_ring().unwrap();
+        let key = key_ring.find(&header.kid.unwrap()).unwrap();
+        assert_eq!(header.alg, key.algorithm());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_unknown_kid() {
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            exp: (Utc::now().timestamp() + TOKEN_TTL_SECONDS) as usize,
+            iat: None,
+            jti: None,
+            sid: None,
+            generation: 0,
+            auth_time: None,
+            amr: vec![],
+            client_id: None,
+            scope: None,
+            principal: PrincipalType::User,
+        };
+        let other_key = SigningKey::from_secret(b"another secret");
+        let token = encode(&other_key.header(), &claims, other_key.encoding_key()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    fn test_claims() -> Claims {
+        Claims {
+            sub: "test@example.com".to_owned(),
+            exp: (Utc::now().timestamp() + TOKEN_TTL_SECONDS) as usize,
+            iat: None,
+            jti: None,
+            sid: None,
+            generation: 0,
+            auth_time: None,
+            amr: vec![],
+            client_id: None,
+            scope: None,
+            principal: PrincipalType::User,
+        }
+    }
+
+    #[test]
+    fn test_key_ring_keeps_validating_tokens_signed_by_retired_key() {
+        let first_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let first_kid = first_key.kid().to_owned();
+        let mut key_ring = KeyRing::new(first_key);
+
+        let old_token = key_ring.sign(&test_claims()).unwrap();
+
+        let second_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let second_kid = second_key.kid().to_owned();
+        key_ring.add(second_key, Utc::now().timestamp());
+
+        // Tokens signed before the rotation are still valid
+        assert_eq!(key_ring.verify::<Claims>(&old_token, None).unwrap().sub, "test@example.com");
+
+        // New tokens are signed with the new key
+        let new_token = key_ring.sign(&test_claims()).unwrap();
+        assert_eq!(decode_header(&new_token).unwrap().kid, Some(second_kid.clone()));
+        assert!(key_ring.verify::<Claims>(&new_token, None).is_ok());
+
+        // Both keys are published until the old one is no longer needed
+        let jwks = key_ring.jwks();
+        assert_eq!(jwks.keys.len(), 2);
+        assert!(jwks.find(&first_kid).is_some());
+        assert!(jwks.find(&second_kid).is_some());
+    }
+
+    #[test]
+    fn test_key_ring_rejects_expired_tokens_signed_by_retired_key() {
+        let mut key_ring = KeyRing::new(SigningKey::generate(Algorithm::EdDSA).unwrap());
+
+        let expired_token = key_ring
+            .sign(&Claims {
+                sub: "test@example.com".to_owned(),
+                exp: (Utc::now().timestamp() - TOKEN_EXP_LEEWAY_SECONDS - 1) as usize,
+                iat: None,
+                jti: None,
+                sid: None,
+                generation: 0,
+                auth_time: None,
+                amr: vec![],
+                client_id: None,
+                scope: None,
+                principal: PrincipalType::User,
+            })
+            .unwrap();
+
+        key_ring.add(SigningKey::generate(Algorithm::EdDSA).unwrap(), Utc::now().timestamp());
+
+        assert!(key_ring.verify::<Claims>(&expired_token, None).is_err());
+    }
+
+    #[test]
+    fn test_key_ring_forgets_retired_key_once_its_tokens_expired() {
+        let first_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let first_kid = first_key.kid().to_owned();
+        let mut key_ring = KeyRing::new(first_key);
+
+        let old_token = key_ring.sign(&test_claims()).unwrap();
+
+        // Pretend the rotation happened longer ago than any token can live
+        let rotated_at =
+            Utc::now().timestamp() - SIGNED_TOKEN_MAX_TTL_SECONDS - TOKEN_EXP_LEEWAY_SECONDS - 1;
+        key_ring.add_at(
+            SigningKey::generate(Algorithm::ES256).unwrap(),
+            rotated_at,
+            rotated_at,
+        );
+
+        assert!(key_ring.find(&first_kid).is_none());
+        assert!(key_ring.verify::<Claims>(&old_token, None).is_err());
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+
+        // The expired key is dropped when the next key is added
+        key_ring.add(SigningKey::generate(Algorithm::ES256).unwrap(), Utc::now().timestamp());
+        assert_eq!(key_ring.keys.len(), 2);
+    }
+
+    #[test]
+    fn test_retired_keys_are_kept_for_the_longest_signed_token_ttl() {
+        let ttls = [
+            TOKEN_TTL_SECONDS,
+            ID_TOKEN_TTL_SECONDS,
+            EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            MAGIC_LINK_TTL_SECONDS,
+            ACCOUNT_LOCK_SECONDS,
+        ];
+
+        assert_eq!(Some(SIGNED_TOKEN_MAX_TTL_SECONDS), ttls.into_iter().max());
+    }
+
+    #[test]
+    fn test_key_ring_verifies_with_key_before_it_activates() {
+        let first_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let first_kid = first_key.kid().to_owned();
+        let mut key_ring = KeyRing::new(first_key);
+
+        let now = Utc::now().timestamp();
+        let next_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let next_kid = next_key.kid().to_owned();
+        let token = encode(&next_key.header(), &test_claims(), next_key.encoding_key()).unwrap();
+        key_ring.add(next_key, now + 60);
+
+        // Tokens keep being signed with the current key, but the next one is already trusted
+        let current_token = key_ring.sign(&test_claims()).unwrap();
+        assert_eq!(decode_header(&current_token).unwrap().kid, Some(first_kid.clone()));
+        assert!(key_ring.verify::<Claims>(&token, None).is_ok());
+        assert!(key_ring.jwks().find(&next_kid).is_some());
+
+        // Once activated, it signs new tokens and the first key is retired
+        assert_eq!(key_ring.active_at(now + 60).kid(), next_kid);
+        assert_eq!(key_ring.active_at(now + 59).kid(), first_kid);
+    }
+
+    #[test]
+    fn test_key_ring_rejects_token_with_unknown_or_missing_kid() {
+        let key_ring = KeyRing::new(SigningKey::generate(Algorithm::ES256).unwrap());
+
+        let other_key = SigningKey::generate(Algorithm::ES256).unwrap();
+        let token = encode(&other_key.header(), &test_claims(), other_key.encoding_key()).unwrap();
+        assert!(key_ring.verify::<Claims>(&token, None).is_err());
+
+        // A token signed by the right key but without a kid is rejected as well
+        let mut header = key_ring.active().header();
+        header.kid = None;
+        let token = encode(&header, &test_claims(), key_ring.active().encoding_key()).unwrap();
+        assert!(key_ring.verify::<Claims>(&token, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_signing_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let signing_key_store: SigningKeyStoreType =
+            Arc::new(RwLock::new(HashmapSigningKeyStore::default()));
+
+        let old_token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+
+        let (new_kid, activates_at) = rotate_signing_key(&signing_key_store).await.unwrap();
+        assert!(activates_at > Utc::now().timestamp());
+
+        // The new key is trusted at once, but only signs tokens once it activates
+        assert!(read_key_ring().unwrap().find(&new_kid).is_some());
+        let token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid, decode_header(&old_token).unwrap().kid);
+        assert!(validate_token(&old_token, banned_token_store.clone()).await.is_ok());
+
+        // Other instances load the key from the store, tokens they sign with it verify here
+        let records = signing_key_store.read().await.get_keys().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].kid, new_kid);
+
+        let exported = decrypt_secret(&records[0].encrypted_key).unwrap();
+        let new_key =
+            SigningKey::import(records[0].algorithm, records[0].kid.clone(), &exported).unwrap();
+        let new_token = encode(
+            &new_key.header(),
+            &auth_token_claims(&email, 0, &Authentication::default()).unwrap(),
+            new_key.encoding_key(),
+        )
+        .unwrap();
+        assert!(validate_token(&new_token, banned_token_store).await.is_ok());
+
+        // Downstream services can verify the new token using only the published key set
+        let jwk = read_key_ring().unwrap().jwks().find(&new_kid).cloned();
+        if let Some(jwk) = jwk {
+            let decoding_key = DecodingKey::from_jwk(&jwk).unwrap();
+            let validation = Validation::new(decode_header(&new_token).unwrap().alg);
+            assert!(decode::<Claims>(&new_token, &decoding_key, &validation).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_key_ring_loads_keys_of_other_instances() {
+        let signing_key_store: SigningKeyStoreType =
+            Arc::new(RwLock::new(HashmapSigningKeyStore::default()));
+
+        // A key another instance rotated to, it activates later so the tests keep their key
+        let algorithm = read_key_ring().unwrap().active().algorithm();
+        let key = SigningKey::generate(algorithm).unwrap();
+        signing_key_store
+            .write()
+            .await
+            .add_key(SigningKeyRecord {
+                kid: key.kid().to_owned(),
+                algorithm,
+                encrypted_key: encrypt_secret(key.export()).unwrap(),
+                activates_at: Utc::now().timestamp() + 600,
+            })
+            .await
+            .unwrap();
+
+        assert!(!read_key_ring().unwrap().contains(key.kid()));
+
+        sync_key_ring(&signing_key_store).await.unwrap();
+
+        assert!(read_key_ring().unwrap().find(key.kid()).is_some());
+        assert_eq!(signing_key_store.read().await.get_keys().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_email_verification_token_is_not_an_auth_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let verification_token = generate_email_verification_token(&email).unwrap();
+        assert_eq!(validate_email_verification_token(&verification_token).unwrap(), email);
+        assert!(validate_token(&verification_token, banned_token_store.clone()).await.is_err());
+
+        let auth_token = generate_auth_token(&email, 0, &Authentication::default(), None).unwrap();
+        assert!(validate_email_verification_token(&auth_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_magic_link_token_is_not_an_auth_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let link_id = MagicLinkId::default();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let magic_link_token = generate_magic_link_token(&email, &link_id).unwrap();
+        assert_eq!(
+            validate_magic_link_token(&magic_link_token).unwrap(),
+            (email.clone(), link_id)
+        );
+        assert!(validate_token(&magic_link_token, banned_token_store.clone()).await.is_err());
+        assert!(validate_email_verification_token(&magic_link_token).is_err());
+
+        let verification_token = generate_email_verification_token(&email).unwrap();
+        assert!(validate_magic_link_token(&verification_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_account_unlock_token_is_not_an_auth_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let lock_id = AccountLockId::default();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let unlock_token = generate_account_unlock_token(&email, &lock_id).unwrap();
+        assert_eq!(
+            validate_account_unlock_token(&unlock_token).unwrap(),
+            (email.clone(), lock_id.clone())
+        );
+        assert!(validate_token(&unlock_token, banned_token_store).await.is_err());
+        assert!(validate_magic_link_token(&unlock_token).is_err());
+
+        let magic_link_token = generate_magic_link_token(&email, &MagicLinkId::default()).unwrap();
+        assert!(validate_account_unlock_token(&magic_link_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_records_authentication() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let authentication = Authentication::now(&[AuthMethod::Password, AuthMethod::MultiFactor]);
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let token = generate_auth_token(&email, 0, &authentication, None).unwrap();
+        let claims = validate_token(&token, banned_token_store).await.unwrap();
+
+        assert_eq!(claims.amr, vec!["pwd", "mfa"]);
+        assert_eq!(claims.authentication(), Some(authentication));
+    }
+
+    #[tokio::test]
+    async fn test_id_token_is_not_an_auth_token() {
+        let grant = AuthorizationGrant {
+            client_id: OAuthClientId::default(),
+            redirect_uri: RedirectUri::parse("https://app.example.com/callback".to_owned())
+                .unwrap(),
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+            scope: Scope::parse("openid").unwrap(),
+            code_challenge: CodeChallenge::from_verifier(
+                "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            ),
+            nonce: Some("n-0S6_WzA2Mj".to_owned()),
+            authentication: Authentication::now(&[AuthMethod::Password]),
+        };
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let id_token = generate_id_token(&grant).unwrap();
+
+        let claims = validate_id_token(&id_token, grant.client_id.as_ref()).unwrap();
+        assert_eq!(claims.iss, *AUTH_SERVICE_URL);
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+        assert_eq!(claims.auth_time, grant.authentication.time);
+        assert_eq!(claims.amr, vec!["pwd"]);
+
+        // Only the client it was issued to accepts it
+        assert!(validate_id_token(&id_token, OAuthClientId::default().as_ref()).is_err());
+        assert!(validate_token(&id_token, banned_token_store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_access_token_rejects_disabled_service_account() {
+        let secret = ServiceAccountSecret::default();
+        let scope = Scope::parse("reports:read").unwrap();
+        let account = ServiceAccount::new("Job".to_owned(), scope, &secret);
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let service_account_store: ServiceAccountStoreType =
+            Arc::new(RwLock::new(HashmapServiceAccountStore::default()));
+        service_account_store
+            .write()
+            .await
+            .add_account(account.clone())
+            .await
+            .unwrap();
+
+        let token = generate_service_account_token(&account, &account.scope).unwrap();
+
+        let claims =
+            validate_access_token(&token, banned_token_store.clone(), &service_account_store)
+                .await
+                .unwrap();
+        assert_eq!(claims.principal, PrincipalType::ServiceAccount);
+        assert_eq!(claims.sub, account.id.as_ref());
+        assert_eq!(claims.scope.as_deref(), Some("reports:read"));
+
+        service_account_store
+            .write()
+            .await
+            .set_disabled(&account.id, true)
+            .await
+            .unwrap();
+
+        assert!(validate_token(&token, banned_token_store.clone()).await.is_ok());
+        assert!(
+            validate_access_token(&token, banned_token_store, &service_account_store)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_api_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let api_key_store: ApiKeyStoreType = Arc::new(RwLock::new(HashmapApiKeyStore::default()));
+
+        let key = ApiKey::default();
+        let record = ApiKeyRecord::new(&key, email.clone(), "CI".to_owned(), Scope::default(), None);
+        api_key_store.write().await.add_key(record).await.unwrap();
+
+        let validated = validate_api_key(key.as_ref(), &api_key_store).await.unwrap();
+        assert_eq!(validated.email, email);
+
+        let stored = api_key_store.read().await.list_keys(&email).await.unwrap();
+        assert!(stored[0].last_used_at.is_some());
+
+        let expired_key = ApiKey::default();
+        let expired = ApiKeyRecord::new(
+            &expired_key,
+            email,
+            "Old".to_owned(),
+            Scope::default(),
+            Some(Utc::now().timestamp() - 1),
+        );
+        api_key_store.write().await.add_key(expired).await.unwrap();
+
+        assert!(validate_api_key(expired_key.as_ref(), &api_key_store).await.is_err());
+        assert!(validate_api_key(ApiKey::default().as_ref(), &api_key_store).await.is_err());
+    }
+}
@@ -1,12 +1,34 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
-use std::env as std_env;
+use std::{env as std_env, net::IpAddr};
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
     pub static ref DATABASE_URL: String = set_db_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref JWT_SIGNING_KEY_FILE: Option<String> = set_optional(env::JWT_SIGNING_KEY_FILE_ENV_VAR);
+    pub static ref JWT_SIGNING_ALGORITHM: String = set_signing_algorithm();
+    pub static ref JWT_SIGNING_KEY_ID: Option<String> = set_optional(env::JWT_SIGNING_KEY_ID_ENV_VAR);
+    pub static ref JWT_SIGNING_KEY_ROTATION_SECONDS: Option<u64> = set_key_rotation_seconds();
+    pub static ref ADMIN_API_TOKEN: Option<String> = set_optional(env::ADMIN_API_TOKEN_ENV_VAR);
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
+    pub static ref UNVERIFIED_LOGIN_POLICY: String = set_unverified_login_policy();
+    pub static ref TOTP_ENCRYPTION_KEY: Option<String> = set_optional(env::TOTP_ENCRYPTION_KEY_ENV_VAR);
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref MAX_SESSIONS_PER_USER: Option<usize> = set_max_sessions_per_user();
+    pub static ref SESSION_LIMIT_POLICY: String = set_session_limit_policy();
+    pub static ref TRUSTED_PROXIES: Vec<IpAddr> = set_trusted_proxies();
+    pub static ref MAX_TWO_FA_ATTEMPTS: u32 = set_max_two_fa_attempts();
+    pub static ref SMTP_HOST: Option<String> = set_optional(env::SMTP_HOST_ENV_VAR);
+    pub static ref SMTP_PORT: Option<u16> = set_smtp_port();
+    pub static ref SMTP_TLS: String = set_smtp_tls();
+    pub static ref SMTP_USERNAME: Option<String> = set_optional(env::SMTP_USERNAME_ENV_VAR);
+    pub static ref SMTP_PASSWORD: Option<String> = set_optional(env::SMTP_PASSWORD_ENV_VAR);
+    pub static ref SMTP_SENDER: Option<String> = set_optional(env::SMTP_SENDER_ENV_VAR);
+    pub static ref SMTP_TIMEOUT_SECONDS: u64 = set_smtp_timeout_seconds();
+    pub static ref SMTP_POOL_MAX_SIZE: u32 = set_smtp_pool_max_size();
 }
 
 fn set_db_url() -> String {
@@ -38,14 +60,182 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_signing_algorithm() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_SIGNING_ALGORITHM_ENV_VAR)
+        .unwrap_or(DEFAULT_JWT_SIGNING_ALGORITHM.to_owned())
+}
+
+// Public base URL of the auth service, used to build links in emails. It is also the issuer
+// of OpenID Connect ID tokens.
+fn set_auth_service_url() -> String {
+    set_optional(env::AUTH_SERVICE_URL_ENV_VAR)
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .unwrap_or(DEFAULT_AUTH_SERVICE_URL.to_owned())
+}
+
+// Either "block" or "warn", see `UnverifiedLoginPolicy`
+fn set_unverified_login_policy() -> String {
+    set_optional(env::UNVERIFIED_LOGIN_POLICY_ENV_VAR)
+        .unwrap_or(DEFAULT_UNVERIFIED_LOGIN_POLICY.to_owned())
+}
+
+// The page WebAuthn ceremonies run on, the auth service's own UI unless configured otherwise
+fn set_webauthn_origin() -> String {
+    set_optional(env::WEBAUTHN_ORIGIN_ENV_VAR)
+        .map(|origin| origin.trim_end_matches('/').to_owned())
+        .unwrap_or(AUTH_SERVICE_URL.clone())
+}
+
+// Passkeys are scoped to this domain, which defaults to the host of WEBAUTHN_ORIGIN
+fn set_webauthn_rp_id() -> String {
+    set_optional(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or_else(|| {
+        let host = WEBAUTHN_ORIGIN
+            .split("://")
+            .last()
+            .and_then(|authority| authority.split([':', '/']).next())
+            .unwrap_or_default();
+
+        host.to_owned()
+    })
+}
+
+fn set_key_rotation_seconds() -> Option<u64> {
+    set_optional(env::JWT_SIGNING_KEY_ROTATION_SECONDS_ENV_VAR).map(|value| {
+        value
+            .parse()
+            .expect("JWT_SIGNING_KEY_ROTATION_SECONDS must be a number of seconds.")
+    })
+}
+
+fn set_max_sessions_per_user() -> Option<usize> {
+    set_optional(env::MAX_SESSIONS_PER_USER_ENV_VAR).map(|value| {
+        value
+            .parse()
+            .ok()
+            .filter(|max_sessions| *max_sessions > 0)
+            .expect("MAX_SESSIONS_PER_USER must be a positive number.")
+    })
+}
+
+// Either "reject" or "evict_oldest", see `SessionLimitPolicy`
+fn set_session_limit_policy() -> String {
+    set_optional(env::SESSION_LIMIT_POLICY_ENV_VAR)
+        .unwrap_or(DEFAULT_SESSION_LIMIT_POLICY.to_owned())
+}
+
+// Comma separated IP addresses of the reverse proxies in front of the service, see
+// `TrustedProxies`
+fn set_trusted_proxies() -> Vec<IpAddr> {
+    set_optional(env::TRUSTED_PROXIES_ENV_VAR)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|ip| {
+                    ip.trim()
+                        .parse()
+                        .expect("TRUSTED_PROXIES must be a comma separated list of IP addresses.")
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Wrong 2FA codes allowed per login attempt, see `TwoFACodeStore::record_failed_attempt`
+fn set_max_two_fa_attempts() -> u32 {
+    set_optional(env::MAX_TWO_FA_ATTEMPTS_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .ok()
+                .filter(|max_attempts| *max_attempts > 0)
+                .expect("MAX_TWO_FA_ATTEMPTS must be a positive number.")
+        })
+        .unwrap_or(DEFAULT_MAX_TWO_FA_ATTEMPTS)
+}
+
+fn set_smtp_port() -> Option<u16> {
+    set_optional(env::SMTP_PORT_ENV_VAR)
+        .map(|value| value.parse().expect("SMTP_PORT must be a port number."))
+}
+
+fn set_smtp_tls() -> String {
+    set_optional(env::SMTP_TLS_ENV_VAR).unwrap_or(DEFAULT_SMTP_TLS.to_owned())
+}
+
+fn set_smtp_timeout_seconds() -> u64 {
+    set_optional(env::SMTP_TIMEOUT_SECONDS_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .ok()
+                .filter(|seconds| *seconds > 0)
+                .expect("SMTP_TIMEOUT_SECONDS must be a positive number.")
+        })
+        .unwrap_or(DEFAULT_SMTP_TIMEOUT_SECONDS)
+}
+
+fn set_smtp_pool_max_size() -> u32 {
+    set_optional(env::SMTP_POOL_MAX_SIZE_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .ok()
+                .filter(|max_size| *max_size > 0)
+                .expect("SMTP_POOL_MAX_SIZE must be a positive number.")
+        })
+        .unwrap_or(DEFAULT_SMTP_POOL_MAX_SIZE)
+}
+
+// Unset and empty variables are both treated as "not configured"
+fn set_optional(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const JWT_SIGNING_KEY_FILE_ENV_VAR: &str = "JWT_SIGNING_KEY_FILE";
+    pub const JWT_SIGNING_ALGORITHM_ENV_VAR: &str = "JWT_SIGNING_ALGORITHM";
+    pub const JWT_SIGNING_KEY_ID_ENV_VAR: &str = "JWT_SIGNING_KEY_ID";
+    pub const JWT_SIGNING_KEY_ROTATION_SECONDS_ENV_VAR: &str = "JWT_SIGNING_KEY_ROTATION_SECONDS";
+    pub const ADMIN_API_TOKEN_ENV_VAR: &str = "ADMIN_API_TOKEN";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const UNVERIFIED_LOGIN_POLICY_ENV_VAR: &str = "UNVERIFIED_LOGIN_POLICY";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const MAX_SESSIONS_PER_USER_ENV_VAR: &str = "MAX_SESSIONS_PER_USER";
+    pub const SESSION_LIMIT_POLICY_ENV_VAR: &str = "SESSION_LIMIT_POLICY";
+    pub const TRUSTED_PROXIES_ENV_VAR: &str = "TRUSTED_PROXIES";
+    pub const MAX_TWO_FA_ATTEMPTS_ENV_VAR: &str = "MAX_TWO_FA_ATTEMPTS";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_SENDER_ENV_VAR: &str = "SMTP_SENDER";
+    pub const SMTP_TIMEOUT_SECONDS_ENV_VAR: &str = "SMTP_TIMEOUT_SECONDS";
+    pub const SMTP_POOL_MAX_SIZE_ENV_VAR: &str = "SMTP_POOL_MAX_SIZE";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_JWT_SIGNING_ALGORITHM: &str = "RS256";
+pub const DEFAULT_AUTH_SERVICE_URL: &str = "http://localhost:3000";
+pub const DEFAULT_UNVERIFIED_LOGIN_POLICY: &str = "warn";
+pub const DEFAULT_SESSION_LIMIT_POLICY: &str = "reject";
+pub const DEFAULT_MAX_TWO_FA_ATTEMPTS: u32 = 5;
+pub const DEFAULT_SMTP_TLS: &str = "starttls";
+pub const DEFAULT_SMTP_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_SMTP_POOL_MAX_SIZE: u32 = 10;
+// Shown next to the account name in authenticator apps
+pub const TOTP_ISSUER: &str = "Auth Service";
+// Shown to users when they create or use a passkey
+pub const WEBAUTHN_RP_NAME: &str = "Auth Service";
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
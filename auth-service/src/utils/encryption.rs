@@ -0,0 +1,86 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+
+use super::{JWT_SECRET, TOTP_ENCRYPTION_KEY};
+
+lazy_static! {
+    static ref ENCRYPTION_KEY: Key<Aes256Gcm> =
+        load_encryption_key().expect("Failed to load TOTP encryption key");
+}
+
+// AES-GCM uses 96 bit nonces
+const NONCE_LENGTH: usize = 12;
+
+// Encrypt a secret for storage, e.g. a TOTP secret. The random nonce is prepended to the ciphertext.
+pub fn encrypt_secret(secret: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = Aes256Gcm::new(&ENCRYPTION_KEY)
+        .encrypt(&nonce, secret)
+        .map_err(|_| eyre!("failed to encrypt secret"))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn decrypt_secret(encrypted: &[u8]) -> Result<Vec<u8>> {
+    if encrypted.len() < NONCE_LENGTH {
+        return Err(eyre!("encrypted secret is too short"));
+    }
+
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+
+    Aes256Gcm::new(&ENCRYPTION_KEY)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| eyre!("failed to decrypt secret"))
+}
+
+// Use the base64 encoded 256 bit key from TOTP_ENCRYPTION_KEY when configured,
+// otherwise fall back to a key derived from JWT_SECRET
+fn load_encryption_key() -> Result<Key<Aes256Gcm>> {
+    let key = match TOTP_ENCRYPTION_KEY.as_ref() {
+        Some(key) => STANDARD
+            .decode(key)
+            .wrap_err("TOTP_ENCRYPTION_KEY is not valid base64")?,
+        None => {
+            tracing::warn!("TOTP_ENCRYPTION_KEY is not set, deriving the key from JWT_SECRET");
+            Sha256::digest(JWT_SECRET.as_bytes()).to_vec()
+        }
+    };
+
+    if key.len() != 32 {
+        return Err(eyre!("TOTP_ENCRYPTION_KEY must be 32 bytes long"));
+    }
+
+    Ok(*Key::<Aes256Gcm>::from_slice(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt_secret() {
+        let encrypted = encrypt_secret(b"secret").unwrap();
+        assert_ne!(&encrypted[NONCE_LENGTH..], b"secret");
+        assert_eq!(decrypt_secret(&encrypted).unwrap(), b"secret");
+
+        // Every encryption uses a fresh nonce
+        assert_ne!(encrypt_secret(b"secret").unwrap(), encrypted);
+    }
+
+    #[test]
+    fn test_decrypt_rejects_tampered_secret() {
+        let mut encrypted = encrypt_secret(b"secret").unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert!(decrypt_secret(&encrypted).is_err());
+        assert!(decrypt_secret(&encrypted[..4]).is_err());
+    }
+}
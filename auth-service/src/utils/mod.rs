@@ -1,7 +1,15 @@
 pub mod constants;
 pub mod auth;
 pub mod tracing;
+pub mod signing_key;
+pub mod encryption;
+pub mod qr_code;
+pub mod rate_limit;
 
 pub use constants::*;
 pub use auth::*;
 pub use tracing::*;
+pub use signing_key::*;
+pub use encryption::*;
+pub use qr_code::*;
+pub use rate_limit::*;
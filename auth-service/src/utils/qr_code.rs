@@ -0,0 +1,33 @@
+use std::io::Cursor;
+
+use color_eyre::eyre::{Context, Result};
+use image::{ImageFormat, Luma};
+use qrcode::{render::svg, QrCode};
+
+// Large enough to scan from a screen without zooming
+const QR_CODE_MIN_SIZE: u32 = 200;
+
+pub fn qr_code_png(data: &str) -> Result<Vec<u8>> {
+    let image = QrCode::new(data)
+        .wrap_err("failed to create QR code")?
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_CODE_MIN_SIZE, QR_CODE_MIN_SIZE)
+        .build();
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .wrap_err("failed to encode QR code as PNG")?;
+
+    Ok(png)
+}
+
+pub fn qr_code_svg(data: &str) -> Result<String> {
+    let svg = QrCode::new(data)
+        .wrap_err("failed to create QR code")?
+        .render::<svg::Color>()
+        .min_dimensions(QR_CODE_MIN_SIZE, QR_CODE_MIN_SIZE)
+        .build();
+
+    Ok(svg)
+}
@@ -0,0 +1,70 @@
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{client_ip, AuthAPIError, RateLimit},
+};
+
+// Far more than any request to a throttled route needs, the body is buffered to find its email
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct TargetEmail {
+    email: Option<String>,
+}
+
+// Throttles the routes it is layered on, with a token bucket per route and client IP and one
+// per route and target email address. The latter slows down attacks on a single account that
+// are spread over many addresses.
+#[tracing::instrument(name = "Rate limit", skip_all)]
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AuthAPIError> {
+    let route = request.uri().path().to_owned();
+    let ip = client_ip(request.headers(), request.extensions(), &state.trusted_proxies);
+
+    let (parts, body) = request.into_parts();
+    let bytes = body::to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| AuthAPIError::PayloadTooLarge)?;
+
+    // Malformed bodies are left for the route to reject
+    let email = serde_json::from_slice::<TargetEmail>(&bytes)
+        .ok()
+        .and_then(|target| target.email)
+        .map(|email| email.trim().to_lowercase());
+
+    if let Some(ip) = ip {
+        take_token(&state, format!("{}:ip:{}", route, ip), &state.rate_limits.per_ip).await?;
+    }
+
+    if let Some(email) = email {
+        let key = format!("{}:email:{}", route, email);
+        take_token(&state, key, &state.rate_limits.per_email).await?;
+    }
+
+    Ok(next.run(Request::from_parts(parts, Body::from(bytes))).await)
+}
+
+async fn take_token(state: &AppState, key: String, limit: &RateLimit) -> Result<(), AuthAPIError> {
+    let retry_after = state
+        .rate_limit_store
+        .write()
+        .await
+        .take_token(&key, limit)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    match retry_after {
+        Some(retry_after_seconds) => Err(AuthAPIError::TooManyRequests { retry_after_seconds }),
+        None => Ok(()),
+    }
+}
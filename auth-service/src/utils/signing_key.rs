@@ -0,0 +1,389 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header,
+};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand::RngCore;
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding},
+    traits::PublicKeyParts,
+    RsaPrivateKey,
+};
+use sha2::{Digest, Sha256};
+
+// A key used to sign auth tokens, together with everything needed to verify them
+// and to publish the public half in the JWKS document.
+pub struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    // HMAC keys are shared secrets and must never be published
+    jwk: Option<Jwk>,
+    // The PEM private key, or the HMAC secret, see `export`
+    private_key: Vec<u8>,
+}
+
+impl SigningKey {
+    // Symmetric HS256 key, kept for deployments that have not configured a key pair yet
+    pub fn from_secret(secret: &[u8]) -> Self {
+        let digest = Sha256::digest(secret);
+
+        Self {
+            kid: URL_SAFE_NO_PAD.encode(&digest[..8]),
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+            private_key: secret.to_vec(),
+        }
+    }
+
+    // Asymmetric key loaded from a PKCS#8 PEM private key (RSA keys may also be PKCS#1).
+    // When no `kid` is given, the RFC 7638 thumbprint of the public key is used.
+    pub fn from_pem(algorithm: Algorithm, pem: &str, kid: Option<String>) -> Result<Self> {
+        let (key_algorithm, parameters, encoding_key) = match algorithm {
+            Algorithm::RS256 => (
+                KeyAlgorithm::RS256,
+                rsa_parameters(pem)?,
+                EncodingKey::from_rsa_pem(pem.as_bytes()),
+            ),
+            Algorithm::ES256 => (
+                KeyAlgorithm::ES256,
+                ec_parameters(pem)?,
+                EncodingKey::from_ec_pem(pem.as_bytes()),
+            ),
+            Algorithm::EdDSA => (
+                KeyAlgorithm::EdDSA,
+                ed_parameters(pem)?,
+                EncodingKey::from_ed_pem(pem.as_bytes()),
+            ),
+            _ => return Err(eyre!("unsupported signing algorithm: {:?}", algorithm)),
+        };
+
+        let encoding_key = encoding_key.wrap_err("failed to load signing key")?;
+
+        let kid = kid.unwrap_or_else(|| thumbprint(&parameters));
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).wrap_err("failed to derive verification key")?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+            private_key: pem.as_bytes().to_vec(),
+        })
+    }
+
+    // Fresh random key for the given algorithm, used when rotating keys at runtime
+    pub fn generate(algorithm: Algorithm) -> Result<Self> {
+        let mut rng = rand::thread_rng();
+
+        let pem = match algorithm {
+            Algorithm::HS256 => {
+                let mut secret = [0u8; 32];
+                rng.fill_bytes(&mut secret);
+                return Ok(Self::from_secret(&secret));
+            }
+            Algorithm::RS256 => RsaPrivateKey::new(&mut rng, 2048)
+                .wrap_err("failed to generate RSA key")?
+                .to_pkcs8_pem(LineEnding::LF),
+            Algorithm::ES256 => p256::SecretKey::random(&mut rng).to_pkcs8_pem(LineEnding::LF),
+            Algorithm::EdDSA => {
+                ed25519_dalek::SigningKey::generate(&mut rng).to_pkcs8_pem(LineEnding::LF)
+            }
+            _ => return Err(eyre!("unsupported signing algorithm: {:?}", algorithm)),
+        }
+        .wrap_err("failed to encode generated key")?;
+
+        Self::from_pem(algorithm, &pem, None)
+    }
+
+    // Private key material to share the key with other instances, see `import`
+    pub fn export(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    // Restore a key from what `export` returned
+    pub fn import(algorithm: Algorithm, kid: String, exported: &[u8]) -> Result<Self> {
+        match algorithm {
+            Algorithm::HS256 => Ok(Self {
+                kid,
+                ..Self::from_secret(exported)
+            }),
+            _ => {
+                let pem = std::str::from_utf8(exported).wrap_err("exported key is not a PEM")?;
+                Self::from_pem(algorithm, pem, Some(kid))
+            }
+        }
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    // Public key in JWK form, `None` for symmetric keys
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+
+    // JWT header naming this key, so verifiers can pick the right one
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        header
+    }
+}
+
+fn rsa_parameters(pem: &str) -> Result<AlgorithmParameters> {
+    let key = RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .wrap_err("failed to parse RSA private key")?;
+
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+        e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+    }))
+}
+
+fn ec_parameters(pem: &str) -> Result<AlgorithmParameters> {
+    let key = p256::SecretKey::from_pkcs8_pem(pem).wrap_err("failed to parse P-256 private key")?;
+    let point = key.public_key().to_encoded_point(false);
+
+    let (x, y) = match (point.x(), point.y()) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(eyre!("P-256 public key is not an affine point")),
+    };
+
+    Ok(AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+        key_type: EllipticCurveKeyType::EC,
+        curve: EllipticCurve::P256,
+        x: URL_SAFE_NO_PAD.encode(x),
+        y: URL_SAFE_NO_PAD.encode(y),
+    }))
+}
+
+fn ed_parameters(pem: &str) -> Result<AlgorithmParameters> {
+    let key = ed25519_dalek::SigningKey::from_pkcs8_pem(pem)
+        .wrap_err("failed to parse Ed25519 private key")?;
+
+    Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x: URL_SAFE_NO_PAD.encode(key.verifying_key().to_bytes()),
+    }))
+}
+
+// RFC 7638 JWK thumbprint: SHA-256 over the required members in lexicographic order
+fn thumbprint(parameters: &AlgorithmParameters) -> String {
+    let canonical = match parameters {
+        AlgorithmParameters::RSA(rsa) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n)
+        }
+        AlgorithmParameters::EllipticCurve(ec) => {
+            format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, ec.x, ec.y)
+        }
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x)
+        }
+        AlgorithmParameters::OctetKey(_) => unreachable!("symmetric keys have no thumbprint"),
+    };
+
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, encode, Validation};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn generate_pem(algorithm: Algorithm) -> String {
+        let mut rng = rand::thread_rng();
+
+        let pem = match algorithm {
+            Algorithm::RS256 => RsaPrivateKey::new(&mut rng, 2048)
+                .unwrap()
+                .to_pkcs8_pem(LineEnding::LF),
+            Algorithm::ES256 => p256::SecretKey::random(&mut rng).to_pkcs8_pem(LineEnding::LF),
+            Algorithm::EdDSA => {
+                ed25519_dalek::SigningKey::generate(&mut rng).to_pkcs8_pem(LineEnding::LF)
+            }
+            _ => unreachable!(),
+        };
+
+        pem.unwrap().to_string()
+    }
+
+    fn assert_round_trip(key: &SigningKey) {
+        let claims = TestClaims {
+            sub: "test@example.com".to_owned(),
+            exp: 4_102_444_800,
+        };
+
+        let token = encode(&key.header(), &claims, key.encoding_key()).unwrap();
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.alg, key.algorithm());
+        assert_eq!(header.kid.as_deref(), Some(key.kid()));
+
+        let decoded = decode::<TestClaims>(
+            &token,
+            key.decoding_key(),
+            &Validation::new(key.algorithm()),
+        )
+        .unwrap();
+        assert_eq!(decoded.claims, claims);
+
+        // Anyone holding only the published JWK must be able to verify the token
+        if let Some(jwk) = key.jwk() {
+            let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+            let result =
+                decode::<TestClaims>(&token, &decoding_key, &Validation::new(key.algorithm()));
+            assert!(result.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rs256_key_from_pem() {
+        let key = SigningKey::from_pem(Algorithm::RS256, &generate_pem(Algorithm::RS256), None)
+            .unwrap();
+
+        let jwk = key.jwk().unwrap();
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::RSA(_)));
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::RS256));
+        assert_round_trip(&key);
+    }
+
+    #[test]
+    fn test_es256_key_from_pem() {
+        let key = SigningKey::from_pem(Algorithm::ES256, &generate_pem(Algorithm::ES256), None)
+            .unwrap();
+
+        let jwk = key.jwk().unwrap();
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::EllipticCurve(_)));
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::ES256));
+        assert_round_trip(&key);
+    }
+
+    #[test]
+    fn test_eddsa_key_from_pem() {
+        let key = SigningKey::from_pem(Algorithm::EdDSA, &generate_pem(Algorithm::EdDSA), None)
+            .unwrap();
+
+        let jwk = key.jwk().unwrap();
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::OctetKeyPair(_)));
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::EdDSA));
+        assert_round_trip(&key);
+    }
+
+    #[test]
+    fn test_hs256_key_is_not_published() {
+        let key = SigningKey::from_secret(b"secret");
+
+        assert_eq!(key.algorithm(), Algorithm::HS256);
+        assert!(key.jwk().is_none());
+        assert_round_trip(&key);
+    }
+
+    #[test]
+    fn test_kid_defaults_to_thumbprint() {
+        let pem = generate_pem(Algorithm::EdDSA);
+
+        let first = SigningKey::from_pem(Algorithm::EdDSA, &pem, None).unwrap();
+        let second = SigningKey::from_pem(Algorithm::EdDSA, &pem, None).unwrap();
+        let other = SigningKey::from_pem(Algorithm::EdDSA, &generate_pem(Algorithm::EdDSA), None)
+            .unwrap();
+
+        assert_eq!(first.kid(), second.kid());
+        assert_ne!(first.kid(), other.kid());
+
+        let named = SigningKey::from_pem(Algorithm::EdDSA, &pem, Some("key-1".to_owned())).unwrap();
+        assert_eq!(named.kid(), "key-1");
+        assert_eq!(named.jwk().unwrap().common.key_id.as_deref(), Some("key-1"));
+    }
+
+    #[test]
+    fn test_generate_key() {
+        for algorithm in [Algorithm::HS256, Algorithm::ES256, Algorithm::EdDSA] {
+            let key = SigningKey::generate(algorithm).unwrap();
+            let other = SigningKey::generate(algorithm).unwrap();
+
+            assert_eq!(key.algorithm(), algorithm);
+            assert_ne!(key.kid(), other.kid());
+            assert_round_trip(&key);
+        }
+
+        assert!(SigningKey::generate(Algorithm::PS256).is_err());
+    }
+
+    #[test]
+    fn test_export_and_import_key() {
+        for algorithm in [Algorithm::HS256, Algorithm::ES256, Algorithm::EdDSA] {
+            let key = SigningKey::generate(algorithm).unwrap();
+            let imported =
+                SigningKey::import(algorithm, key.kid().to_owned(), key.export()).unwrap();
+
+            assert_eq!(imported.kid(), key.kid());
+            assert_eq!(imported.algorithm(), algorithm);
+            assert_eq!(imported.jwk(), key.jwk());
+
+            // Tokens signed by either one verify with the other
+            let claims = TestClaims {
+                sub: "test@example.com".to_owned(),
+                exp: 4_102_444_800,
+            };
+            let token = encode(&key.header(), &claims, key.encoding_key()).unwrap();
+            let validation = Validation::new(algorithm);
+            assert!(decode::<TestClaims>(&token, imported.decoding_key(), &validation).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rejects_mismatched_algorithm() {
+        let pem = generate_pem(Algorithm::EdDSA);
+
+        assert!(SigningKey::from_pem(Algorithm::ES256, &pem, None).is_err());
+        assert!(SigningKey::from_pem(Algorithm::HS256, &pem, None).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use auth_service::{
+    domain::PrincipalType,
+    routes::{CreateApiKeyResponse, ListApiKeysResponse, VerifyTokenResponse},
+};
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+}
+
+async fn create_api_key(app: &TestApp, body: serde_json::Value) -> CreateApiKeyResponse {
+    let response = app.post_api_key(&body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<CreateApiKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to CreateApiKeyResponse")
+}
+
+async fn list_api_keys(app: &TestApp) -> ListApiKeysResponse {
+    let response = app.get_api_keys().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<ListApiKeysResponse>()
+        .await
+        .expect("Could not deserialize response body to ListApiKeysResponse")
+}
+
+#[tokio::test]
+async fn should_verify_api_key_sent_as_bearer_token() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let created = create_api_key(&app, serde_json::json!({ "name": "CI" })).await;
+
+    assert!(created.key.starts_with("ak_"));
+    assert!(created.key.starts_with(&created.api_key.prefix));
+    assert!(created.api_key.expires_at.is_none());
+    assert!(created.api_key.last_used_at.is_none());
+
+    // Scripts don't log in, they only send the key
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token_with_bearer(&created.key).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+
+    assert_eq!(verified.principal, PrincipalType::User);
+    assert_eq!(verified.sub, random_email);
+    assert!(verified.scope.is_none());
+
+    // The key can also be sent like any other token
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": created.key }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_list_keys_without_the_key() {
+    let app = TestApp::new().await;
+
+    signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let first = create_api_key(&app, serde_json::json!({ "name": "CI" })).await;
+    let second = create_api_key(
+        &app,
+        serde_json::json!({
+            "name": "Deploy script",
+            "scope": "deploy:write",
+            "expiresInDays": 30,
+        }),
+    )
+    .await;
+
+    assert_eq!(second.api_key.scope, "deploy:write");
+    let expires_at = second.api_key.expires_at.expect("No expiry");
+    assert_eq!(expires_at - second.api_key.created_at, 30 * 24 * 60 * 60);
+
+    let response = app.post_verify_token_with_bearer(&second.key).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+
+    assert_eq!(verified.scope.as_deref(), Some("deploy:write"));
+
+    let response = app.get_api_keys().await;
+    let body = response.text().await.unwrap();
+
+    assert!(!body.contains(&first.key) && !body.contains(&second.key));
+
+    let keys = list_api_keys(&app).await.api_keys;
+
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].id, first.api_key.id);
+    assert!(keys[0].last_used_at.is_none());
+    assert_eq!(keys[1].id, second.api_key.id);
+    assert!(keys[1].last_used_at.is_some());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_revoke_key() {
+    let app = TestApp::new().await;
+
+    signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let created = create_api_key(&app, serde_json::json!({ "name": "CI" })).await;
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.post_verify_token_with_bearer(&created.key).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert!(list_api_keys(&app).await.api_keys.is_empty());
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_key_of_other_user() {
+    let app = TestApp::new().await;
+
+    signup_and_login(&app, &TestApp::get_random_email()).await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "CI" })).await;
+
+    signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(list_api_keys(&app).await.api_keys.is_empty());
+
+    let response = app.post_verify_token_with_bearer(&created.key).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.post_api_key(&serde_json::json!({ "name": "CI" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_api_keys().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+
+    signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let test_cases = [
+        serde_json::json!({ "name": " " }),
+        serde_json::json!({ "name": "CI", "scope": "deploy\\write" }),
+        serde_json::json!({ "name": "CI", "expiresInDays": 0 }),
+        serde_json::json!({ "name": "CI", "expiresInDays": 366 }),
+    ];
+
+    for body in test_cases {
+        let response = app.post_api_key(&body).await;
+
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", body);
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_api_key() {
+    let app = TestApp::new().await;
+
+    for token in ["ak_invalid", "ak_AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"] {
+        let response = app.post_verify_token_with_bearer(token).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,240 @@
+use auth_service::{
+    domain::ErrorResponse,
+    routes::MAX_FAILED_LOGINS,
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.login(email, "password123").await
+}
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_other_sessions() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    // A session on another device
+    let response = signup_and_login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let other_token = get_cookie(&response, JWT_COOKIE_NAME);
+    let other_refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    // The current session
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = get_cookie(&response, JWT_COOKIE_NAME);
+    assert!(!new_token.is_empty());
+    assert!(!get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).is_empty());
+
+    // The current session carries on with the reissued cookie
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Every other session is gone
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_TOKEN_COOKIE_NAME, other_refresh_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Only the new password works from now on
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&random_email, "new_password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_incorrect() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for current_password in ["wrong_password", "short"] {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "currentPassword": current_password,
+                "newPassword": "new_password123",
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Incorrect credentials".to_owned()
+        );
+    }
+
+    // The password is unchanged
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_423_after_too_many_incorrect_current_passwords() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let change_password_body = |current_password: &str| {
+        serde_json::json!({
+            "currentPassword": current_password,
+            "newPassword": "new_password123",
+        })
+    };
+
+    let mut statuses = vec![];
+    for _ in 0..MAX_FAILED_LOGINS {
+        let response = app
+            .post_change_password(&change_password_body("wrong_password"))
+            .await;
+        statuses.push(response.status().as_u16());
+    }
+
+    let mut expected = vec![401; MAX_FAILED_LOGINS as usize - 1];
+    expected.push(423);
+    assert_eq!(statuses, expected);
+
+    // Neither the right password nor a login gets past the lock
+    let response = app
+        .post_change_password(&change_password_body("password123"))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 423);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "short",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/", JWT_COOKIE_NAME),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({ "newPassword": "new_password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup_test().await;
+}
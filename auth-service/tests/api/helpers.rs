@@ -1,7 +1,7 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::{Arc, Once}};
 
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType}, get_postgres_pool, get_redis_client, services::{data_stores::{hashmap_user_store::HashmapUserStore, HashmapTwoFACodeStore, HashsetBannedTokenStore, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore}, MockEmailClient}, utils::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME}, Application
+    app_state::{AppState, BannedTokenStoreType, EmailClientType, RateLimits, RefreshTokenStoreType, SessionLimit, TwoFACodeStoreType, UnverifiedLoginPolicy}, domain::{Email, EmailClient, LoginAttemptId, TrustedProxies}, get_postgres_pool, routes::ServiceAccountResponse, get_redis_client, services::data_stores::{PostgresRecoveryCodeStore, PostgresTotpStore, PostgresUserStore, PostgresOAuthClientStore, PostgresOAuthConsentStore, PostgresServiceAccountStore, PostgresApiKeyStore, PostgresWebauthnCredentialStore, RedisAuthorizationCodeStore, RedisBannedTokenStore, RedisCooldownStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisTwoFACodeStore, RedisMagicLinkStore, RedisSessionStore, RedisAccountLockoutStore, RedisWebauthnChallengeStore}, utils::{env, test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME}, Application
 };
 
 use reqwest::cookie::Jar;
@@ -15,11 +15,109 @@ pub struct TestApp {
     pub http_client: reqwest::Client,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub email_client: RecordingEmailClient,
     pub db_name: String,
 }
 
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+// Keeps every sent email in memory, so tests can read the links and codes they contain
+#[derive(Clone, Default)]
+pub struct RecordingEmailClient {
+    emails: Arc<RwLock<Vec<SentEmail>>>,
+}
+
+impl RecordingEmailClient {
+    pub async fn emails_to(&self, recipient: &str) -> Vec<SentEmail> {
+        self.emails
+            .read()
+            .await
+            .iter()
+            .filter(|email| email.recipient == recipient)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn last_email_to(&self, recipient: &str) -> Option<SentEmail> {
+        self.emails_to(recipient).await.pop()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> color_eyre::eyre::Result<()> {
+        self.emails.write().await.push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+
+        Ok(())
+    }
+}
+
+pub const ADMIN_API_TOKEN: &str = "test-admin-token";
+
+// Sign test tokens with the ES256 fixture key instead of JWT_SECRET, so the JWKS is populated,
+// and enable the admin routes
+static CONFIGURE_ENVIRONMENT: Once = Once::new();
+
+fn configure_environment() {
+    CONFIGURE_ENVIRONMENT.call_once(|| {
+        std::env::set_var(
+            env::JWT_SIGNING_KEY_FILE_ENV_VAR,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt_signing_key.pem"),
+        );
+        std::env::set_var(env::JWT_SIGNING_ALGORITHM_ENV_VAR, "ES256");
+        std::env::set_var(env::ADMIN_API_TOKEN_ENV_VAR, ADMIN_API_TOKEN);
+    });
+}
+
 impl TestApp {
     pub async fn new() -> Self {
+        Self::build(|app_state| app_state).await
+    }
+
+    pub async fn new_with_unverified_login_policy(policy: UnverifiedLoginPolicy) -> Self {
+        Self::build(|app_state| app_state.with_unverified_login_policy(policy)).await
+    }
+
+    pub async fn new_with_session_limit(session_limit: SessionLimit) -> Self {
+        Self::build(|app_state| app_state.with_session_limit(session_limit)).await
+    }
+
+    pub async fn new_with_rate_limits(
+        rate_limits: RateLimits,
+        trusted_proxies: TrustedProxies,
+    ) -> Self {
+        Self::build(|app_state| {
+            app_state
+                .with_rate_limits(rate_limits)
+                .with_trusted_proxies(trusted_proxies)
+        })
+        .await
+    }
+
+    // Emails go to the given client instead of `email_client`, which then stays empty
+    pub async fn new_with_email_client(email_client: EmailClientType) -> Self {
+        Self::build(|app_state| app_state.with_email_client(email_client)).await
+    }
+
+    // Rate limits are counted in memory, so every test app starts with full buckets
+    async fn build(configure: impl FnOnce(AppState) -> AppState) -> Self {
+        configure_environment();
+
         // We are creating a new database for each test case, and we need to ensure each database has a unique name!
         let db_name = Uuid::new_v4().to_string();
 
@@ -27,18 +125,70 @@ impl TestApp {
 
         let redis_connection = Arc::new(RwLock::new(configure_redis()));
 
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+
+        let totp_store = Arc::new(RwLock::new(PostgresTotpStore::new(pg_pool.clone())));
+
+        let recovery_code_store = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+
+        let webauthn_credential_store = Arc::new(RwLock::new(PostgresWebauthnCredentialStore::new(pg_pool.clone())));
+
+        let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+
+        let oauth_consent_store = Arc::new(RwLock::new(PostgresOAuthConsentStore::new(pg_pool.clone())));
+
+        let service_account_store = Arc::new(RwLock::new(PostgresServiceAccountStore::new(pg_pool.clone())));
+
+        let api_key_store = Arc::new(RwLock::new(PostgresApiKeyStore::new(pg_pool)));
         
         let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
             redis_connection.clone(),
         )));
 
         let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
-            redis_connection)));
+            redis_connection.clone())));
 
-        let email_client = Arc::new(RwLock::new(MockEmailClient{}));
+        let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+            redis_connection.clone())));
 
-        let app_state = AppState::new(user_store, banned_token_store.clone(), two_fa_code_store.clone(), email_client);
+        let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+            redis_connection.clone())));
+
+        let cooldown_store = Arc::new(RwLock::new(RedisCooldownStore::new(redis_connection.clone())));
+
+        let webauthn_challenge_store = Arc::new(RwLock::new(RedisWebauthnChallengeStore::new(
+            redis_connection.clone())));
+
+        let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_connection.clone())));
+
+        let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_connection.clone())));
+
+        let account_lockout_store = Arc::new(RwLock::new(RedisAccountLockoutStore::new(
+            redis_connection.clone())));
+
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+            redis_connection)));
+
+        let email_client = RecordingEmailClient::default();
+
+        let app_state = AppState::new(user_store, banned_token_store.clone(), two_fa_code_store.clone(), Arc::new(RwLock::new(email_client.clone())))
+            .with_refresh_token_store(refresh_token_store.clone())
+            .with_password_reset_token_store(password_reset_token_store)
+            .with_cooldown_store(cooldown_store)
+            .with_totp_store(totp_store)
+            .with_recovery_code_store(recovery_code_store)
+            .with_webauthn_credential_store(webauthn_credential_store)
+            .with_webauthn_challenge_store(webauthn_challenge_store)
+            .with_magic_link_store(magic_link_store)
+            .with_oauth_client_store(oauth_client_store)
+            .with_oauth_consent_store(oauth_consent_store)
+            .with_authorization_code_store(authorization_code_store)
+            .with_service_account_store(service_account_store)
+            .with_api_key_store(api_key_store)
+            .with_session_store(session_store)
+            .with_account_lockout_store(account_lockout_store);
+
+        let app_state = configure(app_state);
         
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
@@ -54,8 +204,10 @@ impl TestApp {
         // let http_client =  Client::new(); 
         
         let cookie_jar = Arc::new(Jar::default());
+        // Redirects are not followed, so tests can check where e.g. /authorize sends the user
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
 
@@ -65,13 +217,15 @@ impl TestApp {
             http_client,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            email_client,
             db_name,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -79,7 +233,7 @@ impl TestApp {
 
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response where Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -92,16 +246,69 @@ impl TestApp {
 
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response where Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn login(&self, email: &str, password: &str) -> reqwest::Response {
+        self.post_login(&serde_json::json!({
+            "email": email,
+            "password": password,
+        }))
+        .await
+    }
+
+    pub async fn post_unlock_account(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/unlock-account", &self.address))
+            .json(&serde_json::json!({ "token": token }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // The code emailed for a pending 2FA login, read straight from the store
+    pub async fn get_2fa_code(&self, login_attempt_id: &str) -> String {
+        let login_attempt_id = LoginAttemptId::parse(login_attempt_id.to_owned())
+            .expect("Invalid login attempt id");
+
+        let (_, code) = self
+            .two_fa_code_store
+            .read()
+            .await
+            .get_code(&login_attempt_id)
+            .await
+            .expect("Failed to get 2FA code");
+
+        code.as_ref().to_owned()
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -119,6 +326,114 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link_callback<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/callback", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link_opt_in<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/opt-in", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -131,6 +446,296 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_verify_token_with_bearer(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_token_introspect<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-token?introspect=true", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_introspect(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        token: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/introspect", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        token: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/revoke", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_key<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/api-keys", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_keys(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api-keys", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_api_key(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/api-keys/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_signing_key(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/signing-keys/rotate", &self.address));
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_register_oauth_client<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/oauth/clients", &self.address))
+            .bearer_auth(ADMIN_API_TOKEN)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_create_service_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/service-accounts", &self.address))
+            .bearer_auth(ADMIN_API_TOKEN)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn create_service_account(&self, scope: &str) -> ServiceAccountResponse {
+        let response = self
+            .post_create_service_account(&serde_json::json!({
+                "name": "Resource server",
+                "scope": scope,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        response
+            .json::<ServiceAccountResponse>()
+            .await
+            .expect("Could not deserialize response body to ServiceAccountResponse")
+    }
+
+    pub async fn post_rotate_service_account_secret(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/service-accounts/{}/rotate-secret", &self.address, client_id))
+            .bearer_auth(ADMIN_API_TOKEN)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_service_account(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/service-accounts/{}/disable", &self.address, client_id))
+            .bearer_auth(ADMIN_API_TOKEN)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_authorize(&self, params: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_authorize_consent<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/authorize/consent", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token(&self, params: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .form(params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/openid-configuration", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn cleanup_test(&self) {
         delete_database(&self.db_name).await;
     }
@@ -0,0 +1,262 @@
+use auth_service::{
+    domain::{OAuthErrorResponse, PrincipalType},
+    routes::{
+        CreateApiKeyResponse, IntrospectionResponse, ServiceAccountResponse, TokenResponse,
+        TwoFactorAuthResponse,
+    },
+    utils::JWT_COOKIE_NAME,
+};
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str, requires_2fa: bool) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await
+}
+
+fn auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+async fn introspect(
+    app: &TestApp,
+    client: &ServiceAccountResponse,
+    token: &str,
+) -> IntrospectionResponse {
+    let response = app.post_introspect(&client.client_id, client.client_secret.as_deref().unwrap(), token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+
+    response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse")
+}
+
+#[tokio::test]
+async fn should_describe_user_token() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, false).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = introspect(&app, &client, &auth_token(&response)).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(random_email.as_str()));
+    assert_eq!(introspection.principal, Some(PrincipalType::User));
+    assert_eq!(introspection.token_type.as_deref(), Some("Bearer"));
+    assert_eq!(introspection.amr, vec!["pwd"]);
+    assert!(introspection.jti.is_some());
+    assert_eq!(introspection.mfa, Some(false));
+
+    let iat = introspection.iat.expect("No iat");
+    assert!(introspection.exp.expect("No exp") > iat);
+    assert!(introspection.auth_time.expect("No auth_time") <= iat);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_report_completed_2fa() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let two_fa_code = app.get_2fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = introspect(&app, &client, &auth_token(&response)).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.mfa, Some(true));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_describe_service_account_token() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client_id),
+            ("client_secret", client.client_secret.as_deref().unwrap()),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    let introspection = introspect(&app, &client, &token.access_token).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.principal, Some(PrincipalType::ServiceAccount));
+    assert_eq!(introspection.sub.as_deref(), Some(client.client_id.as_str()));
+    assert_eq!(introspection.client_id.as_deref(), Some(client.client_id.as_str()));
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read"));
+    assert!(introspection.mfa.is_none());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_describe_api_key() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    assert_eq!(
+        signup_and_login(&app, &random_email, false)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let response = app
+        .post_api_key(&serde_json::json!({
+            "name": "CI",
+            "scope": "deploy:write",
+            "expiresInDays": 30,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let created = response
+        .json::<CreateApiKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to CreateApiKeyResponse");
+
+    let introspection = introspect(&app, &client, &created.key).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(random_email.as_str()));
+    assert_eq!(introspection.scope.as_deref(), Some("deploy:write"));
+    assert_eq!(introspection.iat, Some(created.api_key.created_at));
+    assert_eq!(introspection.exp, created.api_key.expires_at);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_report_invalid_tokens_as_inactive() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, false).await;
+    let banned_token = auth_token(&response);
+
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    for token in ["", "invalid_token", "ak_invalid", banned_token.as_str()] {
+        let response = app.post_introspect(&client.client_id, client.client_secret.as_deref().unwrap(), token).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        // Nothing but `active` is disclosed about inactive tokens
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "active": false }));
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_client_credentials() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let token = auth_token(&signup_and_login(&app, &random_email, false).await);
+
+    let response = app.post_introspect(&client.client_id, "secret", &token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<OAuthErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthErrorResponse")
+            .error,
+        "invalid_client"
+    );
+
+    let response = app
+        .http_client
+        .post(format!("{}/introspect", &app.address))
+        .form(&[("token", token.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        app.post_disable_service_account(&client.client_id)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let response = app.post_introspect(&client.client_id, client.client_secret.as_deref().unwrap(), &token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,108 @@
+use auth_service::utils::JWT_COOKIE_NAME;
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+
+use crate::helpers::TestApp;
+
+#[derive(serde::Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+#[tokio::test]
+async fn should_return_200_with_public_signing_key() {
+    let app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body to JSON");
+
+    let keys = body["keys"].as_array().expect("No keys in JWKS");
+
+    // Other tests may rotate the signing key concurrently, so retired keys can be listed too
+    assert!(!keys.is_empty());
+
+    for key in keys {
+        assert_eq!(key["kty"], "EC");
+        assert_eq!(key["crv"], "P-256");
+        assert_eq!(key["alg"], "ES256");
+        assert_eq!(key["use"], "sig");
+        assert!(key["kid"].is_string());
+        // The private scalar must never be published
+        assert!(key.get("d").is_none());
+    }
+
+    let jwks: JwkSet = serde_json::from_value(body).expect("Invalid JWKS");
+
+    assert!(jwks
+        .keys
+        .iter()
+        .all(|key| matches!(key.algorithm, AlgorithmParameters::EllipticCurve(_))));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_issue_tokens_verifiable_with_jwks() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    // Verify the token offline, the way a downstream service would
+    let header = decode_header(&token).expect("Failed to decode token header");
+
+    assert_eq!(header.alg, Algorithm::ES256);
+
+    let kid = header.kid.expect("Token has no kid");
+    let jwk = jwks.find(&kid).expect("Token kid not in JWKS");
+    let decoding_key = DecodingKey::from_jwk(jwk).expect("Invalid JWK");
+
+    let claims = decode::<Claims>(&token, &decoding_key, &Validation::new(Algorithm::ES256))
+        .expect("Failed to verify token with JWKS")
+        .claims;
+
+    assert_eq!(claims.sub, random_email);
+
+    app.cleanup_test().await;
+}
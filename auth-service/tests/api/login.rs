@@ -1,7 +1,10 @@
-use core::panic;
-
-use auth_service::{domain::Email, routes::TwoFactorAuthResponse, utils::JWT_COOKIE_NAME};
-use serde_json::error;
+use auth_service::{
+    app_state::{SessionLimit, SessionLimitPolicy},
+    domain::LoginAttemptId,
+    routes::{ListSessionsResponse, TwoFactorAuthResponse, MAX_FAILED_LOGINS},
+    utils::JWT_COOKIE_NAME,
+};
+use reqwest::{header::RETRY_AFTER, Url};
 
 use crate::helpers::TestApp;
 
@@ -58,8 +61,11 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 async fn should_return_401_if_incorrect_credentials() {
     let app = TestApp::new().await;
 
+    // Failed logins are counted per email across test runs, see the lockout tests below
+    let random_email = TestApp::get_random_email();
+
     let signup_body = serde_json::json!({
-        "email": "test@example.com",
+        "email": random_email,
         "password": "password123",
         "requires2FA": false
     });
@@ -69,7 +75,7 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(response.status().as_u16(), 201);
 
     let json_body = serde_json::json!({
-        "email": "test@example.com",
+        "email": random_email,
         "password": "password234"
     });
 
@@ -164,10 +170,336 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let email_result = &Email::parse(random_email).unwrap();
-    let code = app.two_fa_code_store.read().await.get_code(email_result).await.expect("Failed to get 2FA code");
+    let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id).unwrap();
+    let (email, _) = app.two_fa_code_store.read().await.get_code(&login_attempt_id).await.expect("Failed to get 2FA code");
 
-    assert_eq!(json_body.login_attempt_id, code.0.as_ref());
+    assert_eq!(email.as_ref(), random_email);
 
     app.cleanup_test().await;
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn should_return_409_if_session_limit_reached_and_policy_is_reject() {
+    let app = TestApp::new_with_session_limit(SessionLimit {
+        max_sessions: 2,
+        policy: SessionLimitPolicy::Reject,
+    })
+    .await;
+
+    let random_email = TestApp::get_random_email();
+    let tokens = signup_and_login_n_times(&app, &random_email, 2).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(response.cookies().all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    // Existing sessions are left alone
+    for token in &tokens {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Logging out frees up a session
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_409_from_verify_2fa_if_session_limit_reached_and_policy_is_reject() {
+    let app = TestApp::new_with_session_limit(SessionLimit {
+        max_sessions: 1,
+        policy: SessionLimitPolicy::Reject,
+    })
+    .await;
+
+    let random_email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let mut statuses = vec![];
+
+    for _ in 0..2 {
+        let response = app.post_login(&login_body).await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let code = app.get_2fa_code(&login_attempt_id).await;
+
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": code,
+            }))
+            .await;
+
+        statuses.push(response.status().as_u16());
+    }
+
+    assert_eq!(statuses, vec![200, 409]);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_end_oldest_session_if_session_limit_reached_and_policy_is_evict_oldest() {
+    let app = TestApp::new_with_session_limit(SessionLimit {
+        max_sessions: 2,
+        policy: SessionLimitPolicy::EvictOldest,
+    })
+    .await;
+
+    let random_email = TestApp::get_random_email();
+    let tokens = signup_and_login_n_times(&app, &random_email, 3).await;
+
+    let mut statuses = vec![];
+    for token in &tokens {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        statuses.push(response.status().as_u16());
+    }
+
+    // The first login's token is banned, the two latest sessions remain
+    assert_eq!(statuses, vec![401, 200, 200]);
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = response
+        .json::<ListSessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to ListSessionsResponse")
+        .sessions;
+
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.last().is_some_and(|session| session.current));
+
+    app.cleanup_test().await;
+}
+
+async fn signup_and_login_n_times(app: &TestApp, email: &str, logins: usize) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let mut tokens = vec![];
+    for _ in 0..logins {
+        let response = app.post_login(&login_body).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let auth_cookie = response
+            .cookies()
+            .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+            .expect("No auth cookie found");
+
+        tokens.push(auth_cookie.value().to_owned());
+    }
+
+    tokens
+}
+
+async fn signup_and_fail_logins(app: &TestApp, email: &str, failed_logins: u32) -> Vec<u16> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    fail_logins(app, email, failed_logins).await
+}
+
+async fn fail_logins(app: &TestApp, email: &str, failed_logins: u32) -> Vec<u16> {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "wrongpassword"
+    });
+
+    let mut statuses = vec![];
+    for _ in 0..failed_logins {
+        statuses.push(app.post_login(&login_body).await.status().as_u16());
+    }
+
+    statuses
+}
+
+fn get_unlock_token(content: &str) -> String {
+    let link = content
+        .split_whitespace()
+        .find(|word| word.contains("unlock_token="))
+        .expect("No unlock link in email");
+
+    Url::parse(link)
+        .expect("Invalid unlock link")
+        .query_pairs()
+        .find(|(key, _)| key == "unlock_token")
+        .map(|(_, token)| token.into_owned())
+        .expect("No unlock token in link")
+}
+
+#[tokio::test]
+async fn should_return_423_after_too_many_failed_logins() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    let statuses = signup_and_fail_logins(&app, &random_email, MAX_FAILED_LOGINS).await;
+
+    let mut expected = vec![401; MAX_FAILED_LOGINS as usize - 1];
+    expected.push(423);
+    assert_eq!(statuses, expected);
+
+    // The right password doesn't get past the lock either
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 423);
+
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0);
+
+    let sent_email = app
+        .email_client
+        .last_email_to(&random_email)
+        .await
+        .expect("No unlock email sent");
+
+    assert_eq!(sent_email.subject, "Your account has been locked");
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_unlock_account_with_emailed_link() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_fail_logins(&app, &random_email, MAX_FAILED_LOGINS).await;
+
+    let sent_email = app
+        .email_client
+        .last_email_to(&random_email)
+        .await
+        .expect("No unlock email sent");
+
+    let token = get_unlock_token(&sent_email.content);
+
+    let response = app.post_unlock_account(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The link only lifts the lock it was sent for
+    let response = app.post_unlock_account(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_unlock_account("invalid_token").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_reset_failed_logins_on_successful_login() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    let statuses = signup_and_fail_logins(&app, &random_email, MAX_FAILED_LOGINS - 1).await;
+
+    assert!(statuses.iter().all(|status| *status == 401));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let statuses = fail_logins(&app, &random_email, MAX_FAILED_LOGINS - 1).await;
+
+    assert!(statuses.iter().all(|status| *status == 401));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_lock_unknown_emails_without_sending_email() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    let statuses = fail_logins(&app, &random_email, MAX_FAILED_LOGINS).await;
+
+    // Same responses as for a registered email
+    assert_eq!(statuses.last(), Some(&423));
+    assert!(app.email_client.last_email_to(&random_email).await.is_none());
+
+    app.cleanup_test().await;
+}
@@ -1,4 +1,5 @@
-use auth_service::{domain::ErrorResponse, utils::JWT_COOKIE_NAME};
+use auth_service::utils::{Claims, JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use reqwest::Url;
 
 use crate::helpers::TestApp;
@@ -48,9 +49,15 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert!(auth_cookie.value().is_empty());
 
+    // Tokens are banned by their jti
+    let payload = URL_SAFE_NO_PAD
+        .decode(token.split('.').nth(1).expect("Token has no payload"))
+        .expect("Token payload is not base64");
+    let claims: Claims = serde_json::from_slice(&payload).expect("Token payload is not claims");
+
     let banned_token_store = app.banned_token_store.read().await;
     let contains_token = banned_token_store
-        .token_exists(token)
+        .token_exists(&claims.jti.expect("Token has no jti"))
         .await
         .expect("Failed to check if token is banned");
 
@@ -145,4 +152,107 @@ async fn should_return_401_if_invalid_token() {
     assert_eq!(logout_response.status().as_u16(), 401);
     
     app.cleanup_test().await;
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn should_invalidate_tokens_of_every_device_on_logout_all() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // Log in from two devices
+    let mut sessions = vec![];
+    for _ in 0..2 {
+        let response = app.post_login(&login_body).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let get_cookie = |name: &str| {
+            response
+                .cookies()
+                .find(|cookie| cookie.name() == name)
+                .expect("No cookie found")
+                .value()
+                .to_owned()
+        };
+
+        sessions.push((get_cookie(JWT_COOKIE_NAME), get_cookie(REFRESH_TOKEN_COOKIE_NAME)));
+    }
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(auth_cookie.value().is_empty());
+
+    for (token, refresh_token) in &sessions {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        app.cookie_jar.add_cookie_str(
+            &format!(
+                "{}={}; HttpOnly; SameSite=Lax; Path=/",
+                REFRESH_TOKEN_COOKIE_NAME, refresh_token
+            ),
+            &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+        );
+
+        let response = app.post_refresh().await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Logging in again starts a new, valid session
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing_on_logout_all() {
+    let app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,290 @@
+use auth_service::{
+    routes::{RegularAuthResponse, TwoFactorAuthResponse},
+    utils::JWT_COOKIE_NAME,
+};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool, magic_link_enabled: bool) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa,
+        "magicLinkEnabled": magic_link_enabled
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn request_magic_link(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_magic_link(&serde_json::json!({ "email": email })).await
+}
+
+async fn get_magic_link_token(app: &TestApp, email: &str) -> Option<String> {
+    let sent_email = app.email_client.last_email_to(email).await?;
+
+    if sent_email.subject != "Your login link" {
+        return None;
+    }
+
+    let link = sent_email
+        .content
+        .split_whitespace()
+        .find(|word| word.contains("magic_link_token="))
+        .expect("No magic link in email");
+
+    Url::parse(link)
+        .expect("Invalid magic link")
+        .query_pairs()
+        .find(|(key, _)| key == "magic_link_token")
+        .map(|(_, token)| token.into_owned())
+}
+
+#[tokio::test]
+async fn should_log_in_with_magic_link() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, true).await;
+
+    let response = request_magic_link(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = get_magic_link_token(&app, &random_email)
+        .await
+        .expect("No magic link sent");
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    // Following the link verified the email address, so there is no warning
+    let json_body = response
+        .json::<RegularAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to RegularAuthResponse");
+
+    assert_eq!(json_body.warning, None);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_magic_link_is_reused() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, true).await;
+
+    request_magic_link(&app, &random_email).await;
+
+    let token = get_magic_link_token(&app, &random_email)
+        .await
+        .expect("No magic link sent");
+
+    let body = serde_json::json!({ "token": token });
+
+    let response = app.post_magic_link_callback(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_magic_link_callback(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_magic_link_token_is_invalid() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": "invalid" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_206_if_user_requires_2fa() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, true, true).await;
+
+    request_magic_link(&app, &random_email).await;
+
+    let token = get_magic_link_token(&app, &random_email)
+        .await
+        .expect("No magic link sent");
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.message, "2FA required".to_owned());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_not_send_magic_link_unless_user_opted_in() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, false).await;
+
+    let response = request_magic_link(&app, &random_email).await;
+
+    // Same response as for users who opted in, so it doesn't reveal anything about the account
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(get_magic_link_token(&app, &random_email).await, None);
+
+    let response = request_magic_link(&app, &TestApp::get_random_email()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_opt_in_with_auth_cookie() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, false).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_magic_link_opt_in(&serde_json::json!({ "enabled": true }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    request_magic_link(&app, &random_email).await;
+
+    assert!(get_magic_link_token(&app, &random_email).await.is_some());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_opted_out_after_link_was_sent() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, true).await;
+
+    request_magic_link(&app, &random_email).await;
+
+    let token = get_magic_link_token(&app, &random_email)
+        .await
+        .expect("No magic link sent");
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await;
+
+    let response = app
+        .post_magic_link_opt_in(&serde_json::json!({ "enabled": false }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_opt_in_without_auth_cookie() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link_opt_in(&serde_json::json!({ "enabled": true }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_magic_link_requested_too_soon() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, false, true).await;
+
+    let response = request_magic_link(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = request_magic_link(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = request_magic_link(&app, "not-an-email").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
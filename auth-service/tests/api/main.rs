@@ -1,7 +1,27 @@
+mod api_keys;
+mod change_password;
 mod helpers;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth;
+mod oidc;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod revoke;
+mod rotate_signing_key;
 mod root;
+mod service_accounts;
+mod sessions;
 mod signup;
+mod smtp_email_client;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
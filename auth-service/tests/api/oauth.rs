@@ -0,0 +1,493 @@
+use auth_service::{
+    domain::{CodeChallenge, OAuthErrorResponse},
+    routes::{ConsentResponse, OAuthClientResponse, TokenResponse, TwoFactorAuthResponse},
+    utils::JWT_COOKIE_NAME,
+};
+use reqwest::{header::LOCATION, Url};
+use uuid::Uuid;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "http://localhost:8000/callback";
+
+async fn register_client(app: &TestApp) -> String {
+    let response = app
+        .post_register_oauth_client(&serde_json::json!({
+            "name": "App Service",
+            "redirectUris": [REDIRECT_URI],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<OAuthClientResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthClientResponse")
+        .client_id
+}
+
+async fn signup_and_login(app: &TestApp, email: &str, requires_2fa: bool) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await
+}
+
+fn code_verifier() -> String {
+    format!("{}{}", Uuid::new_v4(), Uuid::new_v4())
+}
+
+fn authorize_params<'a>(client_id: &'a str, code_challenge: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("response_type", "code"),
+        ("client_id", client_id),
+        ("redirect_uri", REDIRECT_URI),
+        ("scope", "profile"),
+        ("state", "xyz"),
+        ("code_challenge", code_challenge),
+        ("code_challenge_method", "S256"),
+    ]
+}
+
+fn consent_body(params: &[(&str, &str)], approved: bool) -> serde_json::Value {
+    let mut body: serde_json::Map<String, serde_json::Value> = params
+        .iter()
+        .map(|(key, value)| (key.to_string(), serde_json::Value::from(*value)))
+        .collect();
+    body.insert("approved".to_owned(), approved.into());
+    body.into()
+}
+
+fn location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(LOCATION)
+        .expect("No Location header")
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn query_param(url: &str, key: &str) -> Option<String> {
+    Url::parse(url)
+        .expect("Invalid URL")
+        .query_pairs()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+async fn approve_consent(app: &TestApp, params: &[(&str, &str)]) -> String {
+    let response = app.post_authorize_consent(&consent_body(params, true)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let redirect_to = response
+        .json::<ConsentResponse>()
+        .await
+        .expect("Could not deserialize response body to ConsentResponse")
+        .redirect_to;
+
+    assert!(redirect_to.starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&redirect_to, "state").as_deref(), Some("xyz"));
+
+    query_param(&redirect_to, "code").expect("No code in redirect")
+}
+
+async fn exchange_code(
+    app: &TestApp,
+    client_id: &str,
+    code: &str,
+    code_verifier: &str,
+) -> reqwest::Response {
+    app.post_token(&[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", REDIRECT_URI),
+        ("client_id", client_id),
+        ("code_verifier", code_verifier),
+    ])
+    .await
+}
+
+#[tokio::test]
+async fn should_issue_access_token_with_authorization_code_and_pkce() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    assert_eq!(signup_and_login(&app, &random_email, false).await.status().as_u16(), 200);
+
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    // The user hasn't consented yet, so /authorize sends them to the consent page
+    let response = app.get_authorize(&params).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(location(&response).starts_with("/?oauth_consent="));
+
+    let code = approve_consent(&app, &params).await;
+
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("cache-control").unwrap().to_str().unwrap(),
+        "no-store"
+    );
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope.as_deref(), Some("profile"));
+
+    // The access token is accepted like any other auth token
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_access_token_is_sent_as_auth_cookie() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    assert_eq!(signup_and_login(&app, &random_email, false).await.status().as_u16(), 200);
+
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    let code = approve_consent(&app, &params).await;
+
+    let token = exchange_code(&app, &client_id, &code, &code_verifier)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 200);
+
+    // Only scoped to the user's profile, it must not act as the user's login
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            JWT_COOKIE_NAME, token.access_token
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_remember_consent() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    approve_consent(&app, &params).await;
+
+    // The second time the code is issued right away
+    let response = app.get_authorize(&params).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let redirect_to = location(&response);
+
+    assert!(redirect_to.starts_with(REDIRECT_URI));
+
+    let code = query_param(&redirect_to, "code").expect("No code in redirect");
+
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Asking for more than the user consented to shows the consent page again
+    let mut params = params.clone();
+    params[3] = ("scope", "profile email");
+
+    let response = app.get_authorize(&params).await;
+
+    assert!(location(&response).starts_with("/?oauth_consent="));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_send_user_to_login_page_if_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier());
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    let response = app.get_authorize(&params).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let redirect_to = location(&response);
+
+    assert!(redirect_to.starts_with("/?oauth_authorize="));
+
+    // The login page gets the whole authorization request to come back to /authorize with
+    let authorize_query = query_param(&format!("http://localhost{}", redirect_to), "oauth_authorize")
+        .expect("No authorization request in redirect");
+
+    assert!(authorize_query.contains(&format!("client_id={}", client_id)));
+    assert!(authorize_query.contains("code_challenge_method=S256"));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_reuse_2fa_login_flow() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    // Half way through the login the user is not logged in yet
+    let response = app.get_authorize(&params).await;
+
+    assert!(location(&response).starts_with("/?oauth_authorize="));
+
+    let two_fa_code = app.get_2fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let code = approve_consent(&app, &params).await;
+
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_without_redirect_if_redirect_uri_is_not_registered() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier());
+
+    for redirect_uri in [
+        "http://localhost:8000/callback/",
+        "http://localhost:8000/callback?next=/",
+        "https://attacker.example.com/callback",
+    ] {
+        let mut params = authorize_params(&client_id, code_challenge.as_ref());
+        params[2] = ("redirect_uri", redirect_uri);
+
+        let response = app.get_authorize(&params).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+
+        let error = response
+            .json::<OAuthErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthErrorResponse");
+
+        assert_eq!(error.error, "invalid_request");
+    }
+
+    let mut params = authorize_params(&client_id, code_challenge.as_ref());
+    let unknown_client_id = Uuid::new_v4().to_string();
+    params[1] = ("client_id", &unknown_client_id);
+
+    let response = app.get_authorize(&params).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_require_pkce_with_s256() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+
+    let without_pkce: Vec<_> = authorize_params(&client_id, code_challenge.as_ref())
+        .into_iter()
+        .filter(|(key, _)| !key.starts_with("code_challenge"))
+        .collect();
+
+    let mut plain = authorize_params(&client_id, &code_verifier);
+    plain[6] = ("code_challenge_method", "plain");
+
+    for params in [without_pkce, plain] {
+        let response = app.get_authorize(&params).await;
+
+        assert_eq!(response.status().as_u16(), 303);
+
+        let redirect_to = location(&response);
+
+        assert!(redirect_to.starts_with(REDIRECT_URI));
+        assert_eq!(query_param(&redirect_to, "error").as_deref(), Some("invalid_request"));
+        assert_eq!(query_param(&redirect_to, "state").as_deref(), Some("xyz"));
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_reject_wrong_code_verifier_and_code_reuse() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let code_verifier = code_verifier();
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    let code = approve_consent(&app, &params).await;
+
+    let response = exchange_code(&app, &client_id, &code, &self::code_verifier()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error = response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse");
+
+    assert_eq!(error.error, "invalid_grant");
+
+    // A failed exchange uses up the code
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // And so does a successful one
+    let code = approve_consent(&app, &params).await;
+
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = exchange_code(&app, &client_id, &code, &code_verifier).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_redirect_with_access_denied_if_consent_is_denied() {
+    let app = TestApp::new().await;
+
+    let client_id = register_client(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let code_challenge = CodeChallenge::from_verifier(&code_verifier());
+    let params = authorize_params(&client_id, code_challenge.as_ref());
+
+    let response = app.post_authorize_consent(&consent_body(&params, false)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let redirect_to = response
+        .json::<ConsentResponse>()
+        .await
+        .expect("Could not deserialize response body to ConsentResponse")
+        .redirect_to;
+
+    assert_eq!(query_param(&redirect_to, "error").as_deref(), Some("access_denied"));
+    assert_eq!(query_param(&redirect_to, "code"), None);
+
+    // Nothing was consented to
+    let response = app.get_authorize(&params).await;
+
+    assert!(location(&response).starts_with("/?oauth_consent="));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_oauth_client_is_invalid() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_register_oauth_client(&serde_json::json!({
+            "name": "App Service",
+            "redirectUris": ["http://app.example.com/callback"],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_register_oauth_client(&serde_json::json!({
+            "name": "App Service",
+            "redirectUris": [],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
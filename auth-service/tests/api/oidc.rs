@@ -0,0 +1,621 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{CodeChallenge, OAuthErrorResponse},
+    routes::{
+        ConsentResponse, OAuthClientResponse, OpenIdConfiguration, TokenResponse,
+        TwoFactorAuthResponse, UserInfoResponse,
+    },
+    utils::JWT_COOKIE_NAME,
+};
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::{
+    header::{LOCATION, WWW_AUTHENTICATE},
+    Url,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "http://localhost:8000/callback";
+
+// A relying party that works like an off-the-shelf OpenID Connect client library. It only knows
+// the issuer, discovers everything else, and checks ID tokens against the published keys.
+struct TestClient<'a> {
+    app: &'a TestApp,
+    configuration: OpenIdConfiguration,
+    client_id: String,
+}
+
+// What the client remembers between sending the user to /authorize and the redirect back
+struct PendingAuthorization {
+    params: Vec<(String, String)>,
+    state: String,
+    nonce: String,
+    code_verifier: String,
+}
+
+// Our own view of the ID token, the client doesn't share any code with the auth service
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    exp: i64,
+    iat: i64,
+    auth_time: i64,
+    nonce: Option<String>,
+    #[serde(default)]
+    amr: Vec<String>,
+}
+
+impl<'a> TestClient<'a> {
+    async fn register(app: &'a TestApp) -> TestClient<'a> {
+        let response = app.get_openid_configuration().await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let configuration = response
+            .json::<OpenIdConfiguration>()
+            .await
+            .expect("Could not deserialize response body to OpenIdConfiguration");
+
+        let response = app
+            .post_register_oauth_client(&serde_json::json!({
+                "name": "Relying Party",
+                "redirectUris": [REDIRECT_URI],
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        let client_id = response
+            .json::<OAuthClientResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthClientResponse")
+            .client_id;
+
+        TestClient {
+            app,
+            configuration,
+            client_id,
+        }
+    }
+
+    // The discovered endpoints name the configured issuer, the test server listens elsewhere
+    fn endpoint(&self, url: &str) -> String {
+        assert!(url.starts_with(&self.configuration.issuer));
+        url.replacen(&self.configuration.issuer, &self.app.address, 1)
+    }
+
+    async fn start(
+        &self,
+        scope: &str,
+        extra_params: &[(&str, &str)],
+    ) -> (PendingAuthorization, reqwest::Response) {
+        let state = Uuid::new_v4().to_string();
+        let nonce = Uuid::new_v4().to_string();
+        let code_verifier = format!("{}{}", Uuid::new_v4(), Uuid::new_v4());
+        let code_challenge = CodeChallenge::from_verifier(&code_verifier);
+
+        let params: Vec<(String, String)> = [
+            ("response_type", "code"),
+            ("client_id", &self.client_id),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", scope),
+            ("state", &state),
+            ("nonce", &nonce),
+            ("code_challenge", code_challenge.as_ref()),
+            ("code_challenge_method", "S256"),
+        ]
+        .iter()
+        .chain(extra_params)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        let response = self
+            .app
+            .http_client
+            .get(self.endpoint(&self.configuration.authorization_endpoint))
+            .query(&params)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        let pending = PendingAuthorization {
+            params,
+            state,
+            nonce,
+            code_verifier,
+        };
+
+        (pending, response)
+    }
+
+    async fn approve(&self, pending: &PendingAuthorization) -> String {
+        let mut body: serde_json::Map<String, serde_json::Value> = pending
+            .params
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone().into()))
+            .collect();
+        body.insert("approved".to_owned(), true.into());
+
+        let response = self.app.post_authorize_consent(&body).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+            .json::<ConsentResponse>()
+            .await
+            .expect("Could not deserialize response body to ConsentResponse")
+            .redirect_to
+    }
+
+    async fn callback(&self, pending: &PendingAuthorization, redirect_to: &str) -> TokenResponse {
+        assert!(redirect_to.starts_with(REDIRECT_URI));
+        assert_eq!(
+            query_param(redirect_to, "state"),
+            Some(pending.state.clone())
+        );
+
+        let code = query_param(redirect_to, "code").expect("No code in redirect");
+
+        let response = self
+            .app
+            .http_client
+            .post(self.endpoint(&self.configuration.token_endpoint))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", REDIRECT_URI),
+                ("client_id", &self.client_id),
+                ("code_verifier", &pending.code_verifier),
+            ])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+            .json::<TokenResponse>()
+            .await
+            .expect("Could not deserialize response body to TokenResponse")
+    }
+
+    // Runs the whole flow for a logged in user, consenting if asked to
+    async fn authorize(&self, scope: &str) -> (PendingAuthorization, TokenResponse) {
+        let (pending, response) = self.start(scope, &[]).await;
+
+        assert_eq!(response.status().as_u16(), 303);
+
+        let mut redirect_to = location(&response);
+
+        if redirect_to.starts_with("/?oauth_consent=") {
+            redirect_to = self.approve(&pending).await;
+        }
+
+        let token = self.callback(&pending, &redirect_to).await;
+
+        (pending, token)
+    }
+
+    // The checks of OpenID Connect Core section 3.1.3.7
+    async fn verify_id_token(
+        &self,
+        pending: &PendingAuthorization,
+        id_token: &str,
+    ) -> IdTokenClaims {
+        let jwks = self
+            .app
+            .http_client
+            .get(self.endpoint(&self.configuration.jwks_uri))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json::<JwkSet>()
+            .await
+            .expect("Invalid JWKS");
+
+        let header = decode_header(id_token).expect("Invalid ID token header");
+
+        assert!(self
+            .configuration
+            .id_token_signing_alg_values_supported
+            .contains(&header.alg));
+
+        let jwk = jwks
+            .find(&header.kid.expect("ID token has no kid"))
+            .expect("ID token was not signed by a published key");
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&self.configuration.issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims =
+            decode::<IdTokenClaims>(id_token, &DecodingKey::from_jwk(jwk).unwrap(), &validation)
+                .expect("ID token does not verify")
+                .claims;
+
+        let now = Utc::now().timestamp();
+
+        assert_eq!(claims.iss, self.configuration.issuer);
+        assert_eq!(claims.aud, self.client_id);
+        assert_eq!(claims.nonce.as_deref(), Some(pending.nonce.as_str()));
+        assert!(claims.iat <= now && claims.exp > now);
+        assert!(claims.auth_time <= now);
+
+        claims
+    }
+
+    async fn userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.app
+            .http_client
+            .get(self.endpoint(&self.configuration.userinfo_endpoint))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+}
+
+fn location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(LOCATION)
+        .expect("No Location header")
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn query_param(url: &str, key: &str) -> Option<String> {
+    Url::parse(url)
+        .expect("Invalid URL")
+        .query_pairs()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+async fn signup_and_login(app: &TestApp, email: &str, requires_2fa: bool) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await
+}
+
+#[tokio::test]
+async fn should_publish_discovery_document() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let configuration = &client.configuration;
+
+    assert!(!configuration.issuer.ends_with('/'));
+    assert!(configuration
+        .authorization_endpoint
+        .starts_with(&configuration.issuer));
+    assert!(configuration
+        .token_endpoint
+        .starts_with(&configuration.issuer));
+    assert!(configuration
+        .userinfo_endpoint
+        .starts_with(&configuration.issuer));
+    assert!(configuration
+        .introspection_endpoint
+        .starts_with(&configuration.issuer));
+    assert!(configuration
+        .revocation_endpoint
+        .starts_with(&configuration.issuer));
+    assert!(configuration
+        .scopes_supported
+        .contains(&"openid".to_owned()));
+    assert_eq!(configuration.response_types_supported, vec!["code"]);
+    assert!(configuration
+        .subject_types_supported
+        .contains(&"public".to_owned()));
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+    assert_eq!(
+        configuration.id_token_signing_alg_values_supported,
+        vec![Algorithm::ES256]
+    );
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_issue_id_token_for_openid_scope() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    let logged_in_at = Utc::now().timestamp();
+
+    assert_eq!(
+        signup_and_login(&app, &random_email, false)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let (pending, token) = client.authorize("openid").await;
+
+    let claims = client
+        .verify_id_token(&pending, &token.id_token.expect("No ID token"))
+        .await;
+
+    assert_eq!(claims.sub, random_email);
+    assert!(claims.auth_time >= logged_in_at);
+    assert_eq!(claims.amr, vec!["pwd"]);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_record_2fa_in_amr() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let two_fa_code = app.get_2fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (pending, token) = client.authorize("openid").await;
+
+    let claims = client
+        .verify_id_token(&pending, &token.id_token.expect("No ID token"))
+        .await;
+
+    assert!(claims.amr.contains(&"mfa".to_owned()));
+    assert!(claims.amr.contains(&"otp".to_owned()));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_keep_auth_time_when_tokens_are_refreshed() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let refreshed_at = Utc::now().timestamp();
+
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+
+    let (pending, token) = client.authorize("openid").await;
+
+    let claims = client
+        .verify_id_token(&pending, &token.id_token.expect("No ID token"))
+        .await;
+
+    assert!(claims.auth_time < refreshed_at);
+    assert_eq!(claims.amr, vec!["pwd"]);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_not_issue_id_token_without_openid_scope() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let (_, token) = client.authorize("email").await;
+
+    assert!(token.id_token.is_none());
+
+    // Without the openid scope there is no user info either
+    let response = client.userinfo(&token.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_userinfo_for_access_token() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let (pending, token) = client.authorize("openid").await;
+
+    let claims = client
+        .verify_id_token(&pending, &token.id_token.expect("No ID token"))
+        .await;
+
+    let response = client.userinfo(&token.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let userinfo = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    // The sub claim must match the ID token's
+    assert_eq!(userinfo.sub, claims.sub);
+    assert_eq!(userinfo.email, None);
+
+    // The email claims need the email scope
+    let (_, token) = client.authorize("openid email").await;
+
+    let userinfo = client
+        .userinfo(&token.access_token)
+        .await
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    assert_eq!(userinfo.email, Some(random_email));
+    assert_eq!(userinfo.email_verified, Some(false));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_tokens_at_userinfo() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email, false).await;
+
+    let session_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie")
+        .value()
+        .to_owned();
+
+    let (_, token) = client.authorize("openid").await;
+
+    // ID tokens are meant for the client, they can't be used as access tokens
+    for invalid_token in ["invalid", token.id_token.as_deref().expect("No ID token")] {
+        let response = app.get_userinfo(invalid_token).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .expect("No WWW-Authenticate");
+
+        assert!(challenge
+            .to_str()
+            .unwrap()
+            .contains("error=\"invalid_token\""));
+    }
+
+    // The session token was never issued to a client
+    let response = client.userinfo(&session_token).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let error = response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse");
+
+    assert_eq!(error.error, "insufficient_scope");
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_answer_prompt_none_without_showing_pages() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    let (pending, response) = client.start("openid", &[("prompt", "none")]).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let redirect_to = location(&response);
+
+    assert_eq!(
+        query_param(&redirect_to, "error").as_deref(),
+        Some("login_required")
+    );
+    assert_eq!(query_param(&redirect_to, "state"), Some(pending.state));
+
+    signup_and_login(&app, &random_email, false).await;
+
+    let (_, response) = client.start("openid", &[("prompt", "none")]).await;
+
+    assert_eq!(
+        query_param(&location(&response), "error").as_deref(),
+        Some("consent_required")
+    );
+
+    client.authorize("openid").await;
+
+    let (pending, response) = client.start("openid", &[("prompt", "none")]).await;
+
+    client.callback(&pending, &location(&response)).await;
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_require_fresh_login_for_max_age() {
+    let app = TestApp::new().await;
+
+    let client = TestClient::register(&app).await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email, false).await;
+    client.authorize("openid").await;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let (_, response) = client.start("openid", &[("max_age", "0")]).await;
+
+    assert!(location(&response).starts_with("/?oauth_authorize="));
+
+    let (pending, response) = client.start("openid", &[("max_age", "3600")]).await;
+
+    let token = client.callback(&pending, &location(&response)).await;
+
+    client
+        .verify_id_token(&pending, &token.id_token.expect("No ID token"))
+        .await;
+
+    let (_, response) = client.start("openid", &[("max_age", "-1")]).await;
+
+    assert_eq!(
+        query_param(&location(&response), "error").as_deref(),
+        Some("invalid_request")
+    );
+
+    app.cleanup_test().await;
+}
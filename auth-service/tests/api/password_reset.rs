@@ -0,0 +1,264 @@
+use auth_service::{
+    domain::ErrorResponse,
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn signup(app: &TestApp, email: &str, password: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": password,
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_owned()
+}
+
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sent_email = app
+        .email_client
+        .last_email_to(email)
+        .await
+        .expect("No password reset email sent");
+
+    assert_eq!(sent_email.subject, "Reset your password");
+
+    let link = sent_email
+        .content
+        .split_whitespace()
+        .find(|word| word.contains("password_reset_token="))
+        .expect("No password reset link in email");
+
+    Url::parse(link)
+        .expect("Invalid password reset link")
+        .query_pairs()
+        .find(|(key, _)| key == "password_reset_token")
+        .expect("No token in password reset link")
+        .1
+        .into_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_and_send_email_if_user_exists() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, "password123").await;
+
+    let token = request_reset_token(&app, &random_email).await;
+
+    assert_eq!(token.len(), 64);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_200_without_sending_email_if_user_does_not_exist() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.email_client.emails_to(&random_email).await.is_empty());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({ "token": "a".repeat(64) }),
+        serde_json::json!({ "newPassword": "password123" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+
+        assert_eq!(response.status().as_u16(), 422, "Failed for input: {:?}", test_case);
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_update_password_and_ban_outstanding_tokens() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, "password123").await;
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_token = get_cookie(&response, JWT_COOKIE_NAME);
+
+    let token = request_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The JWT issued before the reset is no longer valid
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Neither is the refresh token, which still sits in the cookie jar
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&random_email, "new_password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": get_cookie(&response, JWT_COOKIE_NAME) }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(!get_cookie(&app.post_refresh().await, REFRESH_TOKEN_COOKIE_NAME).is_empty());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_reused() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, "password123").await;
+
+    let token = request_reset_token(&app, &random_email).await;
+
+    let request_body = serde_json::json!({
+        "token": token,
+        "newPassword": "new_password123"
+    });
+
+    let response = app.post_password_reset_confirm(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+
+    let test_cases = ["invalid", &"a".repeat(64)];
+
+    for test_case in test_cases {
+        let response = app
+            .post_password_reset_confirm(&serde_json::json!({
+                "token": test_case,
+                "newPassword": "new_password123"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for input: {}", test_case);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned()
+        );
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_and_keep_token_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email, "password123").await;
+
+    let token = request_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "short"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // The token was not consumed by the rejected attempt
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,250 @@
+use auth_service::{
+    app_state::RateLimits,
+    domain::{RateLimit, TrustedProxies},
+    routes::ListSessionsResponse,
+    utils::MAX_BODY_BYTES,
+};
+use reqwest::header::RETRY_AFTER;
+
+use crate::helpers::TestApp;
+
+// Buckets that don't refill within a test
+fn limit(burst: u32) -> RateLimit {
+    RateLimit {
+        burst,
+        refill_interval_seconds: 3600,
+    }
+}
+
+fn login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+    })
+}
+
+async fn post_login_forwarded_for(
+    app: &TestApp,
+    email: &str,
+    forwarded_for: &str,
+) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .json(&login_body(email))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_logins_for_email() {
+    let rate_limits = RateLimits {
+        per_ip: limit(100),
+        per_email: limit(2),
+    };
+    let app = TestApp::new_with_rate_limits(rate_limits, TrustedProxies::default()).await;
+
+    let email = TestApp::get_random_email();
+
+    for _ in 0..2 {
+        let response = app.post_login(&login_body(&email)).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Email addresses are compared case-insensitively
+    let response = app.post_login(&login_body(&email.to_uppercase())).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0 && retry_after <= 3600);
+
+    // Other accounts are unaffected
+    let response = app
+        .post_login(&login_body(&TestApp::get_random_email()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_logins_from_ip() {
+    let rate_limits = RateLimits {
+        per_ip: limit(2),
+        per_email: limit(100),
+    };
+    let app = TestApp::new_with_rate_limits(rate_limits, TrustedProxies::default()).await;
+
+    for _ in 0..2 {
+        let response = app
+            .post_login(&login_body(&TestApp::get_random_email()))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_login(&login_body(&TestApp::get_random_email()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_limit_clients_behind_trusted_proxy_separately() {
+    let rate_limits = RateLimits {
+        per_ip: limit(1),
+        per_email: limit(100),
+    };
+    let trusted_proxies = TrustedProxies(vec!["127.0.0.1".parse().unwrap()]);
+    let app = TestApp::new_with_rate_limits(rate_limits, trusted_proxies).await;
+
+    let email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let response = post_login_forwarded_for(&app, &email, "198.51.100.1").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_login_forwarded_for(&app, &email, "198.51.100.1").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // A spoofed address left of the one the proxy saw doesn't escape the limit
+    let response = post_login_forwarded_for(&app, &email, "192.0.2.9, 198.51.100.1").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = post_login_forwarded_for(&app, &email, "198.51.100.2").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Sessions are attributed to the client rather than to the proxy
+    let sessions = app
+        .get_sessions()
+        .await
+        .json::<ListSessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to ListSessionsResponse")
+        .sessions;
+
+    let ip_addresses = sessions
+        .iter()
+        .map(|session| session.ip_address.as_deref())
+        .collect::<Vec<_>>();
+
+    assert_eq!(ip_addresses, vec![Some("198.51.100.1"), Some("198.51.100.2")]);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_ignore_forwarded_for_of_untrusted_client() {
+    let rate_limits = RateLimits {
+        per_ip: limit(1),
+        per_email: limit(100),
+    };
+    let app = TestApp::new_with_rate_limits(rate_limits, TrustedProxies::default()).await;
+
+    let response =
+        post_login_forwarded_for(&app, &TestApp::get_random_email(), "198.51.100.1").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response =
+        post_login_forwarded_for(&app, &TestApp::get_random_email(), "198.51.100.2").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_limit_signup_and_verify_2fa_separately_from_login() {
+    let rate_limits = RateLimits {
+        per_ip: limit(1),
+        per_email: limit(100),
+    };
+    let app = TestApp::new_with_rate_limits(rate_limits, TrustedProxies::default()).await;
+
+    let signup_body = |email: String| {
+        serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        })
+    };
+
+    let response = app
+        .post_signup(&signup_body(TestApp::get_random_email()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_signup(&signup_body(TestApp::get_random_email()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let verify_2fa_body = serde_json::json!({
+        "email": TestApp::get_random_email(),
+        "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        "2FACode": "123456",
+    });
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_ne!(response.status().as_u16(), 429);
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // Each route has its own buckets
+    let response = app
+        .post_login(&login_body(&TestApp::get_random_email()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_413_if_body_is_too_large() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": TestApp::get_random_email(),
+            "password": "a".repeat(MAX_BODY_BYTES),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 413);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,123 @@
+use auth_service::routes::{RecoveryCodesResponse, TwoFactorAuthResponse};
+
+use crate::helpers::TestApp;
+
+async fn start_login(app: &TestApp, email: &str) -> TwoFactorAuthResponse {
+    let response = app.login(email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+}
+
+async fn verify_2fa(app: &TestApp, email: &str, login_attempt_id: &str, code: &str) -> u16 {
+    let verify_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+
+    app.post_verify_2fa(&verify_body).await.status().as_u16()
+}
+
+// Sign up a 2FA user and log in with the emailed code
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_response = start_login(app, email).await;
+
+    let two_fa_code = app
+        .email_client
+        .last_email_to(email)
+        .await
+        .expect("No 2FA code sent")
+        .content;
+
+    let status = verify_2fa(app, email, &login_response.login_attempt_id, &two_fa_code).await;
+    assert_eq!(status, 200);
+}
+
+async fn generate_recovery_codes(app: &TestApp) -> Vec<String> {
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes
+}
+
+#[tokio::test]
+async fn should_accept_each_recovery_code_once() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let login_response = start_login(&app, &random_email).await;
+    assert_eq!(login_response.recovery_codes_remaining, 0);
+
+    let codes = generate_recovery_codes(&app).await;
+    assert_eq!(codes.len(), 10);
+
+    let mut unique_codes = codes.clone();
+    unique_codes.sort();
+    unique_codes.dedup();
+    assert_eq!(unique_codes.len(), 10);
+
+    let login_response = start_login(&app, &random_email).await;
+    assert_eq!(login_response.recovery_codes_remaining, 10);
+
+    let status = verify_2fa(&app, &random_email, &login_response.login_attempt_id, &codes[0]).await;
+    assert_eq!(status, 200);
+
+    let login_response = start_login(&app, &random_email).await;
+    assert_eq!(login_response.recovery_codes_remaining, 9);
+
+    let status = verify_2fa(&app, &random_email, &login_response.login_attempt_id, &codes[0]).await;
+    assert_eq!(status, 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_old_codes_on_regeneration() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let old_codes = generate_recovery_codes(&app).await;
+    let new_codes = generate_recovery_codes(&app).await;
+
+    let login_response = start_login(&app, &random_email).await;
+    assert_eq!(login_response.recovery_codes_remaining, 10);
+
+    let status = verify_2fa(&app, &random_email, &login_response.login_attempt_id, &old_codes[0]).await;
+    assert_eq!(status, 401);
+
+    let status = verify_2fa(&app, &random_email, &login_response.login_attempt_id, &new_codes[0]).await;
+    assert_eq!(status, 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,239 @@
+use auth_service::{
+    domain::{ErrorResponse, RefreshToken},
+    routes::TwoFactorAuthResponse,
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+}
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_owned()
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_cookies_if_valid_refresh_token() {
+    let app = TestApp::new().await;
+
+    let response = signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let auth_token = get_cookie(&response, JWT_COOKIE_NAME);
+    let refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_auth_token = get_cookie(&response, JWT_COOKIE_NAME);
+    let new_refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    assert!(!new_auth_token.is_empty());
+    assert_ne!(new_refresh_token, refresh_token);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The original auth token stays valid until it expires
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_200_if_refresh_token_issued_by_verify_2fa() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != REFRESH_TOKEN_COOKIE_NAME));
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let code = app.get_2fa_code(&response_body.login_attempt_id).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": response_body.login_attempt_id,
+        "2FACode": code
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).is_empty());
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_refresh_token() {
+    let app = TestApp::new().await;
+
+    let test_cases = ["invalid", &"a".repeat(64)];
+
+    for test_case in test_cases {
+        set_refresh_cookie(&app, test_case);
+
+        let response = app.post_refresh().await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for input: {}", test_case);
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_rotated_refresh_token_is_reused() {
+    let app = TestApp::new().await;
+
+    let response = signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let first_refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    // Replay the token that was already rotated
+    set_refresh_cookie(&app, &first_refresh_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The legitimate, not yet used token of the same family is now revoked too
+    let record = app
+        .refresh_token_store
+        .read()
+        .await
+        .get_token(&RefreshToken::parse(second_refresh_token.clone()).unwrap())
+        .await
+        .expect("Failed to get refresh token");
+
+    assert!(record.revoked);
+    assert!(!record.used);
+
+    set_refresh_cookie(&app, &second_refresh_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_used_after_logout() {
+    let app = TestApp::new().await;
+
+    let response = signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    let refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).is_empty());
+
+    set_refresh_cookie(&app, &refresh_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
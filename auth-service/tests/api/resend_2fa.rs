@@ -0,0 +1,175 @@
+use auth_service::routes::{TwoFactorAuthResponse, TWO_FA_RESEND_COOLDOWN_SECONDS};
+use reqwest::header::RETRY_AFTER;
+
+use crate::helpers::TestApp;
+
+// Signs up a user with email 2FA and logs in, returns the login attempt id
+async fn signup_and_start_login(app: &TestApp, email: &str) -> String {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+async fn codes_sent_to(app: &TestApp, email: &str) -> usize {
+    app.email_client
+        .emails_to(email)
+        .await
+        .iter()
+        .filter(|sent_email| sent_email.subject == "2FA Code")
+        .count()
+}
+
+fn resend_body(email: &str, login_attempt_id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id
+    })
+}
+
+#[tokio::test]
+async fn should_email_new_code_for_login_attempt() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let login_attempt_id = signup_and_start_login(&app, &email).await;
+    let old_code = app.get_2fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_resend_2fa(&resend_body(&email, &login_attempt_id))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sent_email = app
+        .email_client
+        .last_email_to(&email)
+        .await
+        .expect("No email sent");
+    let new_code = app.get_2fa_code(&login_attempt_id).await;
+
+    assert_eq!(sent_email.subject, "2FA Code");
+    assert_eq!(sent_email.content, new_code);
+    assert_eq!(codes_sent_to(&app, &email).await, 2);
+
+    // Only the latest code works
+    if old_code != new_code {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": old_code
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": new_code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_code_resent_too_recently() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let login_attempt_id = signup_and_start_login(&app, &email).await;
+
+    let response = app
+        .post_resend_2fa(&resend_body(&email, &login_attempt_id))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_resend_2fa(&resend_body(&email, &login_attempt_id))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0 && retry_after <= TWO_FA_RESEND_COOLDOWN_SECONDS);
+    assert_eq!(codes_sent_to(&app, &email).await, 2);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_login_attempt_or_other_users_email() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let login_attempt_id = signup_and_start_login(&app, &email).await;
+
+    let other_email = TestApp::get_random_email();
+    signup_and_start_login(&app, &other_email).await;
+
+    let test_cases = [
+        resend_body(&other_email, &login_attempt_id),
+        resend_body(&email, &uuid::Uuid::new_v4().to_string()),
+    ];
+
+    for body in test_cases {
+        let response = app.post_resend_2fa(&body).await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for input: {:?}", body);
+    }
+
+    assert_eq!(codes_sent_to(&app, &other_email).await, 1);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        resend_body("not-an-email", &uuid::Uuid::new_v4().to_string()),
+        resend_body(&TestApp::get_random_email(), "not-a-login-attempt-id"),
+    ];
+
+    for body in test_cases {
+        let response = app.post_resend_2fa(&body).await;
+
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", body);
+    }
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,170 @@
+use auth_service::{
+    domain::OAuthErrorResponse,
+    routes::CreateApiKeyResponse,
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> reqwest::Response {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let response = app.login(email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+}
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_owned()
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_revoke_only_the_given_auth_token() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let token = get_cookie(
+        &signup_and_login(&app, &random_email).await,
+        JWT_COOKIE_NAME,
+    );
+    let other_token = get_cookie(&app.login(&random_email, "password123").await, JWT_COOKIE_NAME);
+
+    let response = app.post_revoke(&client.client_id, client.client_secret.as_deref().unwrap(), &token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(verify_token_status(&app, &other_token).await, 200);
+
+    // Revoking it again is not an error
+    let response = app.post_revoke(&client.client_id, client.client_secret.as_deref().unwrap(), &token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let response = signup_and_login(&app, &random_email).await;
+    let refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME);
+
+    let response = app.post_revoke(&client.client_id, client.client_secret.as_deref().unwrap(), &refresh_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The refresh cookie from the login is still in the jar
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_revoke_api_key() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    signup_and_login(&app, &random_email).await;
+
+    let response = app.post_api_key(&serde_json::json!({ "name": "CI" })).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let created = response
+        .json::<CreateApiKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to CreateApiKeyResponse");
+
+    let response = app.post_revoke(&client.client_id, client.client_secret.as_deref().unwrap(), &created.key).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &created.key).await, 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_200_for_unknown_tokens() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+
+    for token in [
+        "",
+        "invalid_token",
+        "ak_invalid",
+        "ak_AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+    ] {
+        let response = app.post_revoke(&client.client_id, client.client_secret.as_deref().unwrap(), token).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Failed for token: {:?}",
+            token
+        );
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_client_credentials() {
+    let app = TestApp::new().await;
+
+    let client = app.create_service_account("reports:read").await;
+    let random_email = TestApp::get_random_email();
+
+    let token = get_cookie(
+        &signup_and_login(&app, &random_email).await,
+        JWT_COOKIE_NAME,
+    );
+
+    let response = app.post_revoke(&client.client_id, "secret", &token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<OAuthErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthErrorResponse")
+            .error,
+        "invalid_client"
+    );
+
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+
+    app.cleanup_test().await;
+}
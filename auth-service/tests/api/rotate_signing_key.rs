@@ -0,0 +1,118 @@
+use auth_service::{routes::RotateSigningKeyResponse, utils::JWT_COOKIE_NAME};
+use jsonwebtoken::{decode_header, jwk::JwkSet};
+
+use crate::helpers::{TestApp, ADMIN_API_TOKEN};
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    token
+}
+
+fn get_kid(token: &str) -> String {
+    decode_header(token)
+        .expect("Failed to decode token header")
+        .kid
+        .expect("Token has no kid")
+}
+
+#[tokio::test]
+async fn should_return_200_and_keep_old_tokens_valid() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let old_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_rotate_signing_key(Some(ADMIN_API_TOKEN)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let RotateSigningKeyResponse {
+        kid: new_kid,
+        activates_at,
+    } = response
+        .json()
+        .await
+        .expect("Could not deserialize response body to RotateSigningKeyResponse");
+
+    assert_ne!(get_kid(&old_token), new_kid);
+    assert!(activates_at > chrono::Utc::now().timestamp());
+
+    // Tokens signed by the retired key keep validating until they expire
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The new key only signs once other instances had time to load it
+    let new_token = signup_and_login(&app, &TestApp::get_random_email()).await;
+
+    assert_ne!(get_kid(&new_token), new_kid);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Both keys are published right away so downstream services can verify either token
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert!(jwks.find(&get_kid(&old_token)).is_some());
+    assert!(jwks.find(&new_kid).is_some());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_token_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_rotate_signing_key(None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_admin_token_incorrect() {
+    let app = TestApp::new().await;
+
+    let response = app.post_rotate_signing_key(Some("not-the-admin-token")).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
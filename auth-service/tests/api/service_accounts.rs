@@ -0,0 +1,307 @@
+use auth_service::{
+    domain::{OAuthErrorResponse, PrincipalType},
+    routes::{ServiceAccountResponse, TokenResponse, VerifyTokenResponse},
+};
+
+use crate::helpers::TestApp;
+
+// Authenticates with HTTP Basic, the method every OAuth server has to support
+async fn post_client_credentials(
+    app: &TestApp,
+    client_id: &str,
+    client_secret: &str,
+    scope: Option<&str>,
+) -> reqwest::Response {
+    let mut params = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = scope {
+        params.push(("scope", scope));
+    }
+
+    app.http_client
+        .post(format!("{}/token", &app.address))
+        .basic_auth(client_id, Some(client_secret))
+        .form(&params)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_access_token(app: &TestApp, account: &ServiceAccountResponse) -> String {
+    let response = post_client_credentials(
+        app,
+        &account.client_id,
+        account.client_secret.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token
+}
+
+async fn error_code(response: reqwest::Response) -> String {
+    response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse")
+        .error
+}
+
+#[tokio::test]
+async fn should_issue_token_verified_as_service_account() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:write reports:read").await;
+
+    assert_eq!(account.scope, "reports:read reports:write");
+    assert!(!account.disabled);
+
+    let response = post_client_credentials(
+        &app,
+        &account.client_id,
+        account.client_secret.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope.as_deref(), Some("reports:read reports:write"));
+    assert!(token.id_token.is_none());
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+
+    assert_eq!(verified.principal, PrincipalType::ServiceAccount);
+    assert_eq!(verified.sub, account.client_id);
+    assert_eq!(verified.scope.as_deref(), Some("reports:read reports:write"));
+
+    // Service accounts are not users
+    let response = app.get_userinfo(&token.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_limit_token_to_requested_scope() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:read reports:write").await;
+    let client_secret = account.client_secret.as_deref().unwrap();
+
+    // Credentials in the request body work too
+    let response = app
+        .post_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &account.client_id),
+            ("client_secret", client_secret),
+            ("scope", "reports:read"),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(token.scope.as_deref(), Some("reports:read"));
+
+    let response =
+        post_client_credentials(&app, &account.client_id, client_secret, Some("users:delete"))
+            .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error_code(response).await, "invalid_scope");
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_form_urldecode_basic_client_credentials() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:read").await;
+
+    // RFC 6749 section 2.3.1, clients form-urlencode the id and secret, escaping any character
+    // is allowed
+    let encode = |value: &str| value.bytes().map(|byte| format!("%{byte:02X}")).collect::<String>();
+
+    let response = post_client_credentials(
+        &app,
+        &encode(&account.client_id),
+        &encode(account.client_secret.as_deref().unwrap()),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_invalid_client_credentials() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:read").await;
+    let other_account = app.create_service_account("reports:read").await;
+
+    let test_cases = [
+        (account.client_id.as_str(), other_account.client_secret.as_deref().unwrap()),
+        (account.client_id.as_str(), "secret"),
+        ("unknown", account.client_secret.as_deref().unwrap()),
+    ];
+
+    for (client_id, client_secret) in test_cases {
+        let response = post_client_credentials(&app, client_id, client_secret, None).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(error_code(response).await, "invalid_client");
+    }
+
+    let response = app
+        .post_token(&[("grant_type", "client_credentials"), ("client_id", &account.client_id)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_rotate_secret() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:read").await;
+    let old_token = get_access_token(&app, &account).await;
+
+    let response = app.post_rotate_service_account_secret(&account.client_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rotated = response
+        .json::<ServiceAccountResponse>()
+        .await
+        .expect("Could not deserialize response body to ServiceAccountResponse");
+
+    assert_eq!(rotated.client_id, account.client_id);
+    assert_ne!(rotated.client_secret, account.client_secret);
+
+    let response = post_client_credentials(
+        &app,
+        &account.client_id,
+        account.client_secret.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    get_access_token(&app, &rotated).await;
+
+    // Tokens issued with the old secret run out on their own
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_reject_disabled_service_account() {
+    let app = TestApp::new().await;
+
+    let account = app.create_service_account("reports:read").await;
+    let token = get_access_token(&app, &account).await;
+
+    let response = app.post_disable_service_account(&account.client_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let disabled = response
+        .json::<ServiceAccountResponse>()
+        .await
+        .expect("Could not deserialize response body to ServiceAccountResponse");
+
+    assert!(disabled.disabled);
+    assert!(disabled.client_secret.is_none());
+
+    let response = post_client_credentials(
+        &app,
+        &account.client_id,
+        account.client_secret.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_service_account() {
+    let app = TestApp::new().await;
+
+    for client_id in ["4b3f2e2c-54a4-4f4e-9c87-ff8b2a7f1f3e", "unknown"] {
+        let response = app.post_rotate_service_account_secret(client_id).await;
+
+        assert_eq!(response.status().as_u16(), 404);
+
+        let response = app.post_disable_service_account(client_id).await;
+
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_service_account() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "name": " ", "scope": "reports:read" }),
+        serde_json::json!({ "name": "Job", "scope": "openid" }),
+        serde_json::json!({ "name": "Job", "scope": "reports\\read" }),
+    ];
+
+    for body in test_cases {
+        let response = app.post_create_service_account(&body).await;
+
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", body);
+    }
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,205 @@
+use auth_service::{
+    routes::{ListSessionsResponse, SessionResponse},
+    utils::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::{header::USER_AGENT, Url};
+
+use crate::helpers::TestApp;
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+}
+
+// Logs in from a device identifying itself as `user_agent`, returns the auth and refresh tokens
+async fn login_from(app: &TestApp, email: &str, user_agent: &str) -> (String, String) {
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header(USER_AGENT, user_agent)
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let get_cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .expect("No cookie found")
+            .value()
+            .to_owned()
+    };
+
+    (get_cookie(JWT_COOKIE_NAME), get_cookie(REFRESH_TOKEN_COOKIE_NAME))
+}
+
+async fn list_sessions(app: &TestApp) -> Vec<SessionResponse> {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<ListSessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to ListSessionsResponse")
+        .sessions
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_logged_in_user() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    signup(&app, &email).await;
+
+    login_from(&app, &email, "Laptop").await;
+    login_from(&app, &email, "Phone").await;
+
+    let sessions = list_sessions(&app).await;
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].user_agent.as_deref(), Some("Laptop"));
+    assert_eq!(sessions[1].user_agent.as_deref(), Some("Phone"));
+    assert!(sessions
+        .iter()
+        .all(|session| session.ip_address.as_deref() == Some("127.0.0.1")));
+    assert!(sessions[0].created_at <= sessions[1].created_at);
+
+    // The cookie jar holds the tokens of the latest login
+    assert!(!sessions[0].current);
+    assert!(sessions[1].current);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_end_revoked_session_only() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    signup(&app, &email).await;
+
+    let (laptop_token, laptop_refresh_token) = login_from(&app, &email, "Laptop").await;
+    let (phone_token, _) = login_from(&app, &email, "Phone").await;
+
+    let sessions = list_sessions(&app).await;
+    let laptop_session = sessions
+        .iter()
+        .find(|session| session.user_agent.as_deref() == Some("Laptop"))
+        .expect("No session found");
+
+    let response = app.delete_session(&laptop_session.id).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    // The revoked session's tokens stop working right away
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": laptop_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": phone_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = list_sessions(&app).await;
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].user_agent.as_deref(), Some("Phone"));
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, laptop_refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_remove_session_on_logout() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    signup(&app, &email).await;
+
+    login_from(&app, &email, "Laptop").await;
+    login_from(&app, &email, "Phone").await;
+
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    login_from(&app, &email, "Tablet").await;
+
+    let user_agents = list_sessions(&app)
+        .await
+        .into_iter()
+        .map(|session| session.user_agent)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        user_agents,
+        vec![Some("Laptop".to_owned()), Some("Tablet".to_owned())]
+    );
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_session_or_session_of_another_user() {
+    let app = TestApp::new().await;
+
+    let other_email = TestApp::get_random_email();
+    signup(&app, &other_email).await;
+    login_from(&app, &other_email, "Laptop").await;
+
+    let other_session_id = list_sessions(&app).await[0].id.clone();
+
+    let email = TestApp::get_random_email();
+    signup(&app, &email).await;
+    login_from(&app, &email, "Laptop").await;
+
+    let unknown_session_id = uuid::Uuid::new_v4().to_string();
+
+    for id in [other_session_id.as_str(), unknown_session_id.as_str(), "not-a-session-id"] {
+        let response = app.delete_session(id).await;
+
+        assert_eq!(response.status().as_u16(), 404, "Failed for session id {}", id);
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_auth_cookie_missing() {
+    let app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+
+    let response = app
+        .delete_session(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,324 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use auth_service::{
+    domain::{Email, EmailClient},
+    routes::TwoFactorAuthResponse,
+    services::{SmtpCredentials, SmtpEmailClient, SmtpSettings, SmtpTls},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{Mutex, RwLock},
+};
+
+use crate::helpers::TestApp;
+
+const SENDER: &str = "no-reply@example.com";
+const USERNAME: &str = "smtp-user";
+const PASSWORD: &str = "smtp-password";
+
+// What the SMTP stand-in received in one mail transaction
+#[derive(Clone, Debug)]
+struct ReceivedEmail {
+    mail_from: String,
+    rcpt_to: Vec<String>,
+    data: String,
+}
+
+impl ReceivedEmail {
+    fn header(&self, name: &str) -> Option<&str> {
+        let prefix = format!("{}: ", name);
+
+        self.data
+            .split("\r\n")
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| line.strip_prefix(&prefix))
+    }
+
+    fn body(&self) -> &str {
+        self.data
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.trim_end())
+            .unwrap_or_default()
+    }
+}
+
+// A plain text SMTP server on a random local port that accepts every email once the client has
+// logged in with `USERNAME` and `PASSWORD`, and keeps what it received
+#[derive(Clone, Default)]
+struct SmtpStandIn {
+    port: u16,
+    connections: Arc<AtomicUsize>,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+}
+
+impl SmtpStandIn {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind SMTP stand-in");
+
+        let stand_in = Self {
+            port: listener.local_addr().unwrap().port(),
+            ..Default::default()
+        };
+
+        let server = stand_in.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                server.connections.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(server.clone().handle(stream));
+            }
+        });
+
+        stand_in
+    }
+
+    async fn handle(self, stream: TcpStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let mut authenticated = false;
+        let mut mail_from = String::new();
+        let mut rcpt_to = vec![];
+
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_uppercase();
+
+            let reply = if command.starts_with("EHLO") {
+                "250-localhost\r\n250-AUTH PLAIN\r\n250 8BITMIME\r\n".to_owned()
+            } else if let Some(response) = line.strip_prefix("AUTH PLAIN ") {
+                let decoded = STANDARD.decode(response).unwrap_or_default();
+                authenticated = decoded == format!("\0{}\0{}", USERNAME, PASSWORD).as_bytes();
+
+                if authenticated {
+                    "235 Authentication succeeded\r\n".to_owned()
+                } else {
+                    "535 Authentication failed\r\n".to_owned()
+                }
+            } else if command.starts_with("MAIL FROM:") && !authenticated {
+                "530 Authentication required\r\n".to_owned()
+            } else if command.starts_with("MAIL FROM:") {
+                mail_from = address(&line);
+                rcpt_to.clear();
+                "250 OK\r\n".to_owned()
+            } else if command.starts_with("RCPT TO:") {
+                rcpt_to.push(address(&line));
+                "250 OK\r\n".to_owned()
+            } else if command == "DATA" {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+
+                let mut data = String::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push_str("\r\n");
+                }
+
+                self.received.lock().await.push(ReceivedEmail {
+                    mail_from: mail_from.clone(),
+                    rcpt_to: rcpt_to.clone(),
+                    data,
+                });
+
+                "250 OK\r\n".to_owned()
+            } else if command == "RSET" || command == "NOOP" {
+                "250 OK\r\n".to_owned()
+            } else if command == "QUIT" {
+                let _ = writer.write_all(b"221 Bye\r\n").await;
+                return;
+            } else {
+                "502 Command not implemented\r\n".to_owned()
+            };
+
+            if writer.write_all(reply.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn received(&self) -> Vec<ReceivedEmail> {
+        self.received.lock().await.clone()
+    }
+
+    fn settings(&self) -> SmtpSettings {
+        SmtpSettings {
+            host: "127.0.0.1".to_owned(),
+            port: Some(self.port),
+            tls: SmtpTls::None,
+            credentials: Some(SmtpCredentials {
+                username: USERNAME.to_owned(),
+                password: PASSWORD.to_owned(),
+            }),
+            sender: format!("Auth Service <{}>", SENDER),
+            timeout: Duration::from_secs(5),
+            pool_max_size: 2,
+        }
+    }
+}
+
+// The address in "MAIL FROM:<address>" and "RCPT TO:<address>"
+fn address(line: &str) -> String {
+    line.split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_owned())
+        .unwrap_or_default()
+}
+
+fn recipient(email: &str) -> Email {
+    Email::parse(email.to_owned()).unwrap()
+}
+
+#[tokio::test]
+async fn should_deliver_email_to_smtp_server() {
+    let stand_in = SmtpStandIn::start().await;
+    let email_client = SmtpEmailClient::new(stand_in.settings()).unwrap();
+
+    let email = TestApp::get_random_email();
+
+    email_client
+        .send_email(&recipient(&email), "Reset your password", "Follow the link to reset it")
+        .await
+        .expect("Failed to send email");
+
+    let received = stand_in.received().await;
+
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].mail_from, SENDER);
+    assert_eq!(received[0].rcpt_to, vec![email.clone()]);
+    assert_eq!(received[0].header("To"), Some(email.as_str()));
+    assert!(received[0].header("From").unwrap().contains(SENDER));
+    assert_eq!(received[0].header("Subject"), Some("Reset your password"));
+    assert_eq!(received[0].body(), "Follow the link to reset it");
+}
+
+#[tokio::test]
+async fn should_reuse_pooled_connection() {
+    let stand_in = SmtpStandIn::start().await;
+    let email_client = SmtpEmailClient::new(stand_in.settings()).unwrap();
+
+    for subject in ["First", "Second", "Third"] {
+        email_client
+            .send_email(&recipient(&TestApp::get_random_email()), subject, "content")
+            .await
+            .expect("Failed to send email");
+
+        // Connections go back to the pool in a background task
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(stand_in.received().await.len(), 3);
+    assert_eq!(stand_in.connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn should_fail_if_smtp_server_rejects_credentials() {
+    let stand_in = SmtpStandIn::start().await;
+
+    let mut settings = stand_in.settings();
+    settings.credentials = Some(SmtpCredentials {
+        username: USERNAME.to_owned(),
+        password: "wrong-password".to_owned(),
+    });
+
+    let email_client = SmtpEmailClient::new(settings).unwrap();
+
+    let result = email_client
+        .send_email(&recipient(&TestApp::get_random_email()), "Subject", "content")
+        .await;
+
+    assert!(result.is_err());
+    assert!(stand_in.received().await.is_empty());
+}
+
+#[tokio::test]
+async fn should_time_out_if_smtp_server_does_not_respond() {
+    // Accepts connections but never greets the client
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let mut connections = vec![];
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+
+    let mut settings = SmtpStandIn::default().settings();
+    settings.port = Some(port);
+    settings.timeout = Duration::from_millis(500);
+
+    let email_client = SmtpEmailClient::new(settings).unwrap();
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(10),
+        email_client.send_email(&recipient(&TestApp::get_random_email()), "Subject", "content"),
+    )
+    .await
+    .expect("Sending should have timed out");
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn should_reject_invalid_sender_address() {
+    let mut settings = SmtpStandIn::default().settings();
+    settings.sender = "not an address".to_owned();
+
+    assert!(SmtpEmailClient::new(settings).is_err());
+}
+
+#[tokio::test]
+async fn should_email_2fa_code_over_smtp() {
+    let stand_in = SmtpStandIn::start().await;
+    let email_client = SmtpEmailClient::new(stand_in.settings()).unwrap();
+
+    let app = TestApp::new_with_email_client(Arc::new(RwLock::new(email_client))).await;
+
+    let email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = app.get_2fa_code(&login_attempt_id).await;
+
+    let received = stand_in.received().await;
+    let two_fa_email = received
+        .iter()
+        .find(|received| received.header("Subject") == Some("2FA Code"))
+        .expect("No 2FA code was emailed");
+
+    assert_eq!(two_fa_email.rcpt_to, vec![email]);
+    assert_eq!(two_fa_email.body(), code);
+}
@@ -0,0 +1,198 @@
+use auth_service::{
+    domain::{TotpCode, TotpSecret, TOTP_STEP_SECONDS},
+    routes::{EnrollTotpResponse, TwoFactorAuthResponse},
+    utils::JWT_COOKIE_NAME,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+
+use crate::helpers::TestApp;
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.login(email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let bytes = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &body.secret)
+        .expect("Secret is not base32");
+
+    TotpSecret::from_bytes(bytes).unwrap()
+}
+
+// What an authenticator app would show `steps_ahead` time steps from now
+fn code(secret: &TotpSecret, steps_ahead: i64) -> TotpCode {
+    let step = Utc::now().timestamp() / TOTP_STEP_SECONDS + steps_ahead;
+    secret.code_at(step as u64)
+}
+
+#[tokio::test]
+async fn should_return_secret_and_qr_codes_on_enroll() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(body.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(body.otpauth_uri.contains(&format!("secret={}", body.secret)));
+
+    let png = STANDARD.decode(&body.qr_code_png).expect("QR code is not base64");
+    assert!(png.starts_with(b"\x89PNG"));
+
+    assert!(body.qr_code_svg.contains("<svg"));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_enable_totp_once_first_code_is_confirmed() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    // Confirming before enrolling fails
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let secret = enroll(&app).await;
+
+    // Until confirmed, login doesn't ask for a code
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code(&secret, 5).as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code(&secret, 0).as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The secret can't be replaced or confirmed a second time
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code(&secret, 1).as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_login_with_totp_code_and_reject_replays() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let secret = enroll(&app).await;
+    let confirm_code = code(&secret, 0);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": confirm_code.as_ref() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(body.method, "totp");
+
+    // No code is emailed to TOTP users
+    let emails = app.email_client.emails_to(&random_email).await;
+    assert!(emails.iter().all(|email| email.subject != "2FA Code"));
+
+    let verify = |code: TotpCode| {
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code.as_ref(),
+        })
+    };
+
+    // The code used for confirmation was used up
+    let response = app.post_verify_2fa(&verify(confirm_code)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_code = code(&secret, 1);
+
+    let response = app.post_verify_2fa(&verify(login_code.clone())).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    // Logging in again with the same code fails
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": login_code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
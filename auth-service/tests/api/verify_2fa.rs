@@ -1,3 +1,10 @@
+use auth_service::{
+    domain::{ErrorResponse, MAX_PENDING_LOGIN_ATTEMPTS},
+    routes::TwoFactorAuthResponse,
+};
+
+use crate::helpers::TestApp;
+
 // use auth_service::{domain::{Email, ErrorResponse}, routes::TwoFactorAuthResponse, utils::JWT_COOKIE_NAME};
 
 // use crate::helpers::TestApp;
@@ -206,4 +213,215 @@
 //     assert_eq!(response.status().as_u16(), 401);
 
 //     app.cleanup_test().await;
-// }
\ No newline at end of file
+// }
+
+// Signs up a user with email 2FA and logs in, returns the login attempt id and the emailed code
+async fn signup_and_start_login(app: &TestApp, email: &str) -> (String, String) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    start_login(app, email).await
+}
+
+async fn start_login(app: &TestApp, email: &str) -> (String, String) {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = app.get_2fa_code(&login_attempt_id).await;
+
+    (login_attempt_id, code)
+}
+
+fn wrong_code(code: &str) -> &'static str {
+    if code == "123456" { "654321" } else { "123456" }
+}
+
+#[tokio::test]
+async fn should_return_410_after_too_many_incorrect_codes() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let (login_attempt_id, code) = signup_and_start_login(&app, &email).await;
+
+    let body = |code: &str| {
+        serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        })
+    };
+
+    for _ in 0..4 {
+        let response = app.post_verify_2fa(&body(wrong_code(&code))).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_verify_2fa(&body(wrong_code(&code))).await;
+
+    assert_eq!(response.status().as_u16(), 410);
+
+    let error = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error;
+
+    assert_eq!(error, "Too many incorrect codes, log in again to get a new one");
+
+    // The code stopped working along with its login attempt
+    let response = app.post_verify_2fa(&body(&code)).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let (login_attempt_id, code) = start_login(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_not_count_codes_of_other_login_attempts() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let (login_attempt_id, code) = signup_and_start_login(&app, &email).await;
+
+    // Someone who only knows the email can't end the user's login attempt
+    for _ in 0..5 {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+                "2FACode": wrong_code(&code)
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+fn verify_2fa_body(email: &str, login_attempt_id: &str, code: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })
+}
+
+#[tokio::test]
+async fn should_keep_concurrent_login_attempts_apart() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+
+    // Logins in two browsers, the second doesn't replace the first one's code
+    let (first_login_attempt_id, first_code) = signup_and_start_login(&app, &email).await;
+    let (second_login_attempt_id, second_code) = start_login(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&email, &first_login_attempt_id, &second_code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&email, &first_login_attempt_id, &first_code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&email, &second_login_attempt_id, &second_code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_drop_oldest_login_attempt_beyond_limit() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let (oldest_login_attempt_id, oldest_code) = signup_and_start_login(&app, &email).await;
+
+    let mut login_attempts = vec![];
+    for _ in 0..MAX_PENDING_LOGIN_ATTEMPTS {
+        login_attempts.push(start_login(&app, &email).await);
+    }
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&email, &oldest_login_attempt_id, &oldest_code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let (login_attempt_id, code) = &login_attempts[0];
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&email, login_attempt_id, code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_login_attempt_of_another_user() {
+    let app = TestApp::new().await;
+
+    let email = TestApp::get_random_email();
+    let (login_attempt_id, code) = signup_and_start_login(&app, &email).await;
+
+    let other_email = TestApp::get_random_email();
+    signup_and_start_login(&app, &other_email).await;
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_body(&other_email, &login_attempt_id, &code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
@@ -0,0 +1,234 @@
+use auth_service::{app_state::UnverifiedLoginPolicy, domain::ErrorResponse, utils::JWT_COOKIE_NAME};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn get_verification_token(app: &TestApp, email: &str) -> String {
+    let sent_email = app
+        .email_client
+        .last_email_to(email)
+        .await
+        .expect("No verification email sent");
+
+    assert_eq!(sent_email.subject, "Verify your email address");
+
+    let link = sent_email
+        .content
+        .split_whitespace()
+        .find(|word| word.contains("email_verification_token="))
+        .expect("No verification link in email");
+
+    Url::parse(link)
+        .expect("Invalid verification link")
+        .query_pairs()
+        .find(|(key, _)| key == "email_verification_token")
+        .expect("No token in verification link")
+        .1
+        .into_owned()
+}
+
+#[tokio::test]
+async fn should_send_verification_email_on_signup() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email).await;
+
+    let token = get_verification_token(&app, &random_email).await;
+
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Verifying twice is harmless
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email).await;
+
+    // An auth token is signed by the same key, but must not verify the email address
+    let response = app.login(&random_email, "password123").await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    for token in ["invalid", auth_token.as_str()] {
+        let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for token: {}", token);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned()
+        );
+    }
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_verify_email(&serde_json::json!({ "tok": "abc" })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "mail": "abc" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_block_login_until_verified_if_policy_is_block() {
+    let app = TestApp::new_with_unverified_login_policy(UnverifiedLoginPolicy::Block).await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email).await;
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email address not verified".to_owned()
+    );
+
+    let token = get_verification_token(&app, &random_email).await;
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_warn_on_login_until_verified_if_policy_is_warn() {
+    let app = TestApp::new_with_unverified_login_policy(UnverifiedLoginPolicy::Warn).await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email).await;
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: serde_json::Value = response.json().await.expect("Invalid login response");
+    assert!(body["warning"].is_string());
+
+    let token = get_verification_token(&app, &random_email).await;
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&random_email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: serde_json::Value = response.json().await.expect("Invalid login response");
+    assert!(body.get("warning").is_none());
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_throttle_resending_verification_email() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    signup(&app, &random_email).await;
+
+    // The signup email starts the cooldown
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+
+    assert_eq!(app.email_client.emails_to(&random_email).await.len(), 1);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_user_exists_when_resending() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.email_client.emails_to(&random_email).await.is_empty());
+
+    // Unknown emails are throttled like registered ones
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": "invalid" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}
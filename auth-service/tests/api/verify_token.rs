@@ -1,4 +1,8 @@
-use auth_service::{domain::ErrorResponse, utils::JWT_COOKIE_NAME};
+use auth_service::{
+    domain::{ErrorResponse, PrincipalType},
+    routes::{IntrospectionResponse, VerifyTokenResponse},
+    utils::JWT_COOKIE_NAME,
+};
 
 use crate::helpers::TestApp;
 
@@ -44,6 +48,70 @@ async fn should_return_200_valid_token() {
 
     assert_eq!(response.status().as_u16(), 200);
 
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+
+    assert_eq!(verified.principal, PrincipalType::User);
+    assert_eq!(verified.sub, random_email);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_introspection_body_if_asked() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token_introspect(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(random_email.as_str()));
+    assert_eq!(introspection.mfa, Some(false));
+    assert!(introspection.exp.is_some() && introspection.iat.is_some());
+
+    // Invalid tokens are still rejected, unlike with `/introspect`
+    let response = app
+        .post_verify_token_introspect(&serde_json::json!({ "token": "invalid_token" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
     app.cleanup_test().await;
 }
 
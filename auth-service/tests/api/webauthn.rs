@@ -0,0 +1,382 @@
+use auth_service::{
+    domain::COSE_ALGORITHM_ES256,
+    routes::{LoginOptionsResponse, RegistrationOptionsResponse, TwoFactorAuthResponse},
+    utils::{JWT_COOKIE_NAME, WEBAUTHN_ORIGIN},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use sha2::{Digest, Sha256};
+
+use crate::helpers::TestApp;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// Plays the part of a passkey provider, so the ceremonies can run without hardware
+struct SoftwareAuthenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+    origin: String,
+    user_verified: bool,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        Self {
+            key: SigningKey::random(&mut rand::thread_rng()),
+            credential_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            sign_count: 0,
+            origin: WEBAUTHN_ORIGIN.clone(),
+            user_verified: true,
+        }
+    }
+
+    // The response to navigator.credentials.create()
+    fn create(&self, options: &RegistrationOptionsResponse, format: &str) -> serde_json::Value {
+        let options = &options.public_key;
+        let client_data = self.client_data("webauthn.create", &options.challenge);
+
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (1.into(), 2.into()),
+            (3.into(), COSE_ALGORITHM_ES256.into()),
+            ((-1).into(), 1.into()),
+            ((-2).into(), Value::Bytes(point.x().unwrap().to_vec())),
+            ((-3).into(), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+
+        let mut auth_data =
+            self.authenticator_data(&options.rp.id, FLAG_ATTESTED_CREDENTIAL_DATA);
+        auth_data.extend([0u8; 16]);
+        auth_data.extend((self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend(&self.credential_id);
+        ciborium::ser::into_writer(&cose_key, &mut auth_data).unwrap();
+
+        let statement = match format {
+            "packed" => vec![
+                ("alg".into(), COSE_ALGORITHM_ES256.into()),
+                ("sig".into(), Value::Bytes(self.sign(&auth_data, &client_data))),
+            ],
+            _ => vec![],
+        };
+
+        let attestation_object = Value::Map(vec![
+            ("fmt".into(), format.into()),
+            ("attStmt".into(), Value::Map(statement)),
+            ("authData".into(), Value::Bytes(auth_data)),
+        ]);
+
+        let mut attestation_object_bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation_object, &mut attestation_object_bytes).unwrap();
+
+        serde_json::json!({
+            "id": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "rawId": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(&client_data),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object_bytes),
+            }
+        })
+    }
+
+    // The response to navigator.credentials.get()
+    fn get(&mut self, options: &LoginOptionsResponse) -> serde_json::Value {
+        let options = &options.public_key;
+        let client_data = self.client_data("webauthn.get", &options.challenge);
+
+        self.sign_count += 1;
+        let auth_data = self.authenticator_data(&options.rp_id, 0);
+        let signature = self.sign(&auth_data, &client_data);
+
+        serde_json::json!({
+            "id": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "rawId": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(&client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(&auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature),
+            }
+        })
+    }
+
+    fn client_data(&self, ceremony_type: &str, challenge: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": self.origin,
+            "crossOrigin": false,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+        let mut flags = flags | FLAG_USER_PRESENT;
+        if self.user_verified {
+            flags |= FLAG_USER_VERIFIED;
+        }
+
+        let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        auth_data.push(flags);
+        auth_data.extend(self.sign_count.to_be_bytes());
+        auth_data
+    }
+
+    fn sign(&self, auth_data: &[u8], client_data: &[u8]) -> Vec<u8> {
+        let message = [auth_data, &Sha256::digest(client_data)].concat();
+        let signature: Signature = self.key.sign(&message);
+        signature.to_der().as_bytes().to_vec()
+    }
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.login(email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn register(
+    app: &TestApp,
+    authenticator: &SoftwareAuthenticator,
+    format: &str,
+    enable_2fa: bool,
+) -> reqwest::Response {
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<RegistrationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to RegistrationOptionsResponse");
+
+    let body = serde_json::json!({
+        "credential": authenticator.create(&options, format),
+        "enable2FA": enable_2fa,
+    });
+
+    app.post_webauthn_register_finish(&body).await
+}
+
+async fn start_login(app: &TestApp, body: serde_json::Value) -> LoginOptionsResponse {
+    let response = app.post_webauthn_login_start(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<LoginOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to LoginOptionsResponse")
+}
+
+async fn finish_login(app: &TestApp, credential: serde_json::Value) -> reqwest::Response {
+    app.post_webauthn_login_finish(&serde_json::json!({ "credential": credential }))
+        .await
+}
+
+fn has_auth_cookie(response: &reqwest::Response) -> bool {
+    response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty())
+}
+
+#[tokio::test]
+async fn should_log_in_with_passkey_instead_of_password() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    let response = register(&app, &authenticator, "none", false).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = start_login(&app, serde_json::json!({ "email": random_email })).await;
+    assert_eq!(options.public_key.allow_credentials.len(), 1);
+    assert_eq!(options.public_key.user_verification, "required");
+
+    let response = finish_login(&app, authenticator.get(&options)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(has_auth_cookie(&response));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_log_in_with_discoverable_passkey() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    let response = register(&app, &authenticator, "packed", false).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.post_logout().await;
+
+    // No email, the browser picks from the passkeys it holds for this site
+    let options = start_login(&app, serde_json::json!({})).await;
+    assert!(options.public_key.allow_credentials.is_empty());
+
+    let response = finish_login(&app, authenticator.get(&options)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(has_auth_cookie(&response));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_complete_2fa_with_passkey() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    let response = register(&app, &authenticator, "none", true).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.post_logout().await;
+
+    let response = app.login(&random_email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_response = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(login_response.method, "webauthn");
+
+    let options = start_login(
+        &app,
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_response.login_attempt_id,
+        }),
+    )
+    .await;
+    assert_eq!(options.public_key.user_verification, "discouraged");
+
+    // The password was checked already, the passkey only has to be present
+    authenticator.user_verified = false;
+
+    let response = finish_login(&app, authenticator.get(&options)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(has_auth_cookie(&response));
+
+    // The login attempt is used up
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_response.login_attempt_id,
+    });
+    let response = app.post_webauthn_login_start(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_not_verified_on_passwordless_login() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator, "none", false).await;
+    app.post_logout().await;
+
+    authenticator.user_verified = false;
+
+    let options = start_login(&app, serde_json::json!({ "email": random_email })).await;
+    let response = finish_login(&app, authenticator.get(&options)).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!has_auth_cookie(&response));
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_assertion_is_replayed() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator, "none", false).await;
+    app.post_logout().await;
+
+    let options = start_login(&app, serde_json::json!({ "email": random_email })).await;
+    let credential = authenticator.get(&options);
+
+    let response = finish_login(&app, credential.clone()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = finish_login(&app, credential).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_ceremony_ran_on_another_origin() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator, "none", false).await;
+    app.post_logout().await;
+
+    authenticator.origin = "https://phishing.example".to_owned();
+
+    let options = start_login(&app, serde_json::json!({ "email": random_email })).await;
+    let response = finish_login(&app, authenticator.get(&options)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_passkey_already_registered() {
+    let app = TestApp::new().await;
+
+    let random_email = TestApp::get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let authenticator = SoftwareAuthenticator::new();
+    let response = register(&app, &authenticator, "none", false).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = register(&app, &authenticator, "none", false).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.cleanup_test().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup_test().await;
+}